@@ -1,27 +1,90 @@
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use chrono::Timelike;
 use tokio::sync::Mutex;
+use tokio::sync::Notify;
 use tokio::sync::RwLock;
 
 use crate::config::types::SpeedScheduleEntry;
 
-const MAX_BURST_BYTES: u64 = 131_072; // 128KB = 2x chunk size
+pub(crate) const MAX_BURST_BYTES: u64 = 131_072; // 128KB = 2x chunk size
 
+/// How often the limiter re-evaluates the speed schedule against the clock.
+pub(crate) const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The limit (bytes/sec, `None` = unlimited) that applies at `at`.
+///
+/// A window covers `start_hour` up to but not including `end_hour`, and wraps
+/// past midnight when `end_hour < start_hour` (22 → 6 is 22:00–05:59). When
+/// windows overlap the shortest one wins, and of equally long windows the
+/// later entry wins. Outside every window `base_limit` applies.
+pub fn active_limit(
+    schedule: &[SpeedScheduleEntry],
+    base_limit: Option<u64>,
+    at: &impl Timelike,
+) -> Option<u64> {
+    match active_entry(schedule, at) {
+        Some(entry) => entry.limit,
+        None => base_limit,
+    }
+}
+
+/// Whether the window in effect at `at` is a pause window, picked by the
+/// same rules as [`active_limit`].
+pub fn pause_window_active(schedule: &[SpeedScheduleEntry], at: &impl Timelike) -> bool {
+    active_entry(schedule, at).is_some_and(|entry| entry.pause)
+}
+
+fn active_entry<'a>(
+    schedule: &'a [SpeedScheduleEntry],
+    at: &impl Timelike,
+) -> Option<&'a SpeedScheduleEntry> {
+    let hour = at.hour() as u8;
+    let mut best: Option<(u8, &SpeedScheduleEntry)> = None;
+    for entry in schedule {
+        let matches = if entry.start_hour <= entry.end_hour {
+            hour >= entry.start_hour && hour < entry.end_hour
+        } else {
+            hour >= entry.start_hour || hour < entry.end_hour
+        };
+        if !matches {
+            continue;
+        }
+        let span = (24 + entry.end_hour - entry.start_hour) % 24;
+        if best.is_none_or(|(best_span, _)| span <= best_span) {
+            best = Some((span, entry));
+        }
+    }
+    best.map(|(_, entry)| entry)
+}
+
+/// Token balance and the instant it was last topped up. Kept under one lock
+/// so refill and debit happen atomically across concurrent connections.
+struct Bucket {
+    /// Available tokens in bytes. Negative while acquirers are paying off debt.
+    available: f64,
+    last_refill: Instant,
+}
+
+/// Shared token bucket capping the aggregate rate of every connection that
+/// acquires from it. Tokens accrue continuously (`elapsed * limit`) on each
+/// acquire, so throughput stays flat instead of arriving in 1-second bursts.
 pub struct BandwidthLimiter {
-    /// Global limit in bytes/sec. 0 means unlimited.
+    /// Limit in effect in bytes/sec, schedule applied. 0 means unlimited.
     limit: AtomicU64,
-    /// Available token balance (bytes).
-    available: Mutex<f64>,
-    /// Timestamp of last refill.
-    last_refill: Mutex<Instant>,
+    bucket: Mutex<Bucket>,
     /// Maximum burst allowance in bytes.
     max_burst: u64,
     /// Schedule entries for time-of-day limits.
     schedule: RwLock<Vec<SpeedScheduleEntry>>,
     /// Base limit from config (used when no schedule entry matches).
     base_limit: AtomicU64,
+    /// When `limit` was last resolved from the schedule; `None` forces a
+    /// re-check on the next acquire.
+    schedule_checked: std::sync::Mutex<Option<Instant>>,
+    /// Wakes acquirers waiting out a deficit so they re-time it at the new rate.
+    limit_changed: Notify,
 }
 
 impl BandwidthLimiter {
@@ -30,15 +93,20 @@ impl BandwidthLimiter {
         let limit_val = limit.unwrap_or(0);
         Self {
             limit: AtomicU64::new(limit_val),
-            available: Mutex::new(MAX_BURST_BYTES as f64),
-            last_refill: Mutex::new(Instant::now()),
+            bucket: Mutex::new(Bucket {
+                available: MAX_BURST_BYTES as f64,
+                last_refill: Instant::now(),
+            }),
             max_burst: MAX_BURST_BYTES,
             schedule: RwLock::new(schedule),
             base_limit: AtomicU64::new(limit_val),
+            schedule_checked: std::sync::Mutex::new(None),
+            limit_changed: Notify::new(),
         }
     }
 
-    /// Wait until `bytes` worth of tokens are available.
+    /// Wait until `bytes` worth of tokens are available. Call this before
+    /// writing the bytes so the cap applies to data actually committed.
     /// Returns immediately if limit is 0 (unlimited).
     pub async fn acquire(&self, bytes: u64) {
         let effective_limit = self.current_limit().await;
@@ -46,34 +114,45 @@ impl BandwidthLimiter {
             return;
         }
 
-        // Update the active limit (schedule may have changed it)
-        self.limit.store(effective_limit, Ordering::Relaxed);
-
         // Refill tokens and check availability
-        let sleep_duration = {
-            let mut available = self.available.lock().await;
-            let mut last_refill = self.last_refill.lock().await;
-
-            let elapsed = last_refill.elapsed().as_secs_f64();
-            *available += elapsed * effective_limit as f64;
-            if *available > self.max_burst as f64 {
-                *available = self.max_burst as f64;
-            }
-            *last_refill = Instant::now();
+        let mut deficit = {
+            let mut bucket = self.bucket.lock().await;
+
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.available =
+                (bucket.available + elapsed * effective_limit as f64).min(self.max_burst as f64);
+            bucket.last_refill = now;
 
-            if *available >= bytes as f64 {
-                *available -= bytes as f64;
+            if bucket.available >= bytes as f64 {
+                bucket.available -= bytes as f64;
                 return;
             }
 
             // Not enough tokens — deduct fully (go negative) so concurrent
             // acquirers see the debt and queue behind us.
-            let deficit = bytes as f64 - *available;
-            *available -= bytes as f64;
-            deficit / effective_limit as f64
+            let deficit = bytes as f64 - bucket.available;
+            bucket.available -= bytes as f64;
+            deficit
         };
 
-        tokio::time::sleep(std::time::Duration::from_secs_f64(sleep_duration)).await;
+        // Wait the deficit out, re-timing the remainder whenever the limit
+        // changes so a raised (or removed) cap applies to waiters at once
+        let mut rate = effective_limit as f64;
+        loop {
+            let started = Instant::now();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs_f64(deficit / rate)) => return,
+                _ = self.limit_changed.notified() => {
+                    deficit -= started.elapsed().as_secs_f64() * rate;
+                    let limit = self.current_limit().await;
+                    if limit == 0 || deficit <= 0.0 {
+                        return;
+                    }
+                    rate = limit as f64;
+                }
+            }
+        }
     }
 
     /// Dynamically update the bandwidth limit (bytes/sec). 0 or None = unlimited.
@@ -81,42 +160,51 @@ impl BandwidthLimiter {
         let val = limit.unwrap_or(0);
         self.base_limit.store(val, Ordering::Relaxed);
         self.limit.store(val, Ordering::Relaxed);
+        self.recheck_schedule();
+        self.limit_changed.notify_waiters();
     }
 
     /// Update the speed schedule entries.
     pub async fn set_schedule(&self, schedule: Vec<SpeedScheduleEntry>) {
         let mut guard = self.schedule.write().await;
         *guard = schedule;
+        self.recheck_schedule();
+        self.limit_changed.notify_waiters();
     }
 
-    /// Resolve the effective limit: check schedule first, fall back to base limit.
-    async fn current_limit(&self) -> u64 {
-        let schedule = self.schedule.read().await;
-        if schedule.is_empty() {
-            return self.base_limit.load(Ordering::Relaxed);
-        }
+    /// Whether the schedule has a pause window in effect at `at`.
+    pub async fn paused_at(&self, at: &impl Timelike) -> bool {
+        pause_window_active(&self.schedule.read().await, at)
+    }
 
-        let current_hour = chrono::Local::now().hour() as u8;
-        for entry in schedule.iter() {
-            let matches = if entry.start_hour <= entry.end_hour {
-                current_hour >= entry.start_hour && current_hour < entry.end_hour
-            } else {
-                // Wraps midnight: e.g. start=22, end=6
-                current_hour >= entry.start_hour || current_hour < entry.end_hour
-            };
-            if matches {
-                return entry.limit.unwrap_or(0);
+    fn recheck_schedule(&self) {
+        *self.schedule_checked.lock().unwrap() = None;
+    }
+
+    /// The effective limit, re-resolved from the schedule at most once per
+    /// `SCHEDULE_CHECK_INTERVAL`.
+    async fn current_limit(&self) -> u64 {
+        let due = {
+            let mut checked = self.schedule_checked.lock().unwrap();
+            let due = checked.is_none_or(|at| at.elapsed() >= SCHEDULE_CHECK_INTERVAL);
+            if due {
+                *checked = Some(Instant::now());
             }
+            due
+        };
+        if due {
+            let base = self.base_limit.load(Ordering::Relaxed);
+            let schedule = self.schedule.read().await;
+            let limit = active_limit(&schedule, (base > 0).then_some(base), &chrono::Local::now());
+            self.limit.store(limit.unwrap_or(0), Ordering::Relaxed);
         }
-
-        self.base_limit.load(Ordering::Relaxed)
+        self.limit.load(Ordering::Relaxed)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
 
     #[tokio::test]
     async fn unlimited_returns_immediately() {
@@ -165,6 +253,28 @@ mod tests {
         assert!(start.elapsed() < Duration::from_millis(50));
     }
 
+    #[tokio::test]
+    async fn raised_limit_wakes_waiting_acquirer() {
+        use std::sync::Arc;
+        let limiter = Arc::new(BandwidthLimiter::new(Some(10_000), vec![]));
+        limiter.acquire(MAX_BURST_BYTES).await; // drain burst
+
+        // 100KB at 10 KB/s would wait ~10s; raising the cap shortens that
+        let start = Instant::now();
+        let waiter = {
+            let lim = limiter.clone();
+            tokio::spawn(async move { lim.acquire(100_000).await })
+        };
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        limiter.set_limit(Some(1_000_000));
+        waiter.await.unwrap();
+        assert!(
+            start.elapsed() < Duration::from_millis(800),
+            "still waiting at the old rate: {:?}",
+            start.elapsed()
+        );
+    }
+
     #[tokio::test]
     async fn concurrent_acquirers_share_budget() {
         use std::sync::Arc;
@@ -193,6 +303,28 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn refill_is_continuous() {
+        // 100 KB/s in 10KB steps should release one step every ~100ms,
+        // not stall for a second and then release a burst.
+        let limiter = BandwidthLimiter::new(Some(100_000), vec![]);
+        limiter.acquire(MAX_BURST_BYTES).await; // drain burst
+
+        let start = Instant::now();
+        let mut last = start;
+        for _ in 0..10 {
+            limiter.acquire(10_000).await;
+            let gap = last.elapsed();
+            assert!(gap <= Duration::from_millis(250), "stalled for {gap:?}");
+            last = Instant::now();
+        }
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(900),
+            "too fast: {elapsed:?}"
+        );
+    }
+
     #[tokio::test]
     async fn schedule_overrides_base_limit() {
         use chrono::Local;
@@ -202,6 +334,7 @@ mod tests {
             start_hour: current_hour,
             end_hour: current_hour.wrapping_add(1),
             limit: None, // unlimited for this hour
+            pause: false,
         };
         let limiter = BandwidthLimiter::new(Some(1_000), vec![entry]); // base very slow
         limiter.acquire(MAX_BURST_BYTES).await; // drain burst
@@ -210,4 +343,77 @@ mod tests {
         limiter.acquire(1_000_000).await;
         assert!(start.elapsed() < Duration::from_millis(50));
     }
+
+    fn window(start_hour: u8, end_hour: u8, limit: Option<u64>) -> SpeedScheduleEntry {
+        SpeedScheduleEntry {
+            start_hour,
+            end_hour,
+            limit,
+            pause: false,
+        }
+    }
+
+    fn at(hour: u32) -> chrono::NaiveTime {
+        chrono::NaiveTime::from_hms_opt(hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn active_limit_wraps_past_midnight() {
+        let schedule = [window(22, 6, Some(50_000))];
+        for hour in [22, 23, 0, 3, 5] {
+            assert_eq!(
+                active_limit(&schedule, None, &at(hour)),
+                Some(50_000),
+                "{hour}:30"
+            );
+        }
+        for hour in [6, 12, 21] {
+            assert_eq!(active_limit(&schedule, None, &at(hour)), None, "{hour}:30");
+        }
+    }
+
+    #[test]
+    fn active_limit_overlap_prefers_narrowest_then_last() {
+        let schedule = [
+            window(0, 0, Some(1)),         // empty window, never matches
+            window(8, 20, Some(100_000)),  // working day
+            window(12, 13, Some(10_000)),  // lunch, inside the working day
+            window(18, 2, Some(200_000)),  // evening, overlaps the day's end
+            window(19, 20, Some(300_000)), // same span as an hour of the evening
+            window(19, 20, Some(400_000)), // ...listed later, so it wins
+        ];
+        assert_eq!(active_limit(&schedule, None, &at(9)), Some(100_000));
+        assert_eq!(active_limit(&schedule, None, &at(12)), Some(10_000));
+        assert_eq!(active_limit(&schedule, None, &at(18)), Some(200_000));
+        assert_eq!(active_limit(&schedule, None, &at(19)), Some(400_000));
+        assert_eq!(active_limit(&schedule, None, &at(23)), Some(200_000));
+    }
+
+    #[test]
+    fn active_limit_without_match_uses_base() {
+        let schedule = [window(1, 5, Some(10_000))];
+        assert_eq!(active_limit(&schedule, None, &at(12)), None);
+        assert_eq!(active_limit(&schedule, Some(7_000), &at(12)), Some(7_000));
+        assert_eq!(active_limit(&[], Some(7_000), &at(3)), Some(7_000));
+        // A window with no limit lifts the base limit while it is active
+        assert_eq!(
+            active_limit(&[window(1, 5, None)], Some(7_000), &at(3)),
+            None
+        );
+    }
+
+    #[test]
+    fn pause_window_follows_the_winning_entry() {
+        let quiet = SpeedScheduleEntry {
+            pause: true,
+            ..window(9, 17, None)
+        };
+        let schedule = vec![quiet, window(12, 13, Some(10_000))];
+        assert!(pause_window_active(&schedule, &at(9)));
+        assert!(pause_window_active(&schedule, &at(16)));
+        // The narrower throttle window wins over the pause window
+        assert!(!pause_window_active(&schedule, &at(12)));
+        assert!(!pause_window_active(&schedule, &at(17)));
+        assert!(!pause_window_active(&[], &at(9)));
+    }
 }
@@ -1,6 +1,21 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::engine::download::{MIN_PROGRESS_INTERVAL_MS, PROGRESS_INTERVAL_MS};
+use crate::engine::multi::{MIN_CHUNK_SIZE, MIN_CHUNK_SIZE_FLOOR};
+use crate::engine::retry::RetryPolicy;
+use crate::engine::speed::{SPEED_SMOOTHING, SPEED_SMOOTHING_RANGE};
+use crate::types::{CollisionStrategy, CraneError, DedupMode, FtpMode};
+
+/// Allowed `downloads.default_connections`.
+pub const CONNECTIONS_RANGE: std::ops::RangeInclusive<u32> = 1..=64;
+/// Allowed `downloads.max_concurrent`.
+pub const MAX_CONCURRENT_RANGE: std::ops::RangeInclusive<u32> = 1..=100;
+/// Slowest `downloads.bandwidth_limit` accepted, in bytes/sec (1 KB/s).
+pub const MIN_BANDWIDTH_LIMIT: u64 = 1024;
+/// Largest `general.file_mode`: permission, setuid/setgid and sticky bits.
+pub const MAX_FILE_MODE: u32 = 0o7777;
+
 // ─── Enums ──────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
@@ -80,6 +95,9 @@ pub struct GeneralConfig {
     pub notification_level: NotificationLevel,
     pub language: String,
     pub auto_update: bool,
+    /// Unix permission bits for completed downloads, e.g. `0o600` (384);
+    /// `None` leaves them to the umask. Ignored on Windows.
+    pub file_mode: Option<u32>,
 }
 
 impl Default for GeneralConfig {
@@ -95,6 +113,7 @@ impl Default for GeneralConfig {
             notification_level: NotificationLevel::All,
             language: "en".to_string(),
             auto_update: true,
+            file_mode: None,
         }
     }
 }
@@ -103,31 +122,119 @@ impl Default for GeneralConfig {
 #[serde(default)]
 pub struct DownloadsConfig {
     pub default_connections: u32,
+    /// Combined connections allowed to one host across active downloads;
+    /// `None` means unlimited.
+    pub max_connections_per_host: Option<u32>,
     pub max_concurrent: u32,
     pub bandwidth_limit: Option<u64>,
+    /// Restart downloads interrupted by a crash or force-quit on launch.
+    /// Downloads the user paused are never resumed automatically.
     pub auto_resume: bool,
     pub large_file_threshold: Option<u64>,
+    pub retry_policy: RetryPolicy,
+    /// Where chunk files are staged; empty/`None` means next to the destination.
+    pub temp_dir: Option<String>,
+    /// Write chunks in place into a pre-allocated file instead of merging
+    /// chunk files when the download finishes.
+    pub segmented_writes: bool,
+    /// Verify in-place downloads with several concurrent readers instead
+    /// of one sequential pass over the file.
+    pub parallel_verify: bool,
+    /// Follow HTML download pages (meta refresh or a lone download link)
+    /// to the file they point at.
+    pub follow_html_refresh: bool,
+    /// Command run after each successful download, e.g.
+    /// `unzip -o {path} -d {dir}`. Placeholders: `{path}`, `{filename}`,
+    /// `{dir}`, `{url}`, `{id}`. Run directly, not through a shell.
+    pub on_complete_command: Option<String>,
+    /// Milliseconds between progress updates; longer saves wakeups on
+    /// battery, shorter makes the UI more responsive. At least 50.
+    pub progress_interval_ms: u64,
+    /// Weight of each new sample in the displayed speed and ETA; lower is
+    /// steadier, 1.0 shows raw figures. Between 0.01 and 1.0.
+    pub speed_smoothing: f64,
+    /// Bytes each connection must have to itself before a file is split
+    /// further; raise it on high-latency links, lower it for small files.
+    /// At least 4096.
+    pub min_chunk_size: u64,
+    /// Milliseconds between starting each connection of a download, for
+    /// servers that block bursts of connections. 0 starts them together.
+    pub connection_stagger_ms: u64,
+    /// Completed and failed downloads kept in the list; older ones are
+    /// pruned as new ones finish. `None` keeps everything.
+    pub max_history: Option<u32>,
+    /// Also delete the files of pruned downloads instead of leaving them
+    /// on disk.
+    pub delete_pruned_files: bool,
+    /// Largest file, in bytes, that may be added. Files of unknown size are
+    /// always accepted. `None` means no limit.
+    pub max_file_size: Option<u64>,
+    /// Warn while a download runs with fewer than this many bytes free on
+    /// its save filesystem. `None` disables the check.
+    pub low_disk_space_threshold: Option<u64>,
+    /// Also pause a download when the low-space warning fires.
+    pub pause_on_low_disk_space: bool,
+    /// Re-queue failed downloads automatically.
+    pub auto_retry: AutoRetryConfig,
 }
 
 impl Default for DownloadsConfig {
     fn default() -> Self {
         Self {
             default_connections: 8,
+            max_connections_per_host: None,
             max_concurrent: 3,
             bandwidth_limit: None,
             auto_resume: true,
             large_file_threshold: None,
+            retry_policy: RetryPolicy::default(),
+            temp_dir: None,
+            segmented_writes: false,
+            parallel_verify: false,
+            follow_html_refresh: false,
+            on_complete_command: None,
+            progress_interval_ms: PROGRESS_INTERVAL_MS,
+            speed_smoothing: SPEED_SMOOTHING,
+            min_chunk_size: MIN_CHUNK_SIZE,
+            connection_stagger_ms: 0,
+            max_history: None,
+            delete_pruned_files: false,
+            max_file_size: None,
+            low_disk_space_threshold: None,
+            pause_on_low_disk_space: false,
+            auto_retry: AutoRetryConfig::default(),
         }
     }
 }
 
+impl DownloadsConfig {
+    /// Chunk staging directory, or `None` when unset or blank.
+    pub fn temp_dir_path(&self) -> Option<std::path::PathBuf> {
+        self.temp_dir
+            .as_deref()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(std::path::PathBuf::from)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct FileOrgConfig {
     pub auto_categorize: bool,
     pub date_subfolders: bool,
     pub duplicate_handling: DuplicateAction,
+    /// Save folder per file category (`"video"`, `"documents"`, ...).
     pub category_folders: HashMap<String, String>,
+    /// Check finished downloads' categories against their file content,
+    /// fixing `Other` and categories from a mislabelled MIME type.
+    pub sniff_category: bool,
+    /// Skip a download when its destination already holds a file of the
+    /// same size (and hash, when one is known).
+    pub skip_if_downloaded: bool,
+    /// Replace finished downloads whose content matches an earlier one
+    /// with a link to it.
+    pub dedup: DedupMode,
 }
 
 impl Default for FileOrgConfig {
@@ -137,16 +244,99 @@ impl Default for FileOrgConfig {
             date_subfolders: false,
             duplicate_handling: DuplicateAction::Ask,
             category_folders: HashMap::new(),
+            sniff_category: false,
+            skip_if_downloaded: false,
+            dedup: DedupMode::Off,
         }
     }
 }
 
+impl FileOrgConfig {
+    /// How the queue treats an existing file at a new download's path.
+    /// `Ask` overwrites, as before, until the UI can prompt.
+    pub fn collision_strategy(&self) -> CollisionStrategy {
+        match self.duplicate_handling {
+            DuplicateAction::Ask => CollisionStrategy::Overwrite,
+            DuplicateAction::Rename => CollisionStrategy::Rename,
+            DuplicateAction::Overwrite => CollisionStrategy::Overwrite,
+            DuplicateAction::Skip => CollisionStrategy::Skip,
+        }
+    }
+
+    /// Category folders with blank entries dropped and a leading `~`
+    /// expanded to the home directory.
+    pub fn category_folder_paths(&self) -> HashMap<String, std::path::PathBuf> {
+        self.category_folders
+            .iter()
+            .filter_map(|(category, dir)| {
+                let dir = dir.trim();
+                if dir.is_empty() {
+                    return None;
+                }
+                let path = match (dir.strip_prefix('~'), dirs::home_dir()) {
+                    (Some(rest), Some(home))
+                        if rest.is_empty() || rest.starts_with(['/', '\\']) =>
+                    {
+                        home.join(rest.trim_start_matches(['/', '\\']))
+                    }
+                    _ => std::path::PathBuf::from(dir),
+                };
+                Some((category.clone(), path))
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct NetworkConfig {
     pub proxy: ProxyConfig,
     pub user_agent: Option<String>,
+    /// User agents rotated round-robin across downloads that don't set
+    /// their own; empty sends the built-in agent.
+    pub user_agents: Vec<String>,
     pub speed_schedule: Vec<SpeedScheduleEntry>,
+    /// HTTP gateway for `ipfs://` links; empty/`None` uses `https://ipfs.io`.
+    pub ipfs_gateway: Option<String>,
+    pub timeouts: TimeoutConfig,
+    /// Negotiate HTTP/2 (h2c on plain http) so chunks share a connection.
+    pub prefer_http2: bool,
+    /// Redirects followed before a request fails; `None` uses the default (10).
+    pub max_redirects: Option<u32>,
+    /// FTP data connection mode; switch to active for servers that can't
+    /// do passive.
+    pub ftp_mode: FtpMode,
+}
+
+/// Automatic retries of failed downloads. Cancelled downloads are never
+/// retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoRetryConfig {
+    /// Times a failed download is re-queued before it stays failed; `0`
+    /// disables auto-retry.
+    pub max_retries: u32,
+    /// Seconds to wait after a failure before re-queueing.
+    pub delay_secs: u64,
+}
+
+impl Default for AutoRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            delay_secs: 60,
+        }
+    }
+}
+
+/// Download client timeouts in milliseconds. `None` uses the engine
+/// default (10s connect, 60s read); `0` disables the timeout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub connect_ms: Option<u64>,
+    /// Longest silence between reads before a connection counts as stalled.
+    pub read_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -157,6 +347,11 @@ pub struct ProxyConfig {
     pub port: Option<u16>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Hosts that bypass the proxy (same syntax as the `NO_PROXY` env var).
+    pub no_proxy: Vec<String>,
+    /// Let a SOCKS5 proxy resolve hostnames (`socks5h://`) instead of looking
+    /// them up locally, so DNS queries don't leak around e.g. Tor.
+    pub socks5_remote_dns: bool,
 }
 
 impl Default for ProxyConfig {
@@ -167,10 +362,44 @@ impl Default for ProxyConfig {
             port: None,
             username: None,
             password: None,
+            no_proxy: Vec::new(),
+            socks5_remote_dns: false,
         }
     }
 }
 
+impl ProxyConfig {
+    /// Proxy URL for the configured mode, with credentials percent-encoded.
+    ///
+    /// Returns `None` for `none`/`system` (reqwest's defaults apply) or when
+    /// no host is set.
+    pub fn proxy_url(&self) -> Option<String> {
+        let scheme = match self.mode {
+            ProxyMode::None | ProxyMode::System => return None,
+            ProxyMode::Http => "http",
+            ProxyMode::Socks5 if self.socks5_remote_dns => "socks5h",
+            ProxyMode::Socks5 => "socks5",
+        };
+        let host = self.host.as_deref().filter(|h| !h.is_empty())?;
+        let default_port = if self.mode == ProxyMode::Http {
+            8080
+        } else {
+            1080
+        };
+
+        let mut url = url::Url::parse(&format!(
+            "{scheme}://{host}:{}",
+            self.port.unwrap_or(default_port)
+        ))
+        .ok()?;
+        if let Some(ref user) = self.username {
+            url.set_username(user).ok()?;
+            url.set_password(self.password.as_deref()).ok()?;
+        }
+        Some(url.to_string())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppearanceConfig {
@@ -201,6 +430,9 @@ pub struct SpeedScheduleEntry {
     pub start_hour: u8,
     pub end_hour: u8,
     pub limit: Option<u64>,
+    /// Pause every download for the length of the window instead of
+    /// throttling them; `limit` is ignored.
+    pub pause: bool,
 }
 
 impl AppConfig {
@@ -209,46 +441,85 @@ impl AppConfig {
     pub fn validate(&mut self) -> Vec<String> {
         let mut warnings = Vec::new();
 
-        // downloads.default_connections: 1..=128
-        if self.downloads.default_connections < 1 {
-            warnings.push(format!(
-                "default_connections was {}, clamped to 1",
-                self.downloads.default_connections
-            ));
-            self.downloads.default_connections = 1;
-        } else if self.downloads.default_connections > 128 {
+        // downloads.default_connections: 1..=64
+        let connections = self.downloads.default_connections;
+        let clamped = connections.clamp(*CONNECTIONS_RANGE.start(), *CONNECTIONS_RANGE.end());
+        if clamped != connections {
             warnings.push(format!(
-                "default_connections was {}, clamped to 128",
-                self.downloads.default_connections
+                "default_connections was {connections}, clamped to {clamped}"
             ));
-            self.downloads.default_connections = 128;
+            self.downloads.default_connections = clamped;
         }
 
-        // downloads.max_concurrent: 1..=20
-        if self.downloads.max_concurrent < 1 {
-            warnings.push(format!(
-                "max_concurrent was {}, clamped to 1",
-                self.downloads.max_concurrent
-            ));
-            self.downloads.max_concurrent = 1;
-        } else if self.downloads.max_concurrent > 20 {
+        // downloads.max_connections_per_host: if Some, min 1
+        if self.downloads.max_connections_per_host == Some(0) {
+            warnings.push("max_connections_per_host was 0, clamped to 1".to_string());
+            self.downloads.max_connections_per_host = Some(1);
+        }
+
+        // downloads.max_concurrent: 1..=100
+        let concurrent = self.downloads.max_concurrent;
+        let clamped = concurrent.clamp(*MAX_CONCURRENT_RANGE.start(), *MAX_CONCURRENT_RANGE.end());
+        if clamped != concurrent {
             warnings.push(format!(
-                "max_concurrent was {}, clamped to 20",
-                self.downloads.max_concurrent
+                "max_concurrent was {concurrent}, clamped to {clamped}"
             ));
-            self.downloads.max_concurrent = 20;
+            self.downloads.max_concurrent = clamped;
         }
 
         // downloads.bandwidth_limit: if Some, min 1024 (1KB/s)
         if let Some(limit) = self.downloads.bandwidth_limit {
-            if limit < 1024 {
+            if limit < MIN_BANDWIDTH_LIMIT {
                 warnings.push(format!(
                     "bandwidth_limit was {limit}, clamped to 1024 (1 KB/s)"
                 ));
-                self.downloads.bandwidth_limit = Some(1024);
+                self.downloads.bandwidth_limit = Some(MIN_BANDWIDTH_LIMIT);
             }
         }
 
+        // downloads.retry_policy.jitter: 0.0..=1.0
+        if let Some(jitter) = self.downloads.retry_policy.jitter {
+            if !(0.0..=1.0).contains(&jitter) {
+                let clamped = jitter.clamp(0.0, 1.0);
+                warnings.push(format!(
+                    "retry_policy.jitter was {jitter}, clamped to {clamped}"
+                ));
+                self.downloads.retry_policy.jitter = Some(clamped);
+            }
+        }
+
+        // downloads.progress_interval_ms: min 50
+        if self.downloads.progress_interval_ms < MIN_PROGRESS_INTERVAL_MS {
+            warnings.push(format!(
+                "progress_interval_ms was {}, clamped to {MIN_PROGRESS_INTERVAL_MS}",
+                self.downloads.progress_interval_ms
+            ));
+            self.downloads.progress_interval_ms = MIN_PROGRESS_INTERVAL_MS;
+        }
+
+        // downloads.speed_smoothing: 0.01..=1.0
+        let smoothing = self.downloads.speed_smoothing;
+        if !SPEED_SMOOTHING_RANGE.contains(&smoothing) {
+            self.downloads.speed_smoothing = if smoothing.is_nan() {
+                SPEED_SMOOTHING
+            } else {
+                smoothing.clamp(*SPEED_SMOOTHING_RANGE.start(), *SPEED_SMOOTHING_RANGE.end())
+            };
+            warnings.push(format!(
+                "speed_smoothing was {smoothing}, clamped to {}",
+                self.downloads.speed_smoothing
+            ));
+        }
+
+        // downloads.min_chunk_size: min 4096
+        if self.downloads.min_chunk_size < MIN_CHUNK_SIZE_FLOOR {
+            warnings.push(format!(
+                "min_chunk_size was {}, clamped to {MIN_CHUNK_SIZE_FLOOR}",
+                self.downloads.min_chunk_size
+            ));
+            self.downloads.min_chunk_size = MIN_CHUNK_SIZE_FLOOR;
+        }
+
         // appearance.window_opacity: 0.1..=1.0
         if self.appearance.window_opacity < 0.1 {
             warnings.push(format!(
@@ -282,6 +553,81 @@ impl AppConfig {
             }
         }
 
+        // general.file_mode: permission bits only
+        if let Some(mode) = self.general.file_mode.filter(|m| *m > MAX_FILE_MODE) {
+            let masked = mode & MAX_FILE_MODE;
+            warnings.push(format!("file_mode was {mode:o}, masked to {masked:o}"));
+            self.general.file_mode = Some(masked);
+        }
+
         warnings
     }
+
+    /// Reject out-of-range download settings instead of clamping them.
+    /// Used for changes coming from the settings UI, where silently
+    /// saving a different value would be surprising.
+    pub fn check_ranges(&self) -> Result<(), CraneError> {
+        let downloads = &self.downloads;
+        if !CONNECTIONS_RANGE.contains(&downloads.default_connections) {
+            return Err(out_of_range(
+                "downloads.default_connections",
+                downloads.default_connections,
+                &CONNECTIONS_RANGE,
+            ));
+        }
+        if !MAX_CONCURRENT_RANGE.contains(&downloads.max_concurrent) {
+            return Err(out_of_range(
+                "downloads.max_concurrent",
+                downloads.max_concurrent,
+                &MAX_CONCURRENT_RANGE,
+            ));
+        }
+        if downloads.max_connections_per_host == Some(0) {
+            return Err(CraneError::Config(
+                "downloads.max_connections_per_host must be at least 1".to_string(),
+            ));
+        }
+        if let Some(limit) = downloads
+            .bandwidth_limit
+            .filter(|l| *l < MIN_BANDWIDTH_LIMIT)
+        {
+            return Err(CraneError::Config(format!(
+                "downloads.bandwidth_limit must be at least {MIN_BANDWIDTH_LIMIT} bytes/sec, got {limit}"
+            )));
+        }
+        if downloads.progress_interval_ms < MIN_PROGRESS_INTERVAL_MS {
+            return Err(CraneError::Config(format!(
+                "downloads.progress_interval_ms must be at least {MIN_PROGRESS_INTERVAL_MS}, got {}",
+                downloads.progress_interval_ms
+            )));
+        }
+        if !SPEED_SMOOTHING_RANGE.contains(&downloads.speed_smoothing) {
+            return Err(CraneError::Config(format!(
+                "downloads.speed_smoothing must be between {} and {}, got {}",
+                SPEED_SMOOTHING_RANGE.start(),
+                SPEED_SMOOTHING_RANGE.end(),
+                downloads.speed_smoothing
+            )));
+        }
+        if downloads.min_chunk_size < MIN_CHUNK_SIZE_FLOOR {
+            return Err(CraneError::Config(format!(
+                "downloads.min_chunk_size must be at least {MIN_CHUNK_SIZE_FLOOR}, got {}",
+                downloads.min_chunk_size
+            )));
+        }
+        if let Some(mode) = self.general.file_mode.filter(|m| *m > MAX_FILE_MODE) {
+            return Err(CraneError::Config(format!(
+                "general.file_mode must be at most {MAX_FILE_MODE:o}, got {mode:o}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn out_of_range(field: &str, value: u32, range: &std::ops::RangeInclusive<u32>) -> CraneError {
+    CraneError::Config(format!(
+        "{field} must be between {} and {}, got {value}",
+        range.start(),
+        range.end()
+    ))
 }
@@ -1,5 +1,5 @@
 use crate::db::Database;
-use crate::types::{ConnectionInfo, ConnectionStatus, CraneError};
+use crate::types::{ConnectionInfo, ConnectionProgress, ConnectionStatus, CraneError};
 use rusqlite::params;
 
 impl Database {
@@ -100,6 +100,49 @@ impl Database {
         Ok(())
     }
 
+    /// Record live per-connection progress, inserting rows that don't exist
+    /// yet. Used by in-place (segmented) downloads, which have no chunk files
+    /// to resume from.
+    pub fn save_connection_progress(
+        &self,
+        download_id: &str,
+        connections: &[ConnectionProgress],
+    ) -> Result<(), CraneError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "INSERT INTO connections (download_id, connection_num, range_start, range_end, downloaded, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT(download_id, connection_num) DO UPDATE SET
+                     range_start = excluded.range_start,
+                     range_end = excluded.range_end,
+                     downloaded = excluded.downloaded,
+                     status = excluded.status",
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        for c in connections {
+            let status = if c.downloaded > c.range_end - c.range_start {
+                ConnectionStatus::Completed
+            } else if c.downloaded > 0 {
+                ConnectionStatus::Active
+            } else {
+                ConnectionStatus::Pending
+            };
+            stmt.execute(params![
+                download_id,
+                c.connection_num as i64,
+                c.range_start as i64,
+                c.range_end as i64,
+                c.downloaded as i64,
+                status.as_str(),
+            ])
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
     /// Update the status of a single connection.
     pub fn update_connection_status(
         &self,
@@ -217,6 +260,46 @@ mod tests {
         assert_eq!(fetched[1].status, ConnectionStatus::Completed);
     }
 
+    #[test]
+    fn test_save_connection_progress_upserts() {
+        let db = setup_db_with_download();
+        let progress = |first: u64, second: u64| {
+            vec![
+                ConnectionProgress {
+                    connection_num: 0,
+                    downloaded: first,
+                    range_start: 0,
+                    range_end: 511,
+                },
+                ConnectionProgress {
+                    connection_num: 1,
+                    downloaded: second,
+                    range_start: 512,
+                    range_end: 1023,
+                },
+            ]
+        };
+
+        // First save inserts the rows
+        db.save_connection_progress("dl-1", &progress(100, 0))
+            .unwrap();
+        let fetched = db.get_connections("dl-1").unwrap();
+        assert_eq!(fetched.len(), 2);
+        assert_eq!(fetched[0].downloaded, 100);
+        assert_eq!(fetched[0].status, ConnectionStatus::Active);
+        assert_eq!(fetched[1].status, ConnectionStatus::Pending);
+
+        // Later saves update them in place
+        db.save_connection_progress("dl-1", &progress(512, 300))
+            .unwrap();
+        let fetched = db.get_connections("dl-1").unwrap();
+        assert_eq!(fetched.len(), 2);
+        assert_eq!(fetched[0].downloaded, 512);
+        assert_eq!(fetched[0].status, ConnectionStatus::Completed);
+        assert_eq!(fetched[1].downloaded, 300);
+        assert_eq!(fetched[1].range_start, 512);
+    }
+
     #[test]
     fn test_cascade_delete_connections() {
         let db = setup_db_with_download();
@@ -1,6 +1,51 @@
 use crate::db::Database;
-use crate::types::{CraneError, Download, DownloadStatus, FileCategory};
+use crate::types::{CraneError, Download, DownloadStatus, FileCategory, Priority};
 use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// Constraints for [`Database::list_downloads_paged`]. Unset fields match
+/// every row.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadFilter {
+    pub status: Option<DownloadStatus>,
+    pub category: Option<FileCategory>,
+    /// Case-insensitive substring of `source_domain`.
+    pub domain: Option<String>,
+    /// Inclusive lower bound on `created_at` (RFC 3339).
+    pub created_after: Option<String>,
+    /// Exclusive upper bound on `created_at` (RFC 3339).
+    pub created_before: Option<String>,
+}
+
+/// One page of [`Database::list_downloads_paged`] results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadPage {
+    pub downloads: Vec<Download>,
+    /// Rows matching the filter across all pages.
+    pub total: u64,
+}
+
+/// Number of downloads in each status.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusCounts {
+    pub pending: u32,
+    pub analyzing: u32,
+    pub queued: u32,
+    pub downloading: u32,
+    pub paused: u32,
+    pub completed: u32,
+    pub failed: u32,
+    pub cancelled: u32,
+}
+
+/// Aggregates over every row, from [`Database::download_totals`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadTotals {
+    pub counts: StatusCounts,
+    /// Sum of `downloaded_size` across all rows.
+    pub downloaded_bytes: u64,
+}
 
 /// Map a SQLite row to a Download struct.
 fn row_to_download(row: &rusqlite::Row) -> Result<Download, CraneError> {
@@ -13,6 +58,9 @@ fn row_to_download(row: &rusqlite::Row) -> Result<Download, CraneError> {
     let resumable_int: i64 = row
         .get(11)
         .map_err(|e| CraneError::Database(e.to_string()))?;
+    let priority_str: String = row
+        .get::<_, String>(27)
+        .map_err(|e| CraneError::Database(e.to_string()))?;
 
     Ok(Download {
         id: row
@@ -86,15 +134,67 @@ fn row_to_download(row: &rusqlite::Row) -> Result<Download, CraneError> {
         headers: row
             .get(24)
             .map_err(|e| CraneError::Database(e.to_string()))?,
+        scheduled_at: row
+            .get(25)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        final_url: row
+            .get(26)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        priority: Priority::from_db_str(&priority_str)?,
+        etag: row
+            .get(28)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        last_modified: row
+            .get(29)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        average_speed: row
+            .get(30)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        peak_speed: row
+            .get(31)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        auth_scheme: row
+            .get(32)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        content_hash: row
+            .get(33)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
+        mirrors: row
+            .get(34)
+            .map_err(|e| CraneError::Database(e.to_string()))?,
     })
 }
 
+/// Escape `\\`, `%` and `_` so `s` matches literally in `LIKE ... ESCAPE '\\'`.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// `LIKE` pattern matching `s` anywhere in the column.
+fn like_contains(s: &str) -> String {
+    format!("%{}%", escape_like(s))
+}
+
+/// Query behind [`Database::get_next_queued`].
+fn next_queued_sql() -> String {
+    format!(
+        "{SELECT_ALL_COLUMNS} WHERE status = 'queued' \
+         ORDER BY CASE priority WHEN 'high' THEN 0 WHEN 'low' THEN 2 ELSE 1 END, \
+         queue_position ASC LIMIT 1"
+    )
+}
+
 const SELECT_ALL_COLUMNS: &str =
     "SELECT id, url, filename, save_path, total_size, downloaded_size, \
      status, error_message, error_code, mime_type, category, resumable, \
      connections, speed, source_domain, referrer, cookies, user_agent, \
      queue_position, retry_count, created_at, started_at, completed_at, \
-     updated_at, headers FROM downloads";
+     updated_at, headers, scheduled_at, final_url, priority, etag, \
+     last_modified, average_speed, peak_speed, auth_scheme, content_hash, \
+     mirrors \
+     FROM downloads";
 
 impl Database {
     /// Insert a new download record.
@@ -106,13 +206,18 @@ impl Database {
                     status, error_message, error_code, mime_type, category,
                     resumable, connections, speed, source_domain, referrer,
                     cookies, user_agent, queue_position, retry_count,
-                    created_at, started_at, completed_at, updated_at, headers
+                    created_at, started_at, completed_at, updated_at, headers,
+                    scheduled_at, final_url, priority, etag, last_modified,
+                    average_speed, peak_speed, auth_scheme, content_hash,
+                    mirrors
                 ) VALUES (
                     ?1, ?2, ?3, ?4, ?5, ?6,
                     ?7, ?8, ?9, ?10, ?11,
                     ?12, ?13, ?14, ?15, ?16,
                     ?17, ?18, ?19, ?20,
-                    ?21, ?22, ?23, ?24, ?25
+                    ?21, ?22, ?23, ?24, ?25,
+                    ?26, ?27, ?28, ?29, ?30,
+                    ?31, ?32, ?33, ?34, ?35
                 )",
                 params![
                     dl.id,
@@ -140,10 +245,21 @@ impl Database {
                     dl.completed_at,
                     dl.created_at, // updated_at = created_at initially
                     dl.headers,
+                    dl.scheduled_at,
+                    dl.final_url,
+                    dl.priority.as_str(),
+                    dl.etag,
+                    dl.last_modified,
+                    dl.average_speed,
+                    dl.peak_speed,
+                    dl.auth_scheme,
+                    dl.content_hash,
+                    dl.mirrors,
                 ],
             )
             .map_err(|e| CraneError::Database(e.to_string()))?;
 
+        self.note_pending_write();
         Ok(())
     }
 
@@ -193,7 +309,127 @@ impl Database {
         Ok(downloads)
     }
 
+    /// List downloads matching `filter`, newest first, skipping `offset`
+    /// rows and returning at most `limit`. The page carries the total
+    /// number of matching rows so callers can render pagination.
+    pub fn list_downloads_paged(
+        &self,
+        filter: &DownloadFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<DownloadPage, CraneError> {
+        let mut clauses: Vec<&str> = Vec::new();
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(ref status) = filter.status {
+            clauses.push("status = ?");
+            values.push(status.as_str().to_string().into());
+        }
+        if let Some(ref category) = filter.category {
+            clauses.push("category = ?");
+            values.push(category.as_str().to_string().into());
+        }
+        if let Some(ref domain) = filter.domain {
+            clauses.push("source_domain LIKE ? ESCAPE '\\'");
+            values.push(like_contains(domain).into());
+        }
+        if let Some(ref after) = filter.created_after {
+            clauses.push("created_at >= ?");
+            values.push(after.clone().into());
+        }
+        if let Some(ref before) = filter.created_before {
+            clauses.push("created_at < ?");
+            values.push(before.clone().into());
+        }
+        let where_sql = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", clauses.join(" AND "))
+        };
+
+        let conn = self.conn();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM downloads{where_sql}"),
+                rusqlite::params_from_iter(values.iter()),
+                |row| row.get(0),
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let sql =
+            format!("{SELECT_ALL_COLUMNS}{where_sql} ORDER BY created_at DESC LIMIT ? OFFSET ?");
+        values.push(i64::from(limit).into());
+        values.push(i64::from(offset).into());
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(values.iter()), |row| {
+                row_to_download(row).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut downloads = Vec::new();
+        for row in rows {
+            downloads.push(row.map_err(|e| CraneError::Database(e.to_string()))?);
+        }
+        Ok(DownloadPage {
+            downloads,
+            total: total as u64,
+        })
+    }
+
     /// Get downloads filtered by status, ordered by queue_position then created_at.
+    /// Case-insensitive substring search over filename, URL and source domain.
+    /// Filename matches rank first (prefix matches ahead of the rest), then
+    /// domain, then URL; ties are broken by newest first.
+    pub fn search_downloads(&self, query: &str) -> Result<Vec<Download>, CraneError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pattern = like_contains(query);
+        let prefix = format!("{}%", escape_like(query));
+
+        let sql = format!(
+            "{SELECT_ALL_COLUMNS} WHERE filename LIKE ?1 ESCAPE '\\' \
+             OR url LIKE ?1 ESCAPE '\\' OR source_domain LIKE ?1 ESCAPE '\\' \
+             ORDER BY CASE \
+                WHEN filename LIKE ?2 ESCAPE '\\' THEN 0 \
+                WHEN filename LIKE ?1 ESCAPE '\\' THEN 1 \
+                WHEN source_domain LIKE ?1 ESCAPE '\\' THEN 2 \
+                ELSE 3 END, created_at DESC"
+        );
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![pattern, prefix], |row| {
+                row_to_download(row).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut downloads = Vec::new();
+        for row in rows {
+            downloads.push(row.map_err(|e| CraneError::Database(e.to_string()))?);
+        }
+        Ok(downloads)
+    }
+
     pub fn get_downloads_by_status(
         &self,
         status: DownloadStatus,
@@ -225,6 +461,89 @@ impl Database {
         Ok(downloads)
     }
 
+    /// Failed downloads retried fewer than `max_retries` times, oldest
+    /// failure first.
+    pub fn get_failed_under_retry_limit(
+        &self,
+        max_retries: u32,
+    ) -> Result<Vec<Download>, CraneError> {
+        let sql = format!(
+            "{SELECT_ALL_COLUMNS} WHERE status = 'failed' AND retry_count < ?1 \
+             ORDER BY updated_at ASC"
+        );
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![max_retries], |row| {
+                row_to_download(row).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut downloads = Vec::new();
+        for row in rows {
+            downloads.push(row.map_err(|e| CraneError::Database(e.to_string()))?);
+        }
+        Ok(downloads)
+    }
+
+    /// Add one to a download's `retry_count`.
+    pub fn increment_retry_count(&self, id: &str) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET retry_count = retry_count + 1, updated_at = ?1 WHERE id = ?2",
+                params![chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Completed, failed and cancelled downloads other than the newest
+    /// `keep`, newest first. Recency is when the download finished, or last
+    /// changed for failures and cancellations.
+    pub fn finished_downloads_beyond(&self, keep: u32) -> Result<Vec<Download>, CraneError> {
+        let sql = format!(
+            "{SELECT_ALL_COLUMNS} WHERE status IN ('completed', 'failed', 'cancelled') \
+             ORDER BY COALESCE(completed_at, updated_at) DESC, created_at DESC \
+             LIMIT -1 OFFSET ?1"
+        );
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![keep], |row| {
+                row_to_download(row).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut downloads = Vec::new();
+        for row in rows {
+            downloads.push(row.map_err(|e| CraneError::Database(e.to_string()))?);
+        }
+        Ok(downloads)
+    }
+
     /// Check whether a download with the given URL already exists in an active
     /// state (pending, analyzing, downloading, queued, or paused).
     pub fn has_active_url(&self, url: &str) -> Result<bool, CraneError> {
@@ -303,6 +622,9 @@ impl Database {
         if rows == 0 {
             return Err(CraneError::NotFound(id.to_string()));
         }
+        if status == DownloadStatus::Pending {
+            self.note_pending_write();
+        }
         Ok(())
     }
 
@@ -341,6 +663,220 @@ impl Database {
         Ok(())
     }
 
+    /// Change the category of a download.
+    pub fn update_category(&self, id: &str, category: &FileCategory) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET category = ?1, updated_at = ?2 WHERE id = ?3",
+                params![category.as_str(), chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Change the queue tier of a download.
+    pub fn update_priority(&self, id: &str, priority: Priority) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET priority = ?1, updated_at = ?2 WHERE id = ?3",
+                params![priority.as_str(), chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Change how many connections a download uses from its next start.
+    pub fn update_connections(&self, id: &str, connections: u32) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET connections = ?1, updated_at = ?2 WHERE id = ?3",
+                params![connections, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Record the `ETag` and `Last-Modified` the download's data now matches.
+    pub fn update_validators(
+        &self,
+        id: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+    ) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET etag = ?1, last_modified = ?2, updated_at = ?3 WHERE id = ?4",
+                params![etag, last_modified, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Record the average and peak speed (bytes/sec) of a finished download.
+    pub fn update_throughput(
+        &self,
+        id: &str,
+        average_speed: f64,
+        peak_speed: f64,
+    ) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET average_speed = ?1, peak_speed = ?2, updated_at = ?3 WHERE id = ?4",
+                params![average_speed, peak_speed, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Point a download at a different URL for the same file, such as
+    /// another mirror. Its resolved URL is cleared.
+    pub fn update_url(&self, id: &str, url: &str) -> Result<(), CraneError> {
+        let source_domain = url::Url::parse(url)
+            .ok()
+            .and_then(|u| crate::network::source_domain(&u));
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET url = ?1, source_domain = ?2, final_url = NULL, \
+                 updated_at = ?3 WHERE id = ?4",
+                params![url, source_domain, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Replace the fallback mirrors left for a download (cleared when
+    /// `mirrors` is empty).
+    pub fn update_mirrors(&self, id: &str, mirrors: &[String]) -> Result<(), CraneError> {
+        let json = (!mirrors.is_empty())
+            .then(|| serde_json::to_string(mirrors))
+            .transpose()
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET mirrors = ?1, updated_at = ?2 WHERE id = ?3",
+                params![json, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Record where a download's URL resolved to after redirects.
+    pub fn update_final_url(&self, id: &str, final_url: Option<&str>) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET final_url = ?1, updated_at = ?2 WHERE id = ?3",
+                params![final_url, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Record the SHA-256 of a download's finished file.
+    pub fn update_content_hash(&self, id: &str, content_hash: &str) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET content_hash = ?1, updated_at = ?2 WHERE id = ?3",
+                params![content_hash, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// The earliest completed download other than `exclude_id` whose file
+    /// had `content_hash`.
+    pub fn find_completed_by_content_hash(
+        &self,
+        content_hash: &str,
+        exclude_id: &str,
+    ) -> Result<Option<Download>, CraneError> {
+        let sql = format!(
+            "{SELECT_ALL_COLUMNS} WHERE content_hash = ?1 AND status = 'completed' \
+             AND id != ?2 ORDER BY completed_at ASC LIMIT 1"
+        );
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut rows = stmt
+            .query_map(params![content_hash, exclude_id], |row| {
+                row_to_download(row).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        match rows.next() {
+            Some(row) => Ok(Some(row.map_err(|e| CraneError::Database(e.to_string()))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Point a download at a new destination file.
+    pub fn update_save_path(&self, id: &str, save_path: &str) -> Result<(), CraneError> {
+        let rows = self
+            .conn()
+            .execute(
+                "UPDATE downloads SET save_path = ?1, updated_at = ?2 WHERE id = ?3",
+                params![save_path, chrono::Utc::now().to_rfc3339(), id],
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        if rows == 0 {
+            return Err(CraneError::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Update the queue position of a download.
     pub fn update_queue_position(&self, id: &str, position: Option<u32>) -> Result<(), CraneError> {
         let rows = self
@@ -361,11 +897,10 @@ impl Database {
         Ok(())
     }
 
-    /// Get the next queued download (lowest queue_position).
+    /// Get the next queued download: highest priority first, then lowest
+    /// queue_position.
     pub fn get_next_queued(&self) -> Result<Option<Download>, CraneError> {
-        let sql = format!(
-            "{SELECT_ALL_COLUMNS} WHERE status = 'queued' ORDER BY queue_position ASC LIMIT 1"
-        );
+        let sql = next_queued_sql();
         let conn = self.conn();
         let mut stmt = conn
             .prepare(&sql)
@@ -405,13 +940,53 @@ impl Database {
         Ok(count as u32)
     }
 
-    /// Count downloads that are NOT in a terminal state (completed or failed).
-    /// This includes: pending, analyzing, downloading, paused, queued.
+    /// Per-status row counts and total downloaded bytes, in one grouped query.
+    pub fn download_totals(&self) -> Result<DownloadTotals, CraneError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT status, COUNT(*), COALESCE(SUM(downloaded_size), 0)
+                 FROM downloads GROUP BY status",
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut totals = DownloadTotals::default();
+        for row in rows {
+            let (status, count, bytes) = row.map_err(|e| CraneError::Database(e.to_string()))?;
+            let count = count as u32;
+            let counts = &mut totals.counts;
+            match DownloadStatus::from_db_str(&status)? {
+                DownloadStatus::Pending => counts.pending = count,
+                DownloadStatus::Analyzing => counts.analyzing = count,
+                DownloadStatus::Queued => counts.queued = count,
+                DownloadStatus::Downloading => counts.downloading = count,
+                DownloadStatus::Paused => counts.paused = count,
+                DownloadStatus::Completed => counts.completed = count,
+                DownloadStatus::Failed => counts.failed = count,
+                DownloadStatus::Cancelled => counts.cancelled = count,
+            }
+            totals.downloaded_bytes += bytes as u64;
+        }
+        Ok(totals)
+    }
+
+    /// Count downloads that are NOT in a terminal state (completed, failed or
+    /// cancelled). This includes: pending, analyzing, downloading, paused,
+    /// queued.
     pub fn count_non_terminal_downloads(&self) -> Result<u32, CraneError> {
         let count: i64 = self
             .conn()
             .query_row(
-                "SELECT COUNT(*) FROM downloads WHERE status NOT IN ('completed', 'failed')",
+                "SELECT COUNT(*) FROM downloads WHERE status NOT IN ('completed', 'failed', 'cancelled')",
                 [],
                 |row| row.get(0),
             )
@@ -428,11 +1003,11 @@ impl Database {
         Ok(count as u64)
     }
 
-    /// Find the most recent failed download for a given URL.
-    /// Returns `None` if no failed download exists for this URL.
+    /// Find the most recent failed or cancelled download for a given URL.
+    /// Returns `None` if no such download exists for this URL.
     pub fn find_failed_download(&self, url: &str) -> Result<Option<Download>, CraneError> {
         let sql = format!(
-            "{SELECT_ALL_COLUMNS} WHERE url = ?1 AND status = 'failed' \
+            "{SELECT_ALL_COLUMNS} WHERE url = ?1 AND status IN ('failed', 'cancelled') \
              ORDER BY created_at DESC LIMIT 1"
         );
         let conn = self.conn();
@@ -559,6 +1134,16 @@ mod tests {
             started_at: None,
             completed_at: None,
             updated_at: "2026-01-01T00:00:00Z".to_string(),
+            scheduled_at: None,
+            final_url: None,
+            priority: Priority::Normal,
+            etag: None,
+            last_modified: None,
+            average_speed: None,
+            peak_speed: None,
+            auth_scheme: None,
+            content_hash: None,
+            mirrors: None,
         }
     }
 
@@ -617,6 +1202,274 @@ mod tests {
         assert_eq!(list[2].id, "dl-1");
     }
 
+    /// Insert `n` downloads created one day apart, alternating status and category.
+    fn insert_paged_fixture(db: &Database, n: u32) {
+        for i in 0..n {
+            let status = if i % 2 == 0 {
+                DownloadStatus::Completed
+            } else {
+                DownloadStatus::Failed
+            };
+            let mut dl = make_test_download(&format!("dl-{i:02}"), status);
+            dl.created_at = format!("2026-01-{:02}T00:00:00Z", i + 1);
+            if i % 3 == 0 {
+                dl.category = FileCategory::Video;
+                dl.source_domain = Some("cdn.videos.net".to_string());
+            }
+            db.insert_download(&dl).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_list_paged_status_filter() {
+        let db = Database::open_in_memory().unwrap();
+        insert_paged_fixture(&db, 10);
+
+        let filter = DownloadFilter {
+            status: Some(DownloadStatus::Failed),
+            ..Default::default()
+        };
+        let page = db.list_downloads_paged(&filter, 0, 100).unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(page.downloads.len(), 5);
+        assert!(page
+            .downloads
+            .iter()
+            .all(|d| d.status == DownloadStatus::Failed));
+        // Newest first
+        assert_eq!(page.downloads[0].id, "dl-09");
+        assert_eq!(page.downloads[4].id, "dl-01");
+    }
+
+    #[test]
+    fn test_list_paged_category_and_domain_filter() {
+        let db = Database::open_in_memory().unwrap();
+        insert_paged_fixture(&db, 10);
+
+        let filter = DownloadFilter {
+            category: Some(FileCategory::Video),
+            ..Default::default()
+        };
+        let page = db.list_downloads_paged(&filter, 0, 100).unwrap();
+        let ids: Vec<&str> = page.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["dl-09", "dl-06", "dl-03", "dl-00"]);
+
+        let filter = DownloadFilter {
+            domain: Some("VIDEOS".to_string()),
+            status: Some(DownloadStatus::Completed),
+            ..Default::default()
+        };
+        let page = db.list_downloads_paged(&filter, 0, 100).unwrap();
+        let ids: Vec<&str> = page.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["dl-06", "dl-00"]);
+
+        // LIKE wildcards in the needle are matched literally
+        let filter = DownloadFilter {
+            domain: Some("%".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(db.list_downloads_paged(&filter, 0, 100).unwrap().total, 0);
+    }
+
+    #[test]
+    fn test_update_category_moves_between_filters() {
+        let db = Database::open_in_memory().unwrap();
+        insert_paged_fixture(&db, 10);
+
+        db.update_category("dl-01", &FileCategory::Video).unwrap();
+        db.update_category("dl-03", &FileCategory::Audio).unwrap();
+
+        assert_eq!(
+            db.get_download("dl-01").unwrap().category,
+            FileCategory::Video
+        );
+        let by_category = |category: FileCategory| -> Vec<String> {
+            let filter = DownloadFilter {
+                category: Some(category),
+                ..Default::default()
+            };
+            let page = db.list_downloads_paged(&filter, 0, 100).unwrap();
+            page.downloads.into_iter().map(|d| d.id).collect()
+        };
+        assert_eq!(
+            by_category(FileCategory::Video),
+            vec!["dl-09", "dl-06", "dl-01", "dl-00"]
+        );
+        assert_eq!(by_category(FileCategory::Audio), vec!["dl-03"]);
+
+        let integrity: String = db
+            .conn()
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(integrity, "ok");
+
+        assert!(matches!(
+            db.update_category("missing", &FileCategory::Video),
+            Err(CraneError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_paged_created_at_range() {
+        let db = Database::open_in_memory().unwrap();
+        insert_paged_fixture(&db, 10);
+
+        let filter = DownloadFilter {
+            created_after: Some("2026-01-03T00:00:00Z".to_string()),
+            created_before: Some("2026-01-06T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let page = db.list_downloads_paged(&filter, 0, 100).unwrap();
+        let ids: Vec<&str> = page.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["dl-04", "dl-03", "dl-02"]);
+    }
+
+    #[test]
+    fn test_list_paged_offset_limit_boundaries() {
+        let db = Database::open_in_memory().unwrap();
+        insert_paged_fixture(&db, 10);
+        let all = DownloadFilter::default();
+
+        let first = db.list_downloads_paged(&all, 0, 4).unwrap();
+        assert_eq!(first.total, 10);
+        let ids: Vec<&str> = first.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["dl-09", "dl-08", "dl-07", "dl-06"]);
+
+        // Last, partial page
+        let last = db.list_downloads_paged(&all, 8, 4).unwrap();
+        let ids: Vec<&str> = last.downloads.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["dl-01", "dl-00"]);
+
+        // Past the end
+        let past = db.list_downloads_paged(&all, 10, 4).unwrap();
+        assert!(past.downloads.is_empty());
+        assert_eq!(past.total, 10);
+
+        // Zero limit still reports the total
+        let none = db.list_downloads_paged(&all, 0, 0).unwrap();
+        assert!(none.downloads.is_empty());
+        assert_eq!(none.total, 10);
+    }
+
+    fn insert_search_fixture(db: &Database) {
+        let entries = [
+            (
+                "a",
+                "ubuntu-24.04.iso",
+                "https://releases.ubuntu.com/24.04/ubuntu-24.04.iso",
+                "releases.ubuntu.com",
+            ),
+            (
+                "b",
+                "notes.pdf",
+                "https://files.example.org/ubuntu/notes.pdf",
+                "files.example.org",
+            ),
+            (
+                "c",
+                "my_ubuntu_wallpaper.png",
+                "https://img.host.net/w.png",
+                "img.host.net",
+            ),
+            (
+                "d",
+                "report.pdf",
+                "https://mirror.ubuntu-fr.org/report.pdf",
+                "mirror.ubuntu-fr.org",
+            ),
+            (
+                "e",
+                "100%_done.txt",
+                "https://example.com/done.txt",
+                "example.com",
+            ),
+        ];
+        for (id, filename, url, domain) in entries {
+            let mut dl = make_test_download(id, DownloadStatus::Completed);
+            dl.filename = filename.to_string();
+            dl.url = url.to_string();
+            dl.source_domain = Some(domain.to_string());
+            db.insert_download(&dl).unwrap();
+        }
+    }
+
+    fn search_ids(db: &Database, query: &str) -> Vec<String> {
+        db.search_downloads(query)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_search_ranks_filename_then_domain_then_url() {
+        let db = Database::open_in_memory().unwrap();
+        insert_search_fixture(&db);
+
+        // a: filename prefix, c: filename contains, d: domain, b: url only
+        assert_eq!(search_ids(&db, "UBUNTU"), vec!["a", "c", "d", "b"]);
+    }
+
+    #[test]
+    fn test_search_hits_and_misses() {
+        let db = Database::open_in_memory().unwrap();
+        insert_search_fixture(&db);
+
+        assert_eq!(search_ids(&db, "report"), vec!["d"]);
+        assert_eq!(search_ids(&db, "img.host"), vec!["c"]);
+        assert!(search_ids(&db, "debian").is_empty());
+        assert!(search_ids(&db, "   ").is_empty());
+        // LIKE wildcards are matched literally
+        assert_eq!(search_ids(&db, "100%_"), vec!["e"]);
+        let mut underscored = search_ids(&db, "_");
+        underscored.sort();
+        assert_eq!(underscored, vec!["c", "e"]);
+    }
+
+    #[test]
+    fn test_download_totals_groups_by_status() {
+        let db = Database::open_in_memory().unwrap();
+        let statuses = [
+            DownloadStatus::Pending,
+            DownloadStatus::Queued,
+            DownloadStatus::Queued,
+            DownloadStatus::Downloading,
+            DownloadStatus::Paused,
+            DownloadStatus::Completed,
+            DownloadStatus::Completed,
+            DownloadStatus::Completed,
+            DownloadStatus::Failed,
+        ];
+        for (i, status) in statuses.into_iter().enumerate() {
+            let mut dl = make_test_download(&format!("dl-{i}"), status);
+            dl.downloaded_size = 100 * (i as u64 + 1);
+            db.insert_download(&dl).unwrap();
+        }
+
+        let totals = db.download_totals().unwrap();
+        assert_eq!(
+            totals.counts,
+            StatusCounts {
+                pending: 1,
+                analyzing: 0,
+                queued: 2,
+                downloading: 1,
+                paused: 1,
+                completed: 3,
+                failed: 1,
+                cancelled: 0,
+            }
+        );
+        // 100 + 200 + ... + 900
+        assert_eq!(totals.downloaded_bytes, 4500);
+    }
+
+    #[test]
+    fn test_download_totals_empty() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.download_totals().unwrap(), DownloadTotals::default());
+    }
+
     #[test]
     fn test_update_status() {
         let db = Database::open_in_memory().unwrap();
@@ -730,6 +1583,99 @@ mod tests {
         assert_eq!(next.unwrap().id, "dl-1"); // now dl-1 has lower position
     }
 
+    #[test]
+    fn test_next_queued_uses_status_queue_index() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.conn();
+        let plan: Vec<String> = conn
+            .prepare(&format!("EXPLAIN QUERY PLAN {}", next_queued_sql()))
+            .unwrap()
+            .query_map([], |row| row.get(3))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert!(
+            plan.iter()
+                .any(|step| step.contains("USING INDEX idx_downloads_status_queue (status=?)")),
+            "{plan:?}"
+        );
+        assert!(
+            !plan.iter().any(|step| step.starts_with("SCAN downloads")),
+            "{plan:?}"
+        );
+    }
+
+    #[test]
+    fn test_validators_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let mut dl = make_test_download("dl-1", DownloadStatus::Downloading);
+        dl.etag = Some("W/\"abc\"".to_string());
+        dl.last_modified = Some("Wed, 21 Oct 2026 07:28:00 GMT".to_string());
+        db.insert_download(&dl).unwrap();
+
+        let fetched = db.get_download("dl-1").unwrap();
+        assert_eq!(fetched.etag, dl.etag);
+        assert_eq!(fetched.last_modified, dl.last_modified);
+
+        db.update_validators("dl-1", Some("\"v2\""), None).unwrap();
+        let fetched = db.get_download("dl-1").unwrap();
+        assert_eq!(fetched.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(fetched.last_modified, None);
+        assert!(matches!(
+            db.update_validators("missing", None, None),
+            Err(CraneError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_throughput_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_download(&make_test_download("dl-1", DownloadStatus::Completed))
+            .unwrap();
+        assert_eq!(db.get_download("dl-1").unwrap().average_speed, None);
+
+        db.update_throughput("dl-1", 1500.0, 4000.0).unwrap();
+        let fetched = db.get_download("dl-1").unwrap();
+        assert_eq!(fetched.average_speed, Some(1500.0));
+        assert_eq!(fetched.peak_speed, Some(4000.0));
+        assert!(matches!(
+            db.update_throughput("missing", 1.0, 1.0),
+            Err(CraneError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_next_queued_orders_by_priority_then_position() {
+        let db = Database::open_in_memory().unwrap();
+        for (id, position, priority) in [
+            ("low", 1, Priority::Low),
+            ("normal", 2, Priority::Normal),
+            ("high-late", 4, Priority::High),
+            ("high", 3, Priority::High),
+        ] {
+            let mut dl = make_test_download(id, DownloadStatus::Queued);
+            dl.queue_position = Some(position);
+            dl.priority = priority;
+            db.insert_download(&dl).unwrap();
+        }
+
+        let mut order = Vec::new();
+        while let Some(next) = db.get_next_queued().unwrap() {
+            db.update_download_status(&next.id, DownloadStatus::Downloading, None, None)
+                .unwrap();
+            order.push(next.id);
+        }
+        assert_eq!(order, ["high", "high-late", "normal", "low"]);
+
+        db.update_priority("low", Priority::High).unwrap();
+        assert_eq!(db.get_download("low").unwrap().priority, Priority::High);
+        assert!(matches!(
+            db.update_priority("missing", Priority::Low),
+            Err(CraneError::NotFound(_))
+        ));
+    }
+
     #[test]
     fn test_count_active_downloads() {
         let db = Database::open_in_memory().unwrap();
@@ -7,6 +7,7 @@ pub mod speed_history;
 use crate::types::CraneError;
 use rusqlite::Connection;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, MutexGuard};
 
 /// Wrapper around a SQLite connection for Crane's persistence layer.
@@ -15,6 +16,8 @@ use std::sync::{Mutex, MutexGuard};
 /// which is required for use inside Tauri's managed state.
 pub struct Database {
     conn: Mutex<Connection>,
+    /// Rows this connection inserted or set back to pending.
+    pending_writes: AtomicU64,
 }
 
 impl Database {
@@ -31,6 +34,7 @@ impl Database {
 
         let db = Self {
             conn: Mutex::new(conn),
+            pending_writes: AtomicU64::new(0),
         };
         db.setup()?;
         Ok(db)
@@ -42,11 +46,28 @@ impl Database {
 
         let db = Self {
             conn: Mutex::new(conn),
+            pending_writes: AtomicU64::new(0),
         };
         db.setup()?;
         Ok(db)
     }
 
+    /// Changes that could have added pending downloads: SQLite's
+    /// `data_version`, bumped by commits from other connections (the native
+    /// host), and this connection's inserts and returns to pending. While
+    /// the marker stays the same, no new pending rows have appeared.
+    pub fn pending_marker(&self) -> Result<(i64, u64), CraneError> {
+        let version = self
+            .conn()
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+        Ok((version, self.pending_writes.load(Ordering::SeqCst)))
+    }
+
+    fn note_pending_write(&self) {
+        self.pending_writes.fetch_add(1, Ordering::SeqCst);
+    }
+
     /// Accessor for the underlying connection.
     ///
     /// Locks the mutex and returns a guard. Panics if the mutex is poisoned.
@@ -92,13 +113,26 @@ fn set_schema_version(conn: &Connection, version: i64) -> Result<(), CraneError>
     Ok(())
 }
 
+/// Schema migrations in order; entry `i` takes the schema to version `i + 1`.
+const MIGRATIONS: &[fn(&Connection) -> Result<(), CraneError>] = &[
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+    migrate_v3_to_v4,
+    migrate_v4_to_v5,
+    migrate_v5_to_v6,
+    migrate_v6_to_v7,
+    migrate_v7_to_v8,
+    migrate_v8_to_v9,
+    migrate_v9_to_v10,
+    migrate_v10_to_v11,
+    migrate_v11_to_v12,
+];
+
 fn run_migrations(conn: &Connection) -> Result<(), CraneError> {
     let current = get_schema_version(conn)?;
 
-    let migrations: &[fn(&Connection) -> Result<(), CraneError>] =
-        &[migrate_v0_to_v1, migrate_v1_to_v2];
-
-    for (i, migrate) in migrations.iter().enumerate() {
+    for (i, migrate) in MIGRATIONS.iter().enumerate() {
         let target = (i + 1) as i64;
         if current < target {
             conn.execute_batch("BEGIN;")
@@ -214,11 +248,119 @@ fn migrate_v1_to_v2(conn: &Connection) -> Result<(), CraneError> {
     Ok(())
 }
 
+/// V3: Add `scheduled_at` column for downloads that start at a set time.
+fn migrate_v2_to_v3(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN scheduled_at TEXT;")
+        .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V4: Add `final_url` column recording where redirects led.
+fn migrate_v3_to_v4(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN final_url TEXT;")
+        .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V5: Add `priority` column; existing downloads become `normal`.
+fn migrate_v4_to_v5(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN priority TEXT NOT NULL DEFAULT 'normal';")
+        .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V6: Add `etag` and `last_modified` validators for `If-Range` resumes.
+fn migrate_v5_to_v6(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch(
+        "ALTER TABLE downloads ADD COLUMN etag TEXT;
+         ALTER TABLE downloads ADD COLUMN last_modified TEXT;",
+    )
+    .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V7: Add `average_speed` and `peak_speed` recorded when a download
+/// completes.
+fn migrate_v6_to_v7(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch(
+        "ALTER TABLE downloads ADD COLUMN average_speed REAL;
+         ALTER TABLE downloads ADD COLUMN peak_speed REAL;",
+    )
+    .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V8: Add `auth_scheme` recording which credentials a download uses (the
+/// secrets themselves are never stored).
+fn migrate_v7_to_v8(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN auth_scheme TEXT;")
+        .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V9: Index queued downloads by `(status, queue_position)` for
+/// `get_next_queued`, replacing the status-only index it prefixes, and
+/// `source_domain` for per-host lookups.
+fn migrate_v8_to_v9(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_downloads_status_queue
+             ON downloads(status, queue_position);
+         DROP INDEX IF EXISTS idx_downloads_status;
+         CREATE INDEX IF NOT EXISTS idx_downloads_source_domain
+             ON downloads(source_domain);",
+    )
+    .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V10: Cancellations get their own `cancelled` status. Older rows recorded
+/// them as `failed` with the error message "cancelled".
+fn migrate_v9_to_v10(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch(
+        "UPDATE downloads SET status = 'cancelled', error_message = NULL
+         WHERE status = 'failed' AND error_message = 'cancelled';",
+    )
+    .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V11: SHA-256 of finished files, looked up by content dedup.
+fn migrate_v10_to_v11(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch(
+        "ALTER TABLE downloads ADD COLUMN content_hash TEXT;
+         CREATE INDEX IF NOT EXISTS idx_downloads_content_hash
+             ON downloads(content_hash);",
+    )
+    .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
+/// V12: Fallback mirrors not yet tried, as a JSON array, so failover
+/// still works after a restart.
+fn migrate_v11_to_v12(conn: &Connection) -> Result<(), CraneError> {
+    conn.execute_batch("ALTER TABLE downloads ADD COLUMN mirrors TEXT;")
+        .map_err(|e| CraneError::Database(e.to_string()))?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    /// A database file as a Crane at schema `version` left it: only the
+    /// first `version` migrations applied.
+    fn create_db_at_version(path: &Path, version: i64) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch("CREATE TABLE schema_version (version INTEGER NOT NULL);")
+            .unwrap();
+        for migrate in &MIGRATIONS[..version as usize] {
+            migrate(&conn).unwrap();
+        }
+        set_schema_version(&conn, version).unwrap();
+        conn
+    }
+
     #[test]
     fn test_open_in_memory() {
         let db = Database::open_in_memory().unwrap();
@@ -278,13 +420,13 @@ mod tests {
     }
 
     #[test]
-    fn test_fresh_db_has_schema_version_2() {
+    fn test_fresh_db_has_schema_version_12() {
         let db = Database::open_in_memory().unwrap();
         let version: i64 = db
             .conn()
             .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
             .unwrap();
-        assert_eq!(version, 2);
+        assert_eq!(version, 12);
     }
 
     #[test]
@@ -307,7 +449,7 @@ mod tests {
             .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
             .unwrap();
         assert_eq!(v1, v2);
-        assert_eq!(v2, 2);
+        assert_eq!(v2, MIGRATIONS.len() as i64);
     }
 
     #[test]
@@ -390,7 +532,7 @@ mod tests {
             .conn()
             .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
             .unwrap();
-        assert_eq!(version, 2);
+        assert_eq!(version, MIGRATIONS.len() as i64);
 
         // Verify all 5 original tables still exist
         let conn = db.conn();
@@ -484,13 +626,13 @@ mod tests {
             .unwrap();
         }
 
-        // Open with Database::open — should run v1→v2 migration
+        // Open with Database::open — should run v1→v2 (and later) migrations
         let db = Database::open(&db_path).unwrap();
         let version: i64 = db
             .conn()
             .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
             .unwrap();
-        assert_eq!(version, 2);
+        assert_eq!(version, MIGRATIONS.len() as i64);
 
         // Verify the headers column exists by querying it
         let conn = db.conn();
@@ -500,9 +642,86 @@ mod tests {
         assert!(has_headers, "headers column should exist after migration");
     }
 
+    /// Insert a download row using only columns every schema version has.
+    fn insert_legacy_row(conn: &Connection, id: &str, status: &str, error_message: Option<&str>) {
+        conn.execute(
+            "INSERT INTO downloads (id, url, filename, save_path, category, status, error_message, created_at, updated_at)
+             VALUES (?1, 'https://example.com/a', 'a', '/tmp/a', 'other', ?2, ?3, '2026-01-01T00:00:00Z', '2026-01-01T00:00:00Z')",
+            rusqlite::params![id, status, error_message],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_old_db_gets_new_columns_with_defaults() {
+        use rusqlite::types::Value;
+
+        // (schema version without the column, column, value existing rows get)
+        let cases = [
+            (2, "scheduled_at", Value::Null),
+            (3, "final_url", Value::Null),
+            (4, "priority", Value::Text("normal".to_string())),
+            (5, "etag", Value::Null),
+            (5, "last_modified", Value::Null),
+            (6, "average_speed", Value::Null),
+            (6, "peak_speed", Value::Null),
+            (7, "auth_scheme", Value::Null),
+            (10, "content_hash", Value::Null),
+            (11, "mirrors", Value::Null),
+        ];
+        for (from_version, column, expected) in cases {
+            let tmp = TempDir::new().unwrap();
+            let db_path = tmp.path().join("crane.db");
+            insert_legacy_row(
+                &create_db_at_version(&db_path, from_version),
+                "old",
+                "pending",
+                None,
+            );
+
+            let db = Database::open(&db_path).unwrap();
+            let version: i64 = db
+                .conn()
+                .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(version, MIGRATIONS.len() as i64, "from v{from_version}");
+            let value: Value = db
+                .conn()
+                .query_row(
+                    &format!("SELECT {column} FROM downloads WHERE id = 'old'"),
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(value, expected, "{column} from v{from_version}");
+            assert!(db.get_download("old").is_ok(), "from v{from_version}");
+        }
+    }
+
+    #[test]
+    fn test_v9_db_reclassifies_cancelled_rows() {
+        use crate::types::DownloadStatus;
+
+        let tmp = TempDir::new().unwrap();
+        let db_path = tmp.path().join("crane.db");
+
+        let conn = create_db_at_version(&db_path, 9);
+        insert_legacy_row(&conn, "cancelled", "failed", Some("cancelled"));
+        insert_legacy_row(&conn, "broken", "failed", Some("HTTP 500"));
+        drop(conn);
+
+        let db = Database::open(&db_path).unwrap();
+        let cancelled = db.get_download("cancelled").unwrap();
+        assert_eq!(cancelled.status, DownloadStatus::Cancelled);
+        assert_eq!(cancelled.error_message, None);
+        let broken = db.get_download("broken").unwrap();
+        assert_eq!(broken.status, DownloadStatus::Failed);
+        assert_eq!(broken.error_message.as_deref(), Some("HTTP 500"));
+    }
+
     #[test]
     fn test_download_round_trip_with_headers() {
-        use crate::types::{Download, DownloadStatus, FileCategory};
+        use crate::types::{Download, DownloadStatus, FileCategory, Priority};
 
         let db = Database::open_in_memory().unwrap();
 
@@ -533,6 +752,16 @@ mod tests {
             started_at: None,
             completed_at: None,
             updated_at: "2026-01-01T00:00:00Z".to_string(),
+            scheduled_at: None,
+            final_url: None,
+            priority: Priority::Normal,
+            etag: None,
+            last_modified: None,
+            average_speed: None,
+            peak_speed: None,
+            auth_scheme: None,
+            content_hash: None,
+            mirrors: None,
         };
 
         db.insert_download(&dl).unwrap();
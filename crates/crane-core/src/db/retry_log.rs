@@ -33,7 +33,7 @@ impl Database {
     }
 
     /// Get all retry entries for a download, ordered by attempt ascending.
-    pub fn get_retries(&self, download_id: &str) -> Result<Vec<RetryEntry>, CraneError> {
+    pub fn get_retry_log(&self, download_id: &str) -> Result<Vec<RetryEntry>, CraneError> {
         let conn = self.conn();
         let mut stmt = conn
             .prepare(
@@ -88,7 +88,7 @@ mod tests {
         db.insert_retry("dl-1", 2, Some("timeout"), None).unwrap();
         db.insert_retry("dl-1", 3, None, None).unwrap();
 
-        let retries = db.get_retries("dl-1").unwrap();
+        let retries = db.get_retry_log("dl-1").unwrap();
         assert_eq!(retries.len(), 3);
 
         assert_eq!(retries[0].attempt, 1);
@@ -107,7 +107,7 @@ mod tests {
         assert!(retries[2].error_code.is_none());
 
         // Non-existent download returns empty
-        let empty = db.get_retries("dl-nonexistent").unwrap();
+        let empty = db.get_retry_log("dl-nonexistent").unwrap();
         assert!(empty.is_empty());
     }
 
@@ -119,7 +119,7 @@ mod tests {
         db.insert_retry("dl-1", 2, Some("error again"), None)
             .unwrap();
 
-        assert_eq!(db.get_retries("dl-1").unwrap().len(), 2);
+        assert_eq!(db.get_retry_log("dl-1").unwrap().len(), 2);
 
         // Delete parent download
         db.conn()
@@ -127,7 +127,7 @@ mod tests {
             .unwrap();
 
         // Retry log should be gone via CASCADE
-        let retries = db.get_retries("dl-1").unwrap();
+        let retries = db.get_retry_log("dl-1").unwrap();
         assert!(retries.is_empty());
     }
 }
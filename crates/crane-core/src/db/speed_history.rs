@@ -55,6 +55,49 @@ impl Database {
         }
         Ok(samples)
     }
+
+    /// Get the most recent `limit` samples as `(timestamp, speed)` pairs,
+    /// ordered oldest-first for plotting.
+    pub fn get_recent_speed_history(
+        &self,
+        download_id: &str,
+        limit: u32,
+    ) -> Result<Vec<(String, f64)>, CraneError> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare(
+                "SELECT timestamp, speed FROM (
+                     SELECT id, timestamp, speed FROM speed_history
+                     WHERE download_id = ?1
+                     ORDER BY timestamp DESC, id DESC
+                     LIMIT ?2
+                 ) ORDER BY timestamp ASC, id ASC",
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let rows = stmt
+            .query_map(params![download_id, limit], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|e| CraneError::Database(e.to_string()))?;
+
+        let mut samples = Vec::new();
+        for row in rows {
+            samples.push(row.map_err(|e| CraneError::Database(e.to_string()))?);
+        }
+        Ok(samples)
+    }
+
+    /// Highest recorded speed for a download, if any samples exist.
+    pub fn peak_speed(&self, download_id: &str) -> Result<Option<f64>, CraneError> {
+        self.conn()
+            .query_row(
+                "SELECT MAX(speed) FROM speed_history WHERE download_id = ?1",
+                params![download_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| CraneError::Database(e.to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -113,4 +156,32 @@ mod tests {
         let samples = db.get_speed_history("dl-1", 60).unwrap();
         assert!(samples.is_empty());
     }
+
+    #[test]
+    fn test_get_recent_speed_history_limit_in_timestamp_order() {
+        let db = setup_db_with_download();
+        for (ts, speed) in [
+            ("2026-01-01T00:00:03+00:00", 300.0),
+            ("2026-01-01T00:00:01+00:00", 100.0),
+            ("2026-01-01T00:00:04+00:00", 400.0),
+            ("2026-01-01T00:00:02+00:00", 200.0),
+        ] {
+            db.conn()
+                .execute(
+                    "INSERT INTO speed_history (download_id, speed, timestamp) VALUES ('dl-1', ?1, ?2)",
+                    params![speed, ts],
+                )
+                .unwrap();
+        }
+
+        let all = db.get_recent_speed_history("dl-1", 10).unwrap();
+        let speeds: Vec<f64> = all.iter().map(|(_, s)| *s).collect();
+        assert_eq!(speeds, vec![100.0, 200.0, 300.0, 400.0]);
+        assert_eq!(all[0].0, "2026-01-01T00:00:01+00:00");
+
+        // Limit keeps the most recent samples, still oldest-first
+        let recent = db.get_recent_speed_history("dl-1", 2).unwrap();
+        let speeds: Vec<f64> = recent.iter().map(|(_, s)| *s).collect();
+        assert_eq!(speeds, vec![300.0, 400.0]);
+    }
 }
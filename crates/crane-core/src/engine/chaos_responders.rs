@@ -47,6 +47,63 @@ impl wiremock::Respond for RangeIgnoringResponder {
     }
 }
 
+/// Answers each range GET with a correct `206` and `Content-Range` but one
+/// byte too many in the body, so the chunk files don't add up at merge time.
+pub struct OverlongRangeResponder {
+    pub body: Vec<u8>,
+}
+
+impl wiremock::Respond for OverlongRangeResponder {
+    fn respond(&self, request: &wiremock::Request) -> wiremock::ResponseTemplate {
+        let range = request.headers.get("range").unwrap().to_str().unwrap();
+        let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+        let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+        let mut slice = self.body[start..=end].to_vec();
+        slice.push(0);
+        wiremock::ResponseTemplate::new(206)
+            .set_body_bytes(slice)
+            .insert_header(
+                "content-range",
+                format!("bytes {start}-{end}/{}", self.body.len()).as_str(),
+            )
+    }
+}
+
+/// Compresses every GET regardless of `Accept-Encoding`, sending
+/// `Content-Encoding: gzip`. Range requests get a `206` with the right
+/// `Content-Range` but a compressed (shorter) body, so chunk files wouldn't
+/// add up to the advertised size. Dropping every other byte stands in for
+/// real compression; the client never decodes the body either way.
+pub struct CompressingResponder {
+    pub body: Vec<u8>,
+}
+
+impl CompressingResponder {
+    pub fn compress(data: &[u8]) -> Vec<u8> {
+        data.iter().step_by(2).copied().collect()
+    }
+}
+
+impl wiremock::Respond for CompressingResponder {
+    fn respond(&self, request: &wiremock::Request) -> wiremock::ResponseTemplate {
+        let Some(range) = request.headers.get("range") else {
+            return wiremock::ResponseTemplate::new(200)
+                .set_body_bytes(Self::compress(&self.body))
+                .insert_header("content-encoding", "gzip");
+        };
+        let range = range.to_str().unwrap();
+        let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+        let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+        wiremock::ResponseTemplate::new(206)
+            .set_body_bytes(Self::compress(&self.body[start..=end]))
+            .insert_header("content-encoding", "gzip")
+            .insert_header(
+                "content-range",
+                format!("bytes {start}-{end}/{}", self.body.len()).as_str(),
+            )
+    }
+}
+
 /// Sends data but with a configurable delay, simulating a slow/stalling
 /// connection (e.g. saturated server, poor network).
 pub struct SlowTrickleResponder {
@@ -225,6 +282,32 @@ impl wiremock::Respond for SlowRangeResponder {
     }
 }
 
+/// Serves the range starting at byte 0 immediately and stalls every other
+/// range, so a download can be stopped with exactly one chunk finished on disk.
+pub struct FirstChunkFastResponder {
+    pub body: Vec<u8>,
+}
+
+impl wiremock::Respond for FirstChunkFastResponder {
+    fn respond(&self, request: &wiremock::Request) -> wiremock::ResponseTemplate {
+        let range = request.headers.get("range").unwrap().to_str().unwrap();
+        let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+        let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+        let delay = if start == 0 {
+            std::time::Duration::ZERO
+        } else {
+            std::time::Duration::from_secs(30)
+        };
+        wiremock::ResponseTemplate::new(206)
+            .set_body_bytes(self.body[start..=end].to_vec())
+            .insert_header(
+                "content-range",
+                format!("bytes {start}-{end}/{}", self.body.len()).as_str(),
+            )
+            .set_delay(delay)
+    }
+}
+
 /// Range-aware responder that fails the first N requests for a specific chunk,
 /// then serves the correct range data. Used for multi-connection retry tests.
 pub struct IntermittentRangeResponder {
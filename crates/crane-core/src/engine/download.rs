@@ -1,29 +1,91 @@
 // Single-connection HTTP/HTTPS downloader
 
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use futures_util::StreamExt;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tokio_util::sync::CancellationToken;
 use url::Url;
 
 use std::sync::Arc;
 
+use super::retry::{is_retryable_status, report_retry, retry_after, RetryCallback};
+use super::speed::SpeedEstimator;
 use crate::bandwidth::BandwidthLimiter;
-use crate::network::safe_redirect_policy;
-use crate::types::{CraneError, DownloadOptions, DownloadProgress, DownloadResult};
-
-pub(crate) const PROGRESS_INTERVAL_MS: u64 = 250;
-pub(crate) const RETRY_BACKOFF_MS: &[u64] = &[1000, 2000, 4000];
-pub(crate) const MAX_RETRIES: u32 = RETRY_BACKOFF_MS.len() as u32;
+use crate::network::{apply_proxy, request_identity_encoding, safe_redirect_policy};
+use crate::types::{
+    Auth, CraneError, DownloadOptions, DownloadPhase, DownloadProgress, DownloadResult,
+};
+
+/// Progress callback spacing when `DownloadOptions::progress_interval_ms` is unset.
+pub const PROGRESS_INTERVAL_MS: u64 = 250;
+/// Shortest progress callback spacing accepted.
+pub const MIN_PROGRESS_INTERVAL_MS: u64 = 50;
 pub(crate) const USER_AGENT: &str = "Crane/0.1.0";
+/// Connect timeout when `DownloadOptions::connect_timeout_ms` is unset.
+pub(crate) const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 10_000;
+/// Longest silence between reads when `DownloadOptions::read_timeout_ms` is unset.
+pub(crate) const DEFAULT_READ_TIMEOUT_MS: u64 = 60_000;
+/// Idle connections kept open per host for chunk and retry requests to reuse.
+pub(crate) const POOL_MAX_IDLE_PER_HOST: usize = 8;
+
+/// Apply the connect and read (stall) timeouts from `options`, falling back
+/// to the defaults. `Some(0)` disables a timeout.
+pub(crate) fn apply_timeouts(
+    mut builder: reqwest::ClientBuilder,
+    options: &DownloadOptions,
+) -> reqwest::ClientBuilder {
+    let connect = options
+        .connect_timeout_ms
+        .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS);
+    if connect > 0 {
+        builder = builder.connect_timeout(Duration::from_millis(connect));
+    }
+    let read = options.read_timeout_ms.unwrap_or(DEFAULT_READ_TIMEOUT_MS);
+    if read > 0 {
+        builder = builder.read_timeout(Duration::from_millis(read));
+    }
+    builder
+}
+
+/// How often a download reports progress: `options.progress_interval_ms`
+/// (no shorter than `MIN_PROGRESS_INTERVAL_MS`) or the default.
+pub(crate) fn progress_interval(options: &DownloadOptions) -> Duration {
+    let ms = options
+        .progress_interval_ms
+        .unwrap_or(PROGRESS_INTERVAL_MS)
+        .max(MIN_PROGRESS_INTERVAL_MS);
+    Duration::from_millis(ms)
+}
 
-/// Apply DownloadOptions headers (Referer, Cookie, custom headers) to a request.
+/// Configure connection reuse and the HTTP version. With `prefer_http2`,
+/// https negotiates HTTP/2 via ALPN (falling back to HTTP/1.1) and plain
+/// http speaks h2c with prior knowledge; otherwise only HTTP/1.1 is used.
+pub(crate) fn apply_http_version(
+    builder: reqwest::ClientBuilder,
+    options: &DownloadOptions,
+    url: &Url,
+) -> reqwest::ClientBuilder {
+    let builder = builder.pool_max_idle_per_host(POOL_MAX_IDLE_PER_HOST);
+    if !options.prefer_http2 {
+        return builder.http1_only();
+    }
+    let builder = builder.http2_adaptive_window(true);
+    if url.scheme() == "http" {
+        builder.http2_prior_knowledge()
+    } else {
+        builder
+    }
+}
+
+/// Apply DownloadOptions headers (Referer, Cookie, custom headers) to a
+/// request, asking for an unencoded body unless the headers say otherwise.
 pub(crate) fn apply_options_headers(
-    mut request: reqwest::RequestBuilder,
+    request: reqwest::RequestBuilder,
     options: &DownloadOptions,
 ) -> reqwest::RequestBuilder {
+    let mut request = request_identity_encoding(request, options.headers.as_ref());
     if let Some(ref referrer) = options.referrer {
         request = request.header("Referer", referrer);
     }
@@ -35,9 +97,28 @@ pub(crate) fn apply_options_headers(
             request = request.header(key.as_str(), value.as_str());
         }
     }
+    if let Some(ref auth) = options.auth {
+        request = apply_auth(request, auth);
+    }
     request
 }
 
+/// Send `auth` as the request's `Authorization` header.
+pub(crate) fn apply_auth(request: reqwest::RequestBuilder, auth: &Auth) -> reqwest::RequestBuilder {
+    match auth {
+        Auth::Basic { user, pass } => request.basic_auth(user, Some(pass)),
+        Auth::Bearer { token } => request.bearer_auth(token),
+    }
+}
+
+/// `Range` header value for `DownloadOptions::range`.
+pub(crate) fn range_header_value((start, end): (u64, Option<u64>)) -> String {
+    match end {
+        Some(end) => format!("bytes={start}-{end}"),
+        None => format!("bytes={start}-"),
+    }
+}
+
 /// Build the temporary download path by appending `.cranedownload`.
 fn temp_path(save_path: &Path) -> PathBuf {
     let mut temp_name = save_path.as_os_str().to_os_string();
@@ -45,11 +126,23 @@ fn temp_path(save_path: &Path) -> PathBuf {
     PathBuf::from(temp_name)
 }
 
+/// Fail if the body ended before the advertised `Content-Length`.
+fn check_complete(downloaded: u64, total_size: Option<u64>) -> Result<(), CraneError> {
+    match total_size {
+        Some(expected) if downloaded < expected => Err(CraneError::Truncated {
+            expected,
+            received: downloaded,
+        }),
+        _ => Ok(()),
+    }
+}
+
 /// Perform a single download attempt: send GET, stream body to temp file.
 ///
 /// Returns `(downloaded_bytes, total_size)` on success, or a `CraneError`
 /// on failure. The caller is responsible for renaming the temp file.
 async fn attempt_download<F>(
+    download_id: &str,
     parsed_url: &Url,
     save_path: &Path,
     client: &reqwest::Client,
@@ -58,94 +151,155 @@ async fn attempt_download<F>(
     start_time: Instant,
     cancel_token: &CancellationToken,
     limiter: &Option<Arc<BandwidthLimiter>>,
+    retry_after_out: &mut Option<Duration>,
 ) -> Result<(u64, Option<u64>), CraneError>
 where
     F: Fn(&DownloadProgress) + Send + Sync,
 {
+    let filename = save_path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+    let response = tokio::select! {
+        sent = send_request(parsed_url, client, options, filename, retry_after_out) => sent?,
+        _ = cancel_token.cancelled() => return Err(CraneError::Cancelled),
+    };
+    let total_size = response.content_length();
+
+    // Ensure parent directory exists
+    let tmp = temp_path(save_path);
+    if let Some(parent) = tmp.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // Stream body to temp file
+    let mut file = tokio::fs::File::create(&tmp).await?;
+    let mut downloaded: u64 = 0;
+    let written = write_body(
+        download_id,
+        response,
+        &mut file,
+        options,
+        on_progress,
+        start_time,
+        cancel_token,
+        limiter,
+        &mut downloaded,
+    )
+    .await;
+    file.shutdown().await?;
+    written?;
+
+    Ok((downloaded, total_size))
+}
+
+/// Send the GET for one attempt and check the response is the body asked
+/// for: a success status, the requested range, and (captive portal guard)
+/// not an HTML page standing in for `filename`.
+async fn send_request(
+    parsed_url: &Url,
+    client: &reqwest::Client,
+    options: &DownloadOptions,
+    filename: &str,
+    retry_after_out: &mut Option<Duration>,
+) -> Result<reqwest::Response, CraneError> {
     // Build request
     let request = client.get(parsed_url.as_str());
-    let request = apply_options_headers(request, options);
+    let mut request = apply_options_headers(request, options);
+    if let Some(range) = options.range {
+        request = request.header(reqwest::header::RANGE, range_header_value(range));
+    }
 
     // Send request
     let response = request.send().await.map_err(CraneError::Network)?;
     let status = response.status();
     if !status.is_success() {
+        *retry_after_out = retry_after(&response);
         return Err(CraneError::Http {
             status: status.as_u16(),
             message: status.canonical_reason().unwrap_or("Unknown").to_string(),
         });
     }
+    // A sub-range must come back as exactly that slice, never the whole file
+    match options.range {
+        Some((start, Some(end))) => super::multi::check_range_response(&response, start, end)?,
+        Some((start, None)) if status != reqwest::StatusCode::PARTIAL_CONTENT => {
+            return Err(CraneError::RangeIgnored(format!(
+                "asked for bytes {start}-, got {}",
+                status.as_u16()
+            )));
+        }
+        _ => {}
+    }
 
-    // Validate Content-Type against expected filename (captive portal guard)
     let content_type = response
         .headers()
         .get(reqwest::header::CONTENT_TYPE)
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
-
-    let filename = save_path.file_name().and_then(|f| f.to_str()).unwrap_or("");
     crate::metadata::validate_content_type(content_type.as_deref(), filename)?;
 
-    let total_size = response.content_length();
-
-    // Ensure parent directory exists
-    let tmp = temp_path(save_path);
-    if let Some(parent) = tmp.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
+    Ok(response)
+}
 
-    // Stream body to temp file
-    let mut file = tokio::fs::File::create(&tmp).await?;
+/// Stream `response`'s body into `writer`, counting bytes in `downloaded`
+/// and reporting progress at most once per interval. Returns early, with
+/// the body unfinished, when `cancel_token` fires; otherwise fails if the
+/// body ended before its `Content-Length`.
+async fn write_body<W, F>(
+    download_id: &str,
+    response: reqwest::Response,
+    writer: &mut W,
+    options: &DownloadOptions,
+    on_progress: &F,
+    start_time: Instant,
+    cancel_token: &CancellationToken,
+    limiter: &Option<Arc<BandwidthLimiter>>,
+    downloaded: &mut u64,
+) -> Result<(), CraneError>
+where
+    W: AsyncWrite + Unpin,
+    F: Fn(&DownloadProgress) + Send + Sync,
+{
+    let total_size = response.content_length();
     let mut stream = response.bytes_stream();
-    let mut downloaded: u64 = 0;
+    let interval = progress_interval(options);
     let mut last_progress_time = Instant::now();
     let mut last_speed_bytes: u64 = 0;
     let mut last_speed_time = Instant::now();
-    let mut current_speed: f64 = 0.0;
+    let mut estimator = SpeedEstimator::from_options(options);
 
     loop {
         tokio::select! {
             chunk_result = stream.next() => {
                 match chunk_result {
                     Some(Ok(chunk)) => {
-                        file.write_all(&chunk).await?;
-                        downloaded += chunk.len() as u64;
                         if let Some(ref lim) = *limiter {
                             lim.acquire(chunk.len() as u64).await;
                         }
-
-                        // Update speed with EMA smoothing every 1 second
-                        let speed_elapsed = last_speed_time.elapsed().as_secs_f64();
-                        if speed_elapsed >= 1.0 {
-                            let instant_speed = (downloaded - last_speed_bytes) as f64 / speed_elapsed;
-                            const ALPHA: f64 = 0.3;
-                            current_speed = if current_speed > 0.0 {
-                                ALPHA * instant_speed + (1.0 - ALPHA) * current_speed
-                            } else {
-                                instant_speed
-                            };
-                            last_speed_bytes = downloaded;
+                        writer.write_all(&chunk).await?;
+                        *downloaded += chunk.len() as u64;
+
+                        // Sample speed every second; the estimator smooths it
+                        let speed_elapsed = last_speed_time.elapsed();
+                        if speed_elapsed >= Duration::from_secs(1) {
+                            estimator.sample(
+                                *downloaded - last_speed_bytes,
+                                speed_elapsed,
+                                total_size.map(|total| total.saturating_sub(*downloaded)),
+                            );
+                            last_speed_bytes = *downloaded;
                             last_speed_time = Instant::now();
                         }
 
-                        // Report progress at most every PROGRESS_INTERVAL_MS
-                        if last_progress_time.elapsed().as_millis() >= PROGRESS_INTERVAL_MS as u128 {
-                            let eta = if current_speed > 0.0 {
-                                total_size.map(|total| {
-                                    let remaining = total.saturating_sub(downloaded);
-                                    (remaining as f64 / current_speed) as u64
-                                })
-                            } else {
-                                None
-                            };
-
+                        // Report progress at most once per interval
+                        if last_progress_time.elapsed() >= interval {
                             on_progress(&DownloadProgress {
-                                download_id: String::new(),
-                                downloaded_size: downloaded,
+                                download_id: download_id.to_string(),
+                                downloaded_size: *downloaded,
                                 total_size,
-                                speed: current_speed,
-                                eta_seconds: eta,
+                                speed: estimator.speed(),
+                                instant_speed: estimator.instant_speed(),
+                                eta_seconds: estimator.eta_seconds(),
                                 connections: vec![],
+                                phase: DownloadPhase::Downloading,
                             });
                             last_progress_time = Instant::now();
                         }
@@ -157,32 +311,68 @@ where
                 }
             }
             _ = cancel_token.cancelled() => {
-                file.shutdown().await?;
-                return Ok((downloaded, total_size));
+                return Ok(());
             }
         }
     }
 
-    file.shutdown().await?;
+    check_complete(*downloaded, total_size)?;
 
     // Final speed calculation
     let total_elapsed = start_time.elapsed().as_secs_f64();
-    if total_elapsed > 0.0 {
-        current_speed = downloaded as f64 / total_elapsed;
-    }
+    let current_speed = if total_elapsed > 0.0 {
+        *downloaded as f64 / total_elapsed
+    } else {
+        estimator.speed()
+    };
 
     // Final progress report
     let eta = Some(0u64);
     on_progress(&DownloadProgress {
-        download_id: String::new(),
-        downloaded_size: downloaded,
+        download_id: download_id.to_string(),
+        downloaded_size: *downloaded,
         total_size,
         speed: current_speed,
+        instant_speed: current_speed,
         eta_seconds: eta,
         connections: vec![],
+        phase: DownloadPhase::Downloading,
     });
 
-    Ok((downloaded, total_size))
+    Ok(())
+}
+
+/// Client for single-connection requests, configured from `options`.
+fn build_client(parsed: &Url, options: &DownloadOptions) -> Result<reqwest::Client, CraneError> {
+    let ua = options
+        .user_agent
+        .as_deref()
+        .unwrap_or(USER_AGENT)
+        .to_string();
+    let builder = reqwest::Client::builder()
+        .user_agent(ua)
+        .redirect(safe_redirect_policy(options.max_redirects));
+    let builder = apply_timeouts(builder, options);
+    let builder = apply_http_version(builder, options, parsed);
+    apply_proxy(
+        builder,
+        options.proxy.as_deref(),
+        options.no_proxy.as_deref(),
+    )?
+    .build()
+    .map_err(CraneError::Network)
+}
+
+/// Whether a failed attempt is worth retrying. 4xx errors (except 429),
+/// Content-Type mismatches and URL-level errors are permanent.
+fn is_retryable_error(err: &CraneError) -> bool {
+    match err {
+        CraneError::Http { status, .. } => is_retryable_status(*status),
+        CraneError::Network(_) => true,
+        CraneError::Truncated { .. } => true,
+        CraneError::ContentTypeMismatch { .. } => false,
+        _ => false,
+    }
 }
 
 /// Download a file from a URL to a local path using a single HTTP connection,
@@ -193,46 +383,45 @@ where
 /// (5xx) errors up to 3 times with exponential backoff.
 ///
 /// The `on_progress` callback fires at most every 250ms with current
-/// download statistics.
+/// download statistics, tagged with `download_id`. `on_retry` hears about
+/// every retried or exhausted attempt.
 pub(crate) async fn download_file_with_token<F>(
+    download_id: &str,
     url: &str,
     save_path: &Path,
     options: &DownloadOptions,
     on_progress: F,
     cancel_token: CancellationToken,
     limiter: Option<Arc<BandwidthLimiter>>,
+    on_retry: Option<RetryCallback>,
 ) -> Result<DownloadResult, CraneError>
 where
     F: Fn(&DownloadProgress) + Send + Sync,
 {
     let parsed = Url::parse(url)?;
-
-    let ua = options
-        .user_agent
-        .as_deref()
-        .unwrap_or(USER_AGENT)
-        .to_string();
-    let client = reqwest::Client::builder()
-        .user_agent(ua)
-        .redirect(safe_redirect_policy())
-        .build()
-        .map_err(CraneError::Network)?;
+    let client = build_client(&parsed, options)?;
 
     let start = Instant::now();
     let tmp = temp_path(save_path);
     let mut last_error: Option<CraneError> = None;
 
-    // Initial attempt + up to MAX_RETRIES retries
-    for attempt in 0..=MAX_RETRIES {
+    // Initial attempt + up to max_retries retries
+    let retry_policy = options.retry_policy.clone().unwrap_or_default();
+    let max_retries = retry_policy.max_retries();
+    let mut server_wait = None;
+    for attempt in 0..=max_retries {
         if attempt > 0 {
             // Clean up temp file from previous failed attempt
             let _ = tokio::fs::remove_file(&tmp).await;
 
-            let backoff = RETRY_BACKOFF_MS[(attempt - 1) as usize];
-            tokio::time::sleep(std::time::Duration::from_millis(backoff)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(retry_policy.delay_for(attempt, server_wait.take())) => {}
+                _ = cancel_token.cancelled() => return Err(CraneError::Cancelled),
+            }
         }
 
         match attempt_download(
+            download_id,
             &parsed,
             save_path,
             &client,
@@ -241,27 +430,30 @@ where
             start,
             &cancel_token,
             &limiter,
+            &mut server_wait,
         )
         .await
         {
-            Ok((downloaded_bytes, _total_size)) => {
+            Ok((downloaded_bytes, total_size)) => {
                 // Rename temp file to final path
                 tokio::fs::rename(&tmp, save_path).await?;
+                crate::metadata::apply_file_mode(save_path, options.file_mode).await?;
+
+                if crate::hash::wants_verification(options) {
+                    on_progress(&DownloadProgress {
+                        download_id: download_id.to_string(),
+                        downloaded_size: downloaded_bytes,
+                        total_size,
+                        speed: 0.0,
+                        instant_speed: 0.0,
+                        eta_seconds: None,
+                        connections: vec![],
+                        phase: DownloadPhase::Verifying,
+                    });
+                }
 
-                // Hash verification (if expected hash was provided)
-                let hash_verified = if let Some(ref expected) = options.expected_hash {
-                    let actual = crate::hash::compute_hash(save_path, expected.algorithm).await?;
-                    if actual != expected.value {
-                        let _ = tokio::fs::remove_file(save_path).await;
-                        return Err(CraneError::HashMismatch {
-                            expected: expected.value.clone(),
-                            actual,
-                        });
-                    }
-                    Some(true)
-                } else {
-                    None
-                };
+                // Hash verification (expected hash or checksum sidecar)
+                let hash_verified = crate::hash::verify_download(url, save_path, options).await?;
 
                 return Ok(DownloadResult {
                     downloaded_bytes,
@@ -271,14 +463,11 @@ where
                 });
             }
             Err(e) => {
-                // Don't retry 4xx errors, Content-Type mismatches, or URL-level errors — they're permanent
-                let is_retryable = match &e {
-                    CraneError::Http { status, .. } => *status >= 500,
-                    CraneError::Network(_) => true,
-                    CraneError::ContentTypeMismatch { .. } => false,
-                    _ => false,
-                };
-                if !is_retryable || attempt == MAX_RETRIES {
+                let is_retryable = is_retryable_error(&e);
+                if is_retryable {
+                    report_retry(&on_retry, attempt, &e, attempt == max_retries);
+                }
+                if !is_retryable || attempt == max_retries {
                     // Clean up temp file on final failure
                     let _ = tokio::fs::remove_file(&tmp).await;
                     return Err(e);
@@ -311,16 +500,95 @@ where
     F: Fn(&DownloadProgress) + Send + Sync,
 {
     download_file_with_token(
+        "",
         url,
         save_path,
         options,
         on_progress,
         CancellationToken::new(),
         None,
+        None,
     )
     .await
 }
 
+/// Download a URL into `writer` over a single HTTP connection, e.g. to pipe
+/// it to stdout. Returns the number of bytes written; `writer` is flushed
+/// but not shut down.
+///
+/// Multi-connection downloads need a seekable file, so this always uses
+/// one connection. Retries are best-effort: a sink can't be rewound or
+/// truncated, so an attempt that fails before any body bytes were written
+/// is retried per `options.retry_policy`, but one that fails partway
+/// through returns the error with the partial body already in `writer`.
+/// Expected hashes, checksum sidecars and file modes don't apply.
+pub async fn download_to_writer<W, F>(
+    url: &str,
+    writer: &mut W,
+    options: &DownloadOptions,
+    on_progress: F,
+) -> Result<u64, CraneError>
+where
+    W: AsyncWrite + Unpin,
+    F: Fn(&DownloadProgress) + Send + Sync,
+{
+    let parsed = Url::parse(url)?;
+    let client = build_client(&parsed, options)?;
+    // Stands in for the save path in the captive portal check
+    let filename = options
+        .filename
+        .clone()
+        .or_else(|| {
+            parsed
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+
+    let start = Instant::now();
+    let cancel_token = CancellationToken::new();
+    let retry_policy = options.retry_policy.clone().unwrap_or_default();
+    let max_retries = retry_policy.max_retries();
+    let mut server_wait = None;
+    let mut attempt = 0;
+    loop {
+        let mut downloaded: u64 = 0;
+        let result =
+            match send_request(&parsed, &client, options, &filename, &mut server_wait).await {
+                Ok(response) => {
+                    write_body(
+                        "",
+                        response,
+                        writer,
+                        options,
+                        &on_progress,
+                        start,
+                        &cancel_token,
+                        &None,
+                        &mut downloaded,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+        match result {
+            Ok(()) => {
+                writer.flush().await?;
+                return Ok(downloaded);
+            }
+            // Bytes already handed to the writer can't be taken back
+            Err(e) if downloaded > 0 || !is_retryable_error(&e) || attempt == max_retries => {
+                return Err(e);
+            }
+            Err(_) => {
+                attempt += 1;
+                tokio::time::sleep(retry_policy.delay_for(attempt, server_wait.take())).await;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -514,6 +782,64 @@ mod tests {
         assert_eq!(std::fs::read(&save).unwrap(), body);
     }
 
+    #[tokio::test]
+    async fn test_retry_after_on_429_replaces_backoff() {
+        use crate::engine::retry::RetryPolicy;
+
+        let server = MockServer::start().await;
+        let body = b"after the wait";
+
+        Mock::given(method("GET"))
+            .and(path("/limited.bin"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/limited.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.to_vec())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("limited.bin");
+        // A backoff this long would fail the timing check below
+        let opts = DownloadOptions {
+            retry_policy: Some(RetryPolicy {
+                base_delay_ms: 10_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        download_file(
+            &format!("{}/limited.bin", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+        let elapsed = started.elapsed();
+
+        assert!(
+            elapsed >= Duration::from_secs(1),
+            "retried after {elapsed:?}"
+        );
+        assert!(
+            elapsed < Duration::from_secs(5),
+            "retried after {elapsed:?}"
+        );
+        assert_eq!(std::fs::read(&save).unwrap(), body);
+    }
+
     #[tokio::test]
     async fn test_retry_exhausted() {
         let server = MockServer::start().await;
@@ -541,6 +867,126 @@ mod tests {
         assert!(!save.exists(), "final file should not exist");
     }
 
+    #[tokio::test]
+    async fn test_cancel_interrupts_retry_backoff() {
+        use crate::engine::retry::RetryPolicy;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/backoff.bin"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("backoff.bin");
+        let opts = DownloadOptions {
+            retry_policy: Some(RetryPolicy {
+                base_delay_ms: 60_000,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = download_file_with_token(
+            "",
+            &format!("{}/backoff.bin", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+            token,
+            None,
+            None,
+        )
+        .await;
+
+        assert!(matches!(result, Err(CraneError::Cancelled)), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_pending_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/stalled.bin"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(60)))
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("stalled.bin");
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = download_file_with_token(
+            "",
+            &format!("{}/stalled.bin", server.uri()),
+            &save,
+            &DownloadOptions::default(),
+            noop_progress,
+            token,
+            None,
+            None,
+        )
+        .await;
+
+        assert!(matches!(result, Err(CraneError::Cancelled)), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!save.exists());
+    }
+
+    #[tokio::test]
+    async fn test_custom_retry_policy_limits_attempts() {
+        use crate::engine::retry::RetryPolicy;
+
+        let server = MockServer::start().await;
+
+        // max_attempts = 6 → 1 initial + 5 retries, with short delays
+        Mock::given(method("GET"))
+            .and(path("/policy-fail.bin"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(6)
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("policy-fail.bin");
+        let opts = DownloadOptions {
+            retry_policy: Some(RetryPolicy {
+                max_attempts: 6,
+                base_delay_ms: 10,
+                multiplier: 1.5,
+                max_delay_ms: 50,
+                jitter: Some(0.5),
+            }),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let err = download_file(
+            &format!("{}/policy-fail.bin", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await;
+
+        assert!(err.is_err(), "should have failed after retries exhausted");
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+
     #[tokio::test]
     async fn test_custom_user_agent() {
         let server = MockServer::start().await;
@@ -578,6 +1024,61 @@ mod tests {
         assert_eq!(result.downloaded_bytes, body.len() as u64);
     }
 
+    #[tokio::test]
+    async fn test_auth_sends_authorization_header() {
+        let server = MockServer::start().await;
+        let body = b"secret-file";
+
+        let cases = [
+            (
+                Auth::Basic {
+                    user: "user".to_string(),
+                    pass: "pass".to_string(),
+                },
+                "Basic dXNlcjpwYXNz",
+            ),
+            (
+                Auth::Bearer {
+                    token: "tok123".to_string(),
+                },
+                "Bearer tok123",
+            ),
+        ];
+        // Without the expected header the server answers 404
+        for (_, header) in &cases {
+            Mock::given(method("GET"))
+                .and(path("/private.bin"))
+                .and(wiremock::matchers::header("Authorization", *header))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_bytes(body.to_vec())
+                        .insert_header("Content-Length", body.len().to_string().as_str()),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let tmp = TempDir::new().unwrap();
+        for (n, (auth, _)) in cases.into_iter().enumerate() {
+            let save = tmp.path().join(format!("private{n}.bin"));
+            let opts = DownloadOptions {
+                auth: Some(auth),
+                ..Default::default()
+            };
+            let result = download_file(
+                &format!("{}/private.bin", server.uri()),
+                &save,
+                &opts,
+                noop_progress,
+            )
+            .await
+            .unwrap();
+            assert_eq!(result.downloaded_bytes, body.len() as u64);
+            assert_eq!(std::fs::read(&save).unwrap(), body);
+        }
+    }
+
     #[tokio::test]
     async fn test_custom_referrer() {
         let server = MockServer::start().await;
@@ -849,26 +1350,77 @@ mod tests {
         )
         .await;
 
-        // Should fail because the stream ends before Content-Length is reached
-        // (reqwest detects the mismatch and returns an error), OR the download
-        // succeeds but with fewer bytes. Either way, the final file at `save`
-        // should not contain the full expected data.
-        match result {
-            Err(_) => {
-                // Expected: network error due to truncation
-                assert!(
-                    !save.exists(),
-                    "final file should be cleaned up after failure"
-                );
-            }
-            Ok(r) => {
-                // Some HTTP clients may silently accept the truncated body.
-                // In this case, verify we got fewer bytes than expected.
-                assert!(
-                    r.downloaded_bytes < body.len() as u64,
-                    "truncated response should yield fewer bytes than Content-Length"
-                );
+        // Every attempt ends short of Content-Length, so the download must
+        // fail rather than produce a short file.
+        assert!(result.is_err(), "truncated response must not succeed");
+        assert!(
+            !save.exists(),
+            "final file should be cleaned up after failure"
+        );
+        assert!(!temp_path(&save).exists(), "temp file should be cleaned up");
+    }
+
+    #[tokio::test]
+    async fn chaos_truncated_response_recovers_on_retry() {
+        // First response is cut short; the retry gets the full body.
+        use super::super::chaos_responders::TruncatingResponder;
+        use crate::engine::retry::RetryPolicy;
+
+        let server = MockServer::start().await;
+        let body: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+
+        Mock::given(method("GET"))
+            .and(path("/flaky.bin"))
+            .respond_with(TruncatingResponder {
+                body: body.clone(),
+                truncate_after: 5_000,
+            })
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.clone())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("flaky.bin");
+        let opts = DownloadOptions {
+            retry_policy: Some(RetryPolicy {
+                base_delay_ms: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let result = download_file(
+            &format!("{}/flaky.bin", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.downloaded_bytes, body.len() as u64);
+        assert_eq!(std::fs::read(&save).unwrap(), body);
+    }
+
+    #[test]
+    fn test_check_complete_rejects_short_body() {
+        assert!(check_complete(10, Some(10)).is_ok());
+        assert!(check_complete(10, None).is_ok());
+        match check_complete(5, Some(10)) {
+            Err(CraneError::Truncated { expected, received }) => {
+                assert_eq!((expected, received), (10, 5));
             }
+            other => panic!("expected Truncated, got {other:?}"),
         }
     }
 
@@ -1229,4 +1781,306 @@ mod tests {
         assert_eq!(result.hash_verified, None);
         assert!(save.exists());
     }
+
+    /// Mount a GET for `file_path` plus a checksum sidecar at `sidecar_path`.
+    async fn mount_with_sidecar(
+        server: &MockServer,
+        file_path: &str,
+        body: &[u8],
+        sidecar_path: &str,
+        sidecar: String,
+    ) {
+        Mock::given(method("GET"))
+            .and(path(file_path))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.to_vec())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(sidecar_path))
+            .respond_with(ResponseTemplate::new(200).set_body_string(sidecar))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_verification_success() {
+        use sha2::{Digest, Sha256};
+
+        let server = MockServer::start().await;
+        let body = b"sidecar verified body";
+        // Multi-file listing as produced by `sha256sum *`
+        let sidecar = format!(
+            "{}  other.txt\n{:x} *sidecar_ok.txt\n",
+            "0".repeat(64),
+            Sha256::digest(body)
+        );
+        mount_with_sidecar(
+            &server,
+            "/sidecar_ok.txt",
+            body,
+            "/sidecar_ok.txt.sha256",
+            sidecar,
+        )
+        .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("sidecar_ok.txt");
+        let opts = DownloadOptions {
+            verify_from_sidecar: true,
+            ..Default::default()
+        };
+
+        let result = download_file(
+            &format!("{}/sidecar_ok.txt", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.hash_verified, Some(true));
+        assert!(save.exists());
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_verification_mismatch_falls_back_to_md5() {
+        let server = MockServer::start().await;
+        let body = b"sidecar mismatch body";
+        // No .sha256 mounted (404), so the .md5 sidecar is used
+        mount_with_sidecar(
+            &server,
+            "/sidecar_bad.txt",
+            body,
+            "/sidecar_bad.txt.md5",
+            format!("{}  sidecar_bad.txt\n", "0".repeat(32)),
+        )
+        .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("sidecar_bad.txt");
+        let opts = DownloadOptions {
+            verify_from_sidecar: true,
+            ..Default::default()
+        };
+
+        let err = download_file(
+            &format!("{}/sidecar_bad.txt", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, CraneError::HashMismatch { .. }));
+        assert!(!save.exists(), "file should be deleted after hash mismatch");
+    }
+
+    #[tokio::test]
+    async fn test_sidecar_missing_skips_verification() {
+        let server = MockServer::start().await;
+        let body = b"no sidecar body";
+        Mock::given(method("GET"))
+            .and(path("/no_sidecar.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(body.to_vec()))
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("no_sidecar.txt");
+        let opts = DownloadOptions {
+            verify_from_sidecar: true,
+            ..Default::default()
+        };
+
+        let result = download_file(
+            &format!("{}/no_sidecar.txt", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.hash_verified, None);
+        assert!(save.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_routes_through_proxy() {
+        // The mock server plays the HTTP proxy: it receives the absolute-form
+        // request for a host that doesn't resolve.
+        let proxy = MockServer::start().await;
+        let body = b"via proxy";
+
+        Mock::given(method("GET"))
+            .and(path("/proxied.bin"))
+            .and(wiremock::matchers::header("host", "files.crane.invalid"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.to_vec())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .expect(1)
+            .mount(&proxy)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("proxied.bin");
+        let opts = DownloadOptions {
+            proxy: Some(proxy.uri()),
+            ..Default::default()
+        };
+
+        download_file(
+            "http://files.crane.invalid/proxied.bin",
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&save).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_host_bypasses_proxy() {
+        let server = MockServer::start().await;
+        let body = b"direct";
+
+        Mock::given(method("GET"))
+            .and(path("/direct.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.to_vec())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("direct.bin");
+        let opts = DownloadOptions {
+            // Nothing listens on the discard port; using it would fail
+            proxy: Some("http://127.0.0.1:9".to_string()),
+            no_proxy: Some(vec!["127.0.0.1".to_string()]),
+            ..Default::default()
+        };
+
+        download_file(
+            &format!("{}/direct.bin", server.uri()),
+            &save,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&save).unwrap(), body);
+    }
+
+    // ── Test: download into a writer ──
+
+    #[tokio::test]
+    async fn test_download_to_writer_fills_buffer() {
+        let server = MockServer::start().await;
+        let body: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        Mock::given(method("GET"))
+            .and(path("/piped.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(body.clone())
+                    .insert_header("Content-Length", body.len().to_string().as_str()),
+            )
+            .mount(&server)
+            .await;
+
+        let last = Arc::new(Mutex::new(None));
+        let last_clone = last.clone();
+        let mut buffer = Vec::new();
+        let written = download_to_writer(
+            &format!("{}/piped.bin", server.uri()),
+            &mut buffer,
+            &DownloadOptions::default(),
+            move |p: &DownloadProgress| *last_clone.lock().unwrap() = Some(p.downloaded_size),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(written, body.len() as u64);
+        assert_eq!(buffer, body);
+        assert_eq!(*last.lock().unwrap(), Some(body.len() as u64));
+    }
+
+    #[tokio::test]
+    async fn test_download_to_writer_retries_only_before_body() {
+        use super::super::chaos_responders::FailThenSucceedResponder;
+        use crate::engine::retry::RetryPolicy;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::AsyncReadExt;
+
+        let server = MockServer::start().await;
+        let body = b"streamed after two failures".to_vec();
+        Mock::given(method("GET"))
+            .and(path("/flaky.bin"))
+            .respond_with(FailThenSucceedResponder::new(body.clone(), 2))
+            .mount(&server)
+            .await;
+        let opts = DownloadOptions {
+            retry_policy: Some(RetryPolicy {
+                base_delay_ms: 10,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Nothing was written by the failed attempts, so they are retried
+        let mut buffer = Vec::new();
+        download_to_writer(
+            &format!("{}/flaky.bin", server.uri()),
+            &mut buffer,
+            &opts,
+            noop_progress,
+        )
+        .await
+        .unwrap();
+        assert_eq!(buffer, body);
+
+        // A server that drops the connection a quarter of the way in
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                counted.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0u8; 1024];
+                let _ = conn.read(&mut request).await;
+                let head = "HTTP/1.1 200 OK\r\nContent-Length: 4096\r\nConnection: close\r\n\r\n";
+                let _ = conn.write_all(head.as_bytes()).await;
+                let _ = conn.write_all(&[0xAB; 1024]).await;
+            }
+        });
+
+        // Part of the body is already out: no second attempt
+        let mut buffer = Vec::new();
+        let result = download_to_writer(
+            &format!("http://{addr}/cut.bin"),
+            &mut buffer,
+            &opts,
+            noop_progress,
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(buffer, vec![0xAB; 1024]);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
 }
@@ -1,5 +1,10 @@
 pub mod download;
 pub mod multi;
+pub mod retry;
+pub mod speed;
 
+// Shared test responders, kept as upstream wrote them; not every one is
+// used by a scenario yet
 #[cfg(test)]
+#[allow(dead_code, clippy::manual_is_multiple_of)]
 pub(crate) mod chaos_responders;
@@ -0,0 +1,251 @@
+// Retry policy with exponential backoff for transient download failures.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::CraneError;
+
+/// Longest `Retry-After` wait honoured; servers asking for more are retried
+/// after this long anyway.
+pub(crate) const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+/// How many times to attempt a request and how long to wait between attempts.
+///
+/// The delay before retry `n` (1-based) is
+/// `min(base_delay_ms * multiplier^(n-1), max_delay_ms)`, optionally
+/// spread by ±`jitter` (a fraction of the delay) to avoid synchronized
+/// retries across connections. The default reproduces Crane's original
+/// schedule: 4 attempts with 1s, 2s, 4s between them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one. `0` is treated as `1`.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub multiplier: f64,
+    pub max_delay_ms: u64,
+    /// Fraction in `0.0..=1.0`; `None` disables jitter.
+    pub jitter: Option<f64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay_ms: 1000,
+            multiplier: 2.0,
+            max_delay_ms: 30_000,
+            jitter: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub fn max_retries(&self) -> u32 {
+        self.max_attempts.max(1) - 1
+    }
+
+    /// Backoff before retry `retry` (1-based), without jitter.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exp = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.base_delay_ms as f64 * self.multiplier.max(1.0).powi(exp);
+        Duration::from_millis(delay.min(self.max_delay_ms as f64) as u64)
+    }
+
+    /// Delay before retry `retry` (1-based), with jitter applied.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        match self.jitter {
+            Some(jitter) if jitter > 0.0 => {
+                let jitter = jitter.min(1.0);
+                let factor = 1.0 - jitter + 2.0 * jitter * fastrand::f64();
+                backoff.mul_f64(factor)
+            }
+            _ => backoff,
+        }
+    }
+
+    /// Delay before retry `retry`: the server's `Retry-After` when the failed
+    /// attempt carried one (capped at `MAX_RETRY_AFTER`), otherwise the
+    /// backoff from [`delay`](Self::delay).
+    pub fn delay_for(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(wait) => wait.min(MAX_RETRY_AFTER),
+            None => self.delay(retry),
+        }
+    }
+}
+
+/// Whether an HTTP status is worth retrying: rate limiting and server errors.
+pub(crate) fn is_retryable_status(status: u16) -> bool {
+    status == 429 || status >= 500
+}
+
+/// How long a 429 or 503 response asks the client to wait, from its
+/// `Retry-After` header in either delta-seconds or HTTP-date form. A date in
+/// the past means retry now.
+pub(crate) fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let status = response.status().as_u16();
+    if status != 429 && status != 503 {
+        return None;
+    }
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+    parse_retry_after(value, chrono::Utc::now())
+}
+
+fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&chrono::Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// A failed attempt that is about to be retried, or that used up the
+/// last retry (`exhausted`).
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    /// 1-based number of the attempt that failed.
+    pub attempt: u32,
+    pub error_message: String,
+    /// HTTP status code, when the failure was an HTTP error.
+    pub error_code: Option<String>,
+    pub exhausted: bool,
+}
+
+/// Callback invoked for every retried or exhausted attempt.
+pub type RetryCallback = Arc<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+/// Report a failed attempt (0-based `attempt` loop index) to `on_retry`.
+pub(crate) fn report_retry(
+    on_retry: &Option<RetryCallback>,
+    attempt: u32,
+    error: &CraneError,
+    exhausted: bool,
+) {
+    if let Some(cb) = on_retry {
+        let error_code = match error {
+            CraneError::Http { status, .. } => Some(status.to_string()),
+            _ => None,
+        };
+        cb(&RetryAttempt {
+            attempt: attempt + 1,
+            error_message: error.to_string(),
+            error_code,
+            exhausted,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_matches_original_schedule() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.max_retries(), 3);
+        let delays: Vec<Duration> = (1..=policy.max_retries())
+            .map(|n| policy.delay(n))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_millis(1000),
+                Duration::from_millis(2000),
+                Duration::from_millis(4000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backoff_is_capped_at_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 8,
+            base_delay_ms: 500,
+            multiplier: 3.0,
+            max_delay_ms: 10_000,
+            jitter: None,
+        };
+        let delays: Vec<u64> = (1..=policy.max_retries())
+            .map(|n| policy.backoff(n).as_millis() as u64)
+            .collect();
+        assert_eq!(
+            delays,
+            vec![500, 1500, 4500, 10_000, 10_000, 10_000, 10_000]
+        );
+    }
+
+    #[test]
+    fn test_zero_attempts_means_no_retries() {
+        let policy = RetryPolicy {
+            max_attempts: 0,
+            ..Default::default()
+        };
+        assert_eq!(policy.max_retries(), 0);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let policy = RetryPolicy {
+            jitter: Some(0.25),
+            ..Default::default()
+        };
+        for retry in 1..=3 {
+            let backoff = policy.backoff(retry);
+            for _ in 0..200 {
+                let delay = policy.delay(retry);
+                assert!(delay >= backoff.mul_f64(0.75), "{delay:?} below bound");
+                assert!(delay <= backoff.mul_f64(1.25), "{delay:?} above bound");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_retry_after_forms() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        // A date already passed means no wait
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+        assert_eq!(parse_retry_after("-5", now), None);
+    }
+
+    #[test]
+    fn test_retry_after_overrides_and_is_capped() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.delay_for(1, Some(Duration::from_secs(7))),
+            Duration::from_secs(7)
+        );
+        assert_eq!(
+            policy.delay_for(1, Some(Duration::from_secs(86_400))),
+            MAX_RETRY_AFTER
+        );
+        assert_eq!(policy.delay_for(2, None), policy.delay(2));
+    }
+}
@@ -0,0 +1,148 @@
+// Smoothed transfer speed and ETA for progress reports
+
+use std::time::Duration;
+
+use crate::types::DownloadOptions;
+
+/// Weight of the newest sample when `DownloadOptions::speed_smoothing` is unset.
+pub const SPEED_SMOOTHING: f64 = 0.3;
+/// Accepted smoothing factors: 1.0 reports raw samples, smaller values
+/// react more slowly.
+pub const SPEED_SMOOTHING_RANGE: std::ops::RangeInclusive<f64> = 0.01..=1.0;
+
+/// Exponential moving average over speed samples, and over the ETA derived
+/// from them, so a single bursty read doesn't swing the displayed figures.
+#[derive(Debug, Clone)]
+pub struct SpeedEstimator {
+    alpha: f64,
+    instant: f64,
+    speed: f64,
+    eta: Option<f64>,
+}
+
+impl SpeedEstimator {
+    /// `alpha` is the weight of each new sample, clamped into
+    /// `SPEED_SMOOTHING_RANGE`.
+    pub fn new(alpha: f64) -> Self {
+        let alpha = if alpha.is_nan() {
+            SPEED_SMOOTHING
+        } else {
+            alpha.clamp(*SPEED_SMOOTHING_RANGE.start(), *SPEED_SMOOTHING_RANGE.end())
+        };
+        Self {
+            alpha,
+            instant: 0.0,
+            speed: 0.0,
+            eta: None,
+        }
+    }
+
+    /// Estimator using `options.speed_smoothing`, or the default.
+    pub fn from_options(options: &DownloadOptions) -> Self {
+        Self::new(options.speed_smoothing.unwrap_or(SPEED_SMOOTHING))
+    }
+
+    /// Record `bytes` transferred over `elapsed`, with `remaining` bytes
+    /// still to go when the size is known. Empty intervals are ignored.
+    pub fn sample(&mut self, bytes: u64, elapsed: Duration, remaining: Option<u64>) {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return;
+        }
+        self.instant = bytes as f64 / secs;
+        self.speed = self.smooth(self.speed, self.instant);
+
+        self.eta = match remaining {
+            Some(remaining) if self.speed > 0.0 => {
+                let raw = remaining as f64 / self.speed;
+                Some(match self.eta {
+                    Some(prev) => self.alpha * raw + (1.0 - self.alpha) * prev,
+                    None => raw,
+                })
+            }
+            _ => None,
+        };
+    }
+
+    /// Smoothed speed in bytes/sec.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Speed over the latest sample alone, in bytes/sec.
+    pub fn instant_speed(&self) -> f64 {
+        self.instant
+    }
+
+    /// Smoothed seconds remaining, if the size is known and data is flowing.
+    pub fn eta_seconds(&self) -> Option<u64> {
+        self.eta.map(|eta| eta.round() as u64)
+    }
+
+    fn smooth(&self, prev: f64, sample: f64) -> f64 {
+        // Seed from the first non-zero sample rather than decaying up from 0
+        if prev > 0.0 {
+            self.alpha * sample + (1.0 - self.alpha) * prev
+        } else {
+            sample
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn std_dev(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn test_smoothed_speed_and_eta_vary_less_than_raw() {
+        // ~500 KB/s on average, arriving in uneven bursts
+        let bursts: [u64; 12] = [
+            900_000, 100_000, 750_000, 250_000, 1_000_000, 0, 600_000, 400_000, 850_000, 150_000,
+            700_000, 300_000,
+        ];
+        let total: u64 = 20_000_000;
+        let mut estimator = SpeedEstimator::new(SPEED_SMOOTHING);
+        let mut downloaded = 0;
+        let (mut raw, mut smoothed) = (Vec::new(), Vec::new());
+        let (mut raw_eta, mut smoothed_eta) = (Vec::new(), Vec::new());
+
+        for bytes in bursts {
+            downloaded += bytes;
+            estimator.sample(bytes, Duration::from_secs(1), Some(total - downloaded));
+            raw.push(estimator.instant_speed());
+            smoothed.push(estimator.speed());
+            if estimator.instant_speed() > 0.0 {
+                raw_eta.push((total - downloaded) as f64 / estimator.instant_speed());
+            }
+            smoothed_eta.push(estimator.eta_seconds().unwrap() as f64);
+        }
+
+        assert_eq!(raw, bursts.map(|b| b as f64));
+        assert!(
+            std_dev(&smoothed) < std_dev(&raw) / 2.0,
+            "smoothed {smoothed:?} vs raw {raw:?}"
+        );
+        assert!(std_dev(&smoothed_eta) < std_dev(&raw_eta) / 2.0);
+    }
+
+    #[test]
+    fn test_alpha_one_reports_raw_samples() {
+        let mut estimator = SpeedEstimator::new(1.0);
+        estimator.sample(1000, Duration::from_secs(1), Some(4000));
+        estimator.sample(3000, Duration::from_secs(1), Some(1000));
+        assert_eq!(estimator.speed(), 3000.0);
+        assert_eq!(estimator.eta_seconds(), Some(0));
+
+        // Out-of-range factors are clamped rather than freezing the estimate
+        let mut frozen = SpeedEstimator::new(0.0);
+        frozen.sample(1000, Duration::from_secs(1), None);
+        frozen.sample(2000, Duration::from_secs(1), None);
+        assert!(frozen.speed() > 1000.0);
+        assert_eq!(frozen.eta_seconds(), None);
+    }
+}
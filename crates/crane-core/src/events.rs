@@ -1 +1,295 @@
+// Progress event broadcasting for Crane downloads.
 
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::types::{DownloadPhase, DownloadProgress, DownloadResult, DownloadStatus};
+
+/// Events buffered per subscriber before it starts lagging.
+const PROGRESS_CHANNEL_CAPACITY: usize = 256;
+
+/// Default minimum gap between two progress events for one download.
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Downloads silent for this long are dropped from the coalescer's table.
+const COALESCER_STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Sent once when a download finishes successfully.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadCompleted {
+    pub id: String,
+    pub final_path: String,
+    pub total_bytes: u64,
+    /// `None` when no expected hash was checked.
+    pub hash_verified: Option<bool>,
+    pub elapsed_ms: u64,
+}
+
+impl DownloadCompleted {
+    pub fn new(id: &str, result: &DownloadResult) -> Self {
+        Self {
+            id: id.to_string(),
+            final_path: result.final_path.to_string_lossy().into_owned(),
+            total_bytes: result.downloaded_bytes,
+            hash_verified: result.hash_verified,
+            elapsed_ms: result.elapsed_ms,
+        }
+    }
+}
+
+/// Sent when a download moves from one status to another, e.g. `Queued`
+/// when it is added at capacity and `Downloading` once it is promoted.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StatusChanged {
+    pub id: String,
+    pub from: DownloadStatus,
+    pub to: DownloadStatus,
+}
+
+/// Sent while a download runs with less free space on its save
+/// filesystem than the configured threshold.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LowDiskSpace {
+    pub id: String,
+    /// Bytes free when the space was checked.
+    pub available: u64,
+    pub threshold: u64,
+}
+
+/// Rate-limits progress events to at most one per download every
+/// `interval`. An event that reaches the total size, or moves the download
+/// into a new phase, always gets through.
+#[derive(Debug)]
+pub struct ProgressCoalescer {
+    interval: Duration,
+    last_sent: Mutex<HashMap<String, (Instant, DownloadPhase)>>,
+}
+
+impl ProgressCoalescer {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether `progress` should be delivered now.
+    pub fn admit(&self, progress: &DownloadProgress) -> bool {
+        self.admit_at(progress, Instant::now())
+    }
+
+    fn admit_at(&self, progress: &DownloadProgress, now: Instant) -> bool {
+        let mut last_sent = self.last_sent.lock().unwrap();
+        let id = &progress.download_id;
+        if is_final(progress) {
+            last_sent.remove(id);
+            return true;
+        }
+        match last_sent.get(id) {
+            Some((at, phase))
+                if *phase == progress.phase && now.duration_since(*at) < self.interval =>
+            {
+                false
+            }
+            Some(_) => {
+                last_sent.insert(id.clone(), (now, progress.phase));
+                true
+            }
+            None => {
+                last_sent.retain(|_, (at, _)| now.duration_since(*at) < COALESCER_STALE_AFTER);
+                last_sent.insert(id.clone(), (now, progress.phase));
+                true
+            }
+        }
+    }
+}
+
+fn is_final(progress: &DownloadProgress) -> bool {
+    progress
+        .total_size
+        .is_some_and(|total| progress.downloaded_size >= total)
+}
+
+/// Fans out `DownloadProgress` updates (plus completions, status changes
+/// and low-space warnings) to any number of subscribers. Publishing never
+/// blocks; a subscriber that falls behind receives `RecvError::Lagged` and
+/// skips ahead to the newest events.
+#[derive(Debug, Clone)]
+pub struct ProgressBus {
+    sender: broadcast::Sender<DownloadProgress>,
+    completed: broadcast::Sender<DownloadCompleted>,
+    status: broadcast::Sender<StatusChanged>,
+    low_disk_space: broadcast::Sender<LowDiskSpace>,
+    coalescer: Option<Arc<ProgressCoalescer>>,
+}
+
+impl ProgressBus {
+    /// A bus that forwards every progress event.
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let (completed, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let (status, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        let (low_disk_space, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            completed,
+            status,
+            low_disk_space,
+            coalescer: None,
+        }
+    }
+
+    /// A bus that coalesces progress per download to one event every
+    /// `interval` (see [`ProgressCoalescer`]); `Duration::ZERO` forwards all.
+    pub fn with_interval(interval: Duration) -> Self {
+        Self {
+            coalescer: (!interval.is_zero()).then(|| Arc::new(ProgressCoalescer::new(interval))),
+            ..Self::new()
+        }
+    }
+
+    /// Receive every progress event published after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<DownloadProgress> {
+        self.sender.subscribe()
+    }
+
+    /// Publish a progress event. Dropped silently when nobody is listening,
+    /// or when the coalescer holds it back.
+    pub fn publish(&self, progress: DownloadProgress) {
+        if let Some(coalescer) = &self.coalescer {
+            if !coalescer.admit(&progress) {
+                return;
+            }
+        }
+        let _ = self.sender.send(progress);
+    }
+
+    /// Receive every completion event published after this call.
+    pub fn subscribe_completed(&self) -> broadcast::Receiver<DownloadCompleted> {
+        self.completed.subscribe()
+    }
+
+    /// Publish a completion event. Dropped silently when nobody is listening.
+    pub fn publish_completed(&self, event: DownloadCompleted) {
+        let _ = self.completed.send(event);
+    }
+
+    /// Receive every status change published after this call.
+    pub fn subscribe_status(&self) -> broadcast::Receiver<StatusChanged> {
+        self.status.subscribe()
+    }
+
+    /// Publish a status change. Dropped silently when nobody is listening.
+    pub fn publish_status(&self, event: StatusChanged) {
+        let _ = self.status.send(event);
+    }
+
+    /// Receive every low-space warning published after this call.
+    pub fn subscribe_low_disk_space(&self) -> broadcast::Receiver<LowDiskSpace> {
+        self.low_disk_space.subscribe()
+    }
+
+    /// Publish a low-space warning. Dropped silently when nobody is
+    /// listening.
+    pub fn publish_low_disk_space(&self, event: LowDiskSpace) {
+        let _ = self.low_disk_space.send(event);
+    }
+}
+
+impl Default for ProgressBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(id: &str, downloaded_size: u64) -> DownloadProgress {
+        DownloadProgress {
+            download_id: id.to_string(),
+            downloaded_size,
+            total_size: Some(100),
+            speed: 0.0,
+            instant_speed: 0.0,
+            eta_seconds: None,
+            connections: vec![],
+            phase: DownloadPhase::Downloading,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_all_subscribers() {
+        let bus = ProgressBus::new();
+        let mut a = bus.subscribe();
+        let mut b = bus.subscribe();
+
+        bus.publish(progress("dl-1", 10));
+
+        assert_eq!(a.recv().await.unwrap().download_id, "dl-1");
+        assert_eq!(b.recv().await.unwrap().downloaded_size, 10);
+    }
+
+    #[test]
+    fn test_publish_without_subscribers_is_noop() {
+        let bus = ProgressBus::new();
+        bus.publish(progress("dl-1", 10));
+        let mut rx = bus.subscribe();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_coalescer_burst_respects_interval_and_keeps_final() {
+        let interval = Duration::from_millis(100);
+        let coalescer = ProgressCoalescer::new(interval);
+        let start = Instant::now();
+
+        // 8 connections reporting every ~5ms: 100 events over half a second
+        let admitted: Vec<(Instant, u64)> = (1..=100)
+            .map(|i| (start + Duration::from_millis(i * 5), i))
+            .filter(|(at, size)| coalescer.admit_at(&progress("dl-1", *size), *at))
+            .collect();
+
+        assert!(admitted.len() < 10, "{} events got through", admitted.len());
+        for pair in admitted.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            if next.1 < 100 {
+                assert!(next.0.duration_since(prev.0) >= interval);
+            }
+        }
+        assert_eq!(admitted.last().unwrap().1, 100);
+    }
+
+    #[test]
+    fn test_coalescer_tracks_downloads_and_phases_separately() {
+        let coalescer = ProgressCoalescer::new(Duration::from_millis(100));
+        let now = Instant::now();
+
+        assert!(coalescer.admit_at(&progress("dl-1", 10), now));
+        assert!(coalescer.admit_at(&progress("dl-2", 10), now));
+        assert!(!coalescer.admit_at(&progress("dl-1", 20), now));
+
+        let mut merging = progress("dl-1", 0);
+        merging.phase = DownloadPhase::Merging;
+        assert!(coalescer.admit_at(&merging, now));
+    }
+
+    #[tokio::test]
+    async fn test_bus_with_interval_coalesces() {
+        let bus = ProgressBus::with_interval(Duration::from_secs(60));
+        let mut rx = bus.subscribe();
+
+        for size in [10, 20, 30, 100] {
+            bus.publish(progress("dl-1", size));
+        }
+
+        assert_eq!(rx.recv().await.unwrap().downloaded_size, 10);
+        assert_eq!(rx.recv().await.unwrap().downloaded_size, 100);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,48 +1,305 @@
-use crate::types::CraneError;
+pub mod sidecar;
+
+use crate::types::{CraneError, DownloadOptions, ExpectedHash};
 use md5::Md5;
-use sha2::{Digest, Sha256};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 use std::path::Path;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Readers [`compute_hash_parallel`] uses when verifying a download.
+pub const PARALLEL_HASH_READERS: usize = 4;
+/// Bytes each reader of [`compute_hash_parallel`] reads at a time.
+const HASH_SEGMENT_SIZE: u64 = 4 * 1024 * 1024;
+/// Segments a reader may read ahead of the hasher.
+const HASH_READ_AHEAD: usize = 2;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum HashAlgorithm {
     Sha256,
     Md5,
+    Sha1,
+    Sha512,
+    /// Whichever of the above the expected digest's length points to; see
+    /// [`infer_algorithm`].
+    Auto,
+}
+
+impl HashAlgorithm {
+    /// Length of this algorithm's hex digest, or `None` for `Auto`.
+    pub fn hex_len(self) -> Option<usize> {
+        match self {
+            Self::Md5 => Some(32),
+            Self::Sha1 => Some(40),
+            Self::Sha256 => Some(64),
+            Self::Sha512 => Some(128),
+            Self::Auto => None,
+        }
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = CraneError;
+
+    /// Parse an algorithm name as the UI and sidecar files spell it
+    /// (`sha256`, `md5`, `sha1`, `sha512`, `auto`; case-insensitive).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(Self::Sha256),
+            "md5" => Ok(Self::Md5),
+            "sha1" => Ok(Self::Sha1),
+            "sha512" => Ok(Self::Sha512),
+            "auto" => Ok(Self::Auto),
+            _ => Err(CraneError::Config(format!(
+                "Unsupported hash algorithm: {s}"
+            ))),
+        }
+    }
+}
+
+/// The algorithm a hex digest most likely comes from, by its length:
+/// 32 digits for MD5, 40 for SHA-1, 64 for SHA-256 and 128 for SHA-512.
+/// Any other length, or a non-hex digit, is an error.
+pub fn infer_algorithm(hex: &str) -> Result<HashAlgorithm, CraneError> {
+    let hex = hex.trim();
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(CraneError::Config(format!("Not a hex digest: {hex}")));
+    }
+    [
+        HashAlgorithm::Md5,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha512,
+    ]
+    .into_iter()
+    .find(|algorithm| algorithm.hex_len() == Some(hex.len()))
+    .ok_or_else(|| {
+        CraneError::Config(format!(
+            "Can't tell the hash algorithm of a {}-digit digest",
+            hex.len()
+        ))
+    })
+}
+
+/// `expected` with an `Auto` algorithm replaced by the one its digest's
+/// length points to.
+pub fn resolve_algorithm(expected: &ExpectedHash) -> Result<ExpectedHash, CraneError> {
+    let algorithm = match expected.algorithm {
+        HashAlgorithm::Auto => infer_algorithm(&expected.value)?,
+        algorithm => algorithm,
+    };
+    Ok(ExpectedHash {
+        algorithm,
+        value: expected.value.clone(),
+    })
+}
+
+/// Error for hashing with `Auto`, which only names an algorithm once there
+/// is a digest to infer it from.
+fn auto_unresolved() -> CraneError {
+    CraneError::Config("Can't hash with `auto` without an expected digest".to_string())
+}
+
+/// Digest fed incrementally, e.g. with bytes as they are merged, so a
+/// finished file doesn't have to be read again to verify it.
+pub enum StreamHasher {
+    Sha256(Sha256),
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha512(Sha512),
+}
+
+impl StreamHasher {
+    /// # Panics
+    ///
+    /// With `HashAlgorithm::Auto`; resolve it first with
+    /// [`resolve_algorithm`].
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Md5 => Self::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Self::Sha1(Sha1::new()),
+            HashAlgorithm::Sha512 => Self::Sha512(Sha512::new()),
+            HashAlgorithm::Auto => panic!("hash algorithm `auto` must be resolved first"),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Md5(hasher) => hasher.update(data),
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Lowercase hex digest of everything fed so far.
+    pub fn finalize(self) -> String {
+        match self {
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
 }
 
 /// Compute hash of a file using the specified algorithm.
 /// Reads in 64KB chunks to avoid loading the entire file into memory.
 pub async fn compute_hash(path: &Path, algorithm: HashAlgorithm) -> Result<String, CraneError> {
+    if algorithm == HashAlgorithm::Auto {
+        return Err(auto_unresolved());
+    }
     let mut file = tokio::fs::File::open(path).await?;
     let mut buf = vec![0u8; 64 * 1024];
+    let mut hasher = StreamHasher::new(algorithm);
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
 
-    match algorithm {
-        HashAlgorithm::Sha256 => {
-            let mut hasher = Sha256::new();
-            loop {
-                let n = file.read(&mut buf).await?;
-                if n == 0 {
-                    break;
+/// Same digest as [`compute_hash`], but `readers` tasks read disjoint
+/// segments of the file concurrently while a single hasher consumes them in
+/// file order, so a fast disk isn't held up by one read-then-hash loop.
+/// Memory stays bounded by `readers × HASH_READ_AHEAD` segments.
+pub async fn compute_hash_parallel(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    readers: usize,
+) -> Result<String, CraneError> {
+    if algorithm == HashAlgorithm::Auto {
+        return Err(auto_unresolved());
+    }
+    let readers = readers.max(1);
+    let len = tokio::fs::metadata(path).await?.len();
+    let segments = len.div_ceil(HASH_SEGMENT_SIZE);
+
+    // Reader `r` reads segments r, r + readers, r + 2 × readers, ...
+    let mut receivers = Vec::with_capacity(readers);
+    for reader in 0..readers {
+        let (tx, rx) = tokio::sync::mpsc::channel(HASH_READ_AHEAD);
+        receivers.push(rx);
+        let path = path.to_path_buf();
+        tokio::spawn(async move {
+            let mut file = match tokio::fs::File::open(&path).await {
+                Ok(file) => file,
+                Err(err) => {
+                    let _ = tx.send(Err(err)).await;
+                    return;
                 }
-                hasher.update(&buf[..n]);
-            }
-            Ok(format!("{:x}", hasher.finalize()))
-        }
-        HashAlgorithm::Md5 => {
-            let mut hasher = Md5::new();
-            loop {
-                let n = file.read(&mut buf).await?;
-                if n == 0 {
-                    break;
+            };
+            for segment in (reader as u64..segments).step_by(readers) {
+                let start = segment * HASH_SEGMENT_SIZE;
+                let size = (len - start).min(HASH_SEGMENT_SIZE) as usize;
+                let data = read_segment(&mut file, start, size).await;
+                let failed = data.is_err();
+                // A closed channel means the hasher has already failed
+                if tx.send(data).await.is_err() || failed {
+                    return;
                 }
-                hasher.update(&buf[..n]);
             }
-            Ok(format!("{:x}", hasher.finalize()))
+        });
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = StreamHasher::new(algorithm);
+        for segment in 0..segments {
+            let data = receivers[(segment % readers as u64) as usize]
+                .blocking_recv()
+                .ok_or_else(|| std::io::Error::other("hash reader stopped early"))??;
+            hasher.update(&data);
         }
+        Ok(hasher.finalize())
+    })
+    .await
+    .map_err(|e| CraneError::FileSystem(std::io::Error::other(e)))?
+}
+
+/// Read `size` bytes of `file` starting at `start`.
+async fn read_segment(
+    file: &mut tokio::fs::File,
+    start: u64,
+    size: usize,
+) -> std::io::Result<Vec<u8>> {
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    let mut buf = vec![0u8; size];
+    file.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+/// Whether the file at `path` hashes to `expected`. Hex digests compare
+/// case-insensitively; the file is left alone either way.
+pub async fn file_matches(path: &Path, expected: &ExpectedHash) -> Result<bool, CraneError> {
+    let expected = resolve_algorithm(expected)?;
+    let actual = compute_hash(path, expected.algorithm).await?;
+    Ok(actual.eq_ignore_ascii_case(expected.value.trim()))
+}
+
+/// Whether [`verify_download`] will hash the file for these options.
+pub(crate) fn wants_verification(options: &DownloadOptions) -> bool {
+    options.expected_hash.is_some() || options.verify_from_sidecar
+}
+
+/// The digest a download should match: `options.expected_hash` (with an
+/// `Auto` algorithm resolved), or with `verify_from_sidecar` the
+/// `.sha256`/`.md5` file published next to `url`.
+pub(crate) async fn resolve_expected_hash(
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<Option<ExpectedHash>, CraneError> {
+    match options.expected_hash {
+        Some(ref expected) => resolve_algorithm(expected).map(Some),
+        None if options.verify_from_sidecar => sidecar::fetch_sidecar_hash(url, options).await,
+        None => Ok(None),
+    }
+}
+
+/// Verify a finished download against `options.expected_hash` or, when
+/// `verify_from_sidecar` is set and no digest was supplied, against a
+/// `.sha256`/`.md5` file published next to `url`. The file is deleted on
+/// mismatch.
+///
+/// Returns `Some(true)` if a digest was checked, `None` if there was none.
+pub(crate) async fn verify_download(
+    url: &str,
+    save_path: &Path,
+    options: &DownloadOptions,
+) -> Result<Option<bool>, CraneError> {
+    match resolve_expected_hash(url, options).await? {
+        Some(expected) => check_digest(save_path, &expected, None).await,
+        None => Ok(None),
     }
 }
 
+/// Compare `save_path` against `expected`, using `streamed` (a digest
+/// already computed while the file was written) instead of re-reading the
+/// file when given. The file is deleted on mismatch.
+pub(crate) async fn check_digest(
+    save_path: &Path,
+    expected: &ExpectedHash,
+    streamed: Option<String>,
+) -> Result<Option<bool>, CraneError> {
+    let expected = &resolve_algorithm(expected)?;
+    let actual = match streamed {
+        Some(digest) => digest,
+        None => compute_hash(save_path, expected.algorithm).await?,
+    };
+    if actual != expected.value {
+        let _ = tokio::fs::remove_file(save_path).await;
+        return Err(CraneError::HashMismatch {
+            expected: expected.value.clone(),
+            actual,
+        });
+    }
+    Ok(Some(true))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -92,4 +349,97 @@ mod tests {
             "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
         );
     }
+
+    #[tokio::test]
+    async fn test_parallel_hash_matches_serial() {
+        // Three and a bit segments, so the last one is short
+        let data: Vec<u8> = (0..3 * HASH_SEGMENT_SIZE + 12_345)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(&data).unwrap();
+        f.flush().unwrap();
+        let empty = NamedTempFile::new().unwrap();
+
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Md5] {
+            for path in [f.path(), empty.path()] {
+                let serial = compute_hash(path, algorithm).await.unwrap();
+                for readers in [1, 3, 8] {
+                    let parallel = compute_hash_parallel(path, algorithm, readers)
+                        .await
+                        .unwrap();
+                    assert_eq!(parallel, serial, "{algorithm:?} with {readers} readers");
+                }
+            }
+        }
+
+        let missing = compute_hash_parallel(
+            Path::new("/tmp/nonexistent_crane_test_file"),
+            HashAlgorithm::Sha256,
+            PARALLEL_HASH_READERS,
+        )
+        .await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_parse_hash_algorithm() {
+        assert_eq!(
+            "sha256".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Sha256
+        );
+        assert_eq!("MD5".parse::<HashAlgorithm>().unwrap(), HashAlgorithm::Md5);
+        assert_eq!(
+            "Auto".parse::<HashAlgorithm>().unwrap(),
+            HashAlgorithm::Auto
+        );
+        let err = "sha3".parse::<HashAlgorithm>().unwrap_err();
+        assert!(err.to_string().contains("Unsupported hash algorithm: sha3"));
+    }
+
+    #[test]
+    fn test_infer_algorithm_from_length() {
+        for (len, algorithm) in [
+            (32, HashAlgorithm::Md5),
+            (40, HashAlgorithm::Sha1),
+            (64, HashAlgorithm::Sha256),
+            (128, HashAlgorithm::Sha512),
+        ] {
+            let hex = "aB0".repeat(len).chars().take(len).collect::<String>();
+            assert_eq!(infer_algorithm(&hex).unwrap(), algorithm, "{len} digits");
+        }
+
+        for hex in ["abc", &"a".repeat(33), &"a".repeat(63), ""] {
+            let err = infer_algorithm(hex).unwrap_err();
+            assert!(err.to_string().contains("Can't tell the hash algorithm"));
+        }
+        assert!(infer_algorithm(&"z".repeat(32)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_auto_hash_verifies_by_length() {
+        let mut f = NamedTempFile::new().unwrap();
+        f.write_all(b"hello world").unwrap();
+        f.flush().unwrap();
+
+        for algorithm in [
+            HashAlgorithm::Md5,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+        ] {
+            let value = compute_hash(f.path(), algorithm).await.unwrap();
+            assert_eq!(Some(value.len()), algorithm.hex_len());
+            let expected = ExpectedHash {
+                algorithm: HashAlgorithm::Auto,
+                value,
+            };
+            assert!(file_matches(f.path(), &expected).await.unwrap());
+        }
+        assert_eq!(
+            compute_hash(f.path(), HashAlgorithm::Sha1).await.unwrap(),
+            "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed"
+        );
+        assert!(compute_hash(f.path(), HashAlgorithm::Auto).await.is_err());
+    }
 }
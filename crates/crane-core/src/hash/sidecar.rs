@@ -0,0 +1,164 @@
+// Checksum sidecar files (`foo.iso.sha256`, `foo.iso.md5`) published next to downloads.
+
+use std::time::Duration;
+
+use super::HashAlgorithm;
+use crate::engine::download::{apply_options_headers, USER_AGENT};
+use crate::network::{apply_proxy, safe_redirect_policy};
+use crate::types::{CraneError, DownloadOptions, ExpectedHash};
+
+/// Checksum files are a few lines of text; refuse anything larger.
+const MAX_SIDECAR_SIZE: usize = 64 * 1024;
+
+/// Sidecar extensions to try, strongest first.
+const SIDECARS: &[(&str, HashAlgorithm)] = &[
+    ("sha256", HashAlgorithm::Sha256),
+    ("md5", HashAlgorithm::Md5),
+];
+
+/// Find the digest for `filename` in a `sha256sum`/`md5sum` style file.
+///
+/// Each line is `<hex>  <filename>` (a `*` before the name marks binary
+/// mode). A file holding a single bare digest applies to any filename.
+pub fn parse_checksum_file(
+    contents: &str,
+    filename: &str,
+    algorithm: HashAlgorithm,
+) -> Option<String> {
+    let is_digest = |s: &str| match algorithm.hex_len() {
+        Some(hex_len) => s.len() == hex_len && s.bytes().all(|b| b.is_ascii_hexdigit()),
+        None => super::infer_algorithm(s).is_ok(),
+    };
+
+    let mut bare = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (digest, name) = match line.split_once(char::is_whitespace) {
+            Some((digest, name)) => (digest, name.trim_start()),
+            None => (line, ""),
+        };
+        if !is_digest(digest) {
+            continue;
+        }
+        let name = name.trim_start_matches('*');
+        let name = name.rsplit('/').next().unwrap_or(name);
+        if name.is_empty() {
+            bare = Some(digest);
+        } else if name == filename {
+            return Some(digest.to_ascii_lowercase());
+        }
+    }
+    bare.map(str::to_ascii_lowercase)
+}
+
+/// Fetch `<url>.sha256` (falling back to `<url>.md5`) and return the digest
+/// it lists for the URL's file name. `None` if neither sidecar exists or
+/// lists the file.
+pub async fn fetch_sidecar_hash(
+    url: &str,
+    options: &DownloadOptions,
+) -> Result<Option<ExpectedHash>, CraneError> {
+    let parsed = url::Url::parse(url)?;
+    let last_segment = parsed.path().rsplit('/').next().unwrap_or("");
+    let filename = urlencoding::decode(last_segment)
+        .map(|s| s.into_owned())
+        .unwrap_or_else(|_| last_segment.to_string());
+    let ua = options.user_agent.as_deref().unwrap_or(USER_AGENT);
+    let builder = reqwest::Client::builder()
+        .user_agent(ua)
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30))
+        .redirect(safe_redirect_policy(options.max_redirects));
+    let client = apply_proxy(
+        builder,
+        options.proxy.as_deref(),
+        options.no_proxy.as_deref(),
+    )?
+    .build()
+    .map_err(CraneError::Network)?;
+
+    for (ext, algorithm) in SIDECARS {
+        let mut sidecar_url = parsed.clone();
+        sidecar_url.set_path(&format!("{}.{ext}", parsed.path()));
+
+        let request = apply_options_headers(client.get(sidecar_url.as_str()), options);
+        let Ok(response) = request.send().await else {
+            continue;
+        };
+        if !response.status().is_success() {
+            continue;
+        }
+        let Ok(body) = response.bytes().await else {
+            continue;
+        };
+        if body.len() > MAX_SIDECAR_SIZE {
+            continue;
+        }
+        let contents = String::from_utf8_lossy(&body);
+        if let Some(value) = parse_checksum_file(&contents, &filename, *algorithm) {
+            return Ok(Some(ExpectedHash {
+                algorithm: *algorithm,
+                value,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+    const MD5: &str = "5eb63bbbe01eeed093cb22bb8f5acdc3";
+
+    #[test]
+    fn test_parse_single_entry() {
+        let contents = format!("{SHA}  foo.iso\n");
+        assert_eq!(
+            parse_checksum_file(&contents, "foo.iso", HashAlgorithm::Sha256).as_deref(),
+            Some(SHA)
+        );
+        assert_eq!(
+            parse_checksum_file(&contents, "bar.iso", HashAlgorithm::Sha256),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_file_listing() {
+        let other = "0".repeat(64);
+        let contents = format!(
+            "# checksums\n{other}  other.iso\n{}  *./images/foo.iso\n",
+            SHA.to_uppercase()
+        );
+        assert_eq!(
+            parse_checksum_file(&contents, "foo.iso", HashAlgorithm::Sha256).as_deref(),
+            Some(SHA)
+        );
+        assert_eq!(
+            parse_checksum_file(&contents, "other.iso", HashAlgorithm::Sha256),
+            Some(other)
+        );
+    }
+
+    #[test]
+    fn test_parse_bare_digest() {
+        assert_eq!(
+            parse_checksum_file(&format!("{MD5}\n"), "foo.iso", HashAlgorithm::Md5).as_deref(),
+            Some(MD5)
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_length() {
+        let contents = format!("{MD5}  foo.iso\n");
+        assert_eq!(
+            parse_checksum_file(&contents, "foo.iso", HashAlgorithm::Sha256),
+            None
+        );
+    }
+}
@@ -0,0 +1,200 @@
+// User commands run when a download finishes.
+//
+// The template is split into arguments *before* placeholders are filled in,
+// and the program is started directly rather than through a shell, so a
+// filename like `a; rm -rf ~.zip` stays one literal argument.
+
+use std::path::Path;
+
+use crate::types::CraneError;
+
+/// Values substituted into an on-complete command template.
+#[derive(Debug, Clone)]
+pub struct HookContext<'a> {
+    /// Final path of the finished file (`{path}`).
+    pub path: &'a Path,
+    /// Source URL (`{url}`).
+    pub url: &'a str,
+    /// Download id (`{id}`).
+    pub id: &'a str,
+}
+
+/// Split `template` into arguments and substitute `{path}`, `{filename}`,
+/// `{dir}`, `{url}` and `{id}` in each one.
+///
+/// Arguments are separated by whitespace; single or double quotes group
+/// words, and a backslash outside single quotes escapes the next character.
+pub fn expand_command(template: &str, ctx: &HookContext) -> Result<Vec<String>, CraneError> {
+    let args = split_args(template)?;
+    if args.is_empty() {
+        return Err(CraneError::Config(
+            "on_complete_command is empty".to_string(),
+        ));
+    }
+
+    let path = ctx.path.to_string_lossy();
+    let filename = ctx
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let dir = ctx
+        .path
+        .parent()
+        .map(|d| d.to_string_lossy())
+        .unwrap_or_default();
+
+    Ok(args
+        .iter()
+        .map(|arg| {
+            substitute(arg, |name| match name {
+                "path" => Some(&path),
+                "filename" => Some(&filename),
+                "dir" => Some(&dir),
+                "url" => Some(ctx.url),
+                "id" => Some(ctx.id),
+                _ => None,
+            })
+        })
+        .collect())
+}
+
+/// Replace each `{name}` in one pass, so a value that itself contains a
+/// placeholder (a filename like `{url}.zip`) is inserted verbatim. Unknown
+/// names are left as written.
+fn substitute<'v>(arg: &str, value: impl Fn(&str) -> Option<&'v str>) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open..];
+        match after
+            .find('}')
+            .and_then(|close| Some((close, value(&after[1..close])?)))
+        {
+            Some((close, v)) => {
+                out.push_str(v);
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Start the expanded command without waiting for it. The child is reaped in
+/// the background; its output is discarded.
+pub fn spawn_on_complete(template: &str, ctx: &HookContext) -> Result<(), CraneError> {
+    let args = expand_command(template, ctx)?;
+    let mut child = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    tokio::spawn(async move {
+        let _ = child.wait().await;
+    });
+    Ok(())
+}
+
+fn split_args(template: &str) -> Result<Vec<String>, CraneError> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.push(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(CraneError::Config(
+            "on_complete_command has an unterminated quote".to_string(),
+        ));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(path: &Path) -> HookContext<'_> {
+        HookContext {
+            path,
+            url: "https://example.com/a.zip?x=1&y=2",
+            id: "abc",
+        }
+    }
+
+    #[test]
+    fn test_expand_command_placeholders_and_quotes() {
+        let path = Path::new("/downloads/My File.zip");
+        let args = expand_command(
+            r#"unzip -o {path} -d "{dir}/out dir" --note='{url} {id}' {filename}"#,
+            &ctx(path),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            [
+                "unzip",
+                "-o",
+                "/downloads/My File.zip",
+                "-d",
+                "/downloads/out dir",
+                "--note=https://example.com/a.zip?x=1&y=2 abc",
+                "My File.zip",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_command_filename_cannot_inject() {
+        let path = Path::new("/downloads/x; touch pwned $(id) `id` {url}.zip");
+        let args = expand_command("mv {path} /nas/{unknown}", &ctx(path)).unwrap();
+        assert_eq!(args.len(), 3);
+        assert_eq!(args[1], "/downloads/x; touch pwned $(id) `id` {url}.zip");
+        assert_eq!(args[2], "/nas/{unknown}");
+    }
+
+    #[test]
+    fn test_expand_command_rejects_bad_templates() {
+        let path = Path::new("/downloads/a.zip");
+        assert!(expand_command("   ", &ctx(path)).is_err());
+        assert!(expand_command("echo 'oops", &ctx(path)).is_err());
+    }
+}
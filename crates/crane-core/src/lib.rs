@@ -6,8 +6,233 @@ pub mod db;
 pub mod engine;
 pub mod events;
 pub mod hash;
+pub mod hooks;
 pub mod metadata;
+#[cfg(feature = "net")]
+pub mod net;
 pub mod network;
 pub mod protocol;
 pub mod queue;
 pub mod types;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use db::Database;
+use events::DownloadCompleted;
+use queue::QueueManager;
+use types::{CraneError, Download, DownloadOptions, DownloadProgress, DownloadStatus};
+
+/// How often the background monitor promotes queued downloads and records
+/// finished ones.
+const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Single entry point for embedding the downloader. Owns the database and
+/// the queue, and runs the monitor loop that marks downloads finished and
+/// starts queued ones.
+///
+/// Must be built inside a Tokio runtime; dropping it stops the monitor.
+///
+/// ```
+/// use crane_core::types::DownloadStatus;
+/// use crane_core::Crane;
+/// # use wiremock::matchers::{method, path};
+/// # use wiremock::{Mock, MockServer, ResponseTemplate};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), crane_core::types::CraneError> {
+/// # let server = MockServer::start().await;
+/// # for verb in ["HEAD", "GET"] {
+/// #     Mock::given(method(verb))
+/// #         .and(path("/hello.txt"))
+/// #         .respond_with(
+/// #             ResponseTemplate::new(200)
+/// #                 .set_body_string("hello, crane")
+/// #                 .insert_header("content-type", "text/plain"),
+/// #         )
+/// #         .mount(&server)
+/// #         .await;
+/// # }
+/// let dir = tempfile::tempdir().unwrap();
+/// let crane = Crane::builder()
+///     .save_dir(dir.path())
+///     .max_concurrent(2)
+///     .bandwidth_limit(Some(10 * 1024 * 1024))
+///     .build()?;
+///
+/// let url = format!("{}/hello.txt", server.uri());
+/// let id = crane.add(&url, Default::default()).await?;
+/// let download = crane.wait(&id).await?;
+///
+/// assert_eq!(download.status, DownloadStatus::Completed);
+/// let text = std::fs::read_to_string(dir.path().join("hello.txt")).unwrap();
+/// assert_eq!(text, "hello, crane");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Crane {
+    queue: Arc<QueueManager>,
+    save_dir: String,
+    monitor: tokio::task::JoinHandle<()>,
+}
+
+/// Configuration for [`Crane`], created by [`Crane::builder`].
+#[derive(Debug, Clone)]
+pub struct CraneBuilder {
+    save_dir: Option<PathBuf>,
+    database: Option<PathBuf>,
+    max_concurrent: u32,
+    bandwidth_limit: Option<u64>,
+}
+
+impl Default for CraneBuilder {
+    fn default() -> Self {
+        Self {
+            save_dir: None,
+            database: None,
+            max_concurrent: 3,
+            bandwidth_limit: None,
+        }
+    }
+}
+
+impl CraneBuilder {
+    /// Folder downloads are saved to (default: the user's download folder).
+    pub fn save_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.save_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// SQLite file to keep download history in (default: in memory).
+    pub fn database(mut self, path: impl AsRef<Path>) -> Self {
+        self.database = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Downloads allowed to run at once; the rest wait in the queue.
+    pub fn max_concurrent(mut self, max: u32) -> Self {
+        self.max_concurrent = max;
+        self
+    }
+
+    /// Shared speed limit in bytes/sec (`None` = unlimited).
+    pub fn bandwidth_limit(mut self, limit: Option<u64>) -> Self {
+        self.bandwidth_limit = limit;
+        self
+    }
+
+    /// Open the database and start the queue monitor.
+    pub fn build(self) -> Result<Crane, CraneError> {
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+            CraneError::Config("Crane must be built inside a Tokio runtime".to_string())
+        })?;
+        let db = match &self.database {
+            Some(path) => Database::open(path)?,
+            None => Database::open_in_memory()?,
+        };
+        let save_dir = self
+            .save_dir
+            .or_else(dirs::download_dir)
+            .unwrap_or_else(|| PathBuf::from("."))
+            .to_string_lossy()
+            .into_owned();
+        let queue = Arc::new(
+            QueueManager::new(
+                Arc::new(db),
+                self.max_concurrent,
+                self.bandwidth_limit,
+                vec![],
+            )
+            .with_download_dirs(vec![PathBuf::from(&save_dir)]),
+        );
+
+        let monitor_queue = queue.clone();
+        let monitor_save_dir = save_dir.clone();
+        let monitor = runtime.spawn(async move {
+            let mut interval = tokio::time::interval(MONITOR_INTERVAL);
+            loop {
+                interval.tick().await;
+                let _ = monitor_queue.check_completed().await;
+                let _ = monitor_queue.check_pause_window().await;
+                let _ = monitor_queue.check_pending(&monitor_save_dir).await;
+            }
+        });
+
+        Ok(Crane {
+            queue,
+            save_dir,
+            monitor,
+        })
+    }
+}
+
+impl Crane {
+    /// Start configuring a new instance.
+    pub fn builder() -> CraneBuilder {
+        CraneBuilder::default()
+    }
+
+    /// Add a download to the save folder, starting it now if a slot is free.
+    /// Returns the download id.
+    pub async fn add(&self, url: &str, options: DownloadOptions) -> Result<String, CraneError> {
+        self.queue.add_download(url, &self.save_dir, options).await
+    }
+
+    /// Pause an active download.
+    pub async fn pause(&self, id: &str) -> Result<(), CraneError> {
+        self.queue.pause(id).await
+    }
+
+    /// Resume a paused download.
+    pub async fn resume(&self, id: &str) -> Result<(), CraneError> {
+        self.queue.resume(id).await
+    }
+
+    /// Cancel a download and remove its partial data.
+    pub async fn cancel(&self, id: &str) -> Result<(), CraneError> {
+        self.queue.cancel(id).await
+    }
+
+    /// Every download this instance knows about.
+    pub fn list(&self) -> Result<Vec<Download>, CraneError> {
+        self.queue.list_downloads()
+    }
+
+    /// Receive progress events from every download.
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<DownloadProgress> {
+        self.queue.subscribe()
+    }
+
+    /// Receive an event each time a download finishes successfully.
+    pub fn subscribe_completed(&self) -> tokio::sync::broadcast::Receiver<DownloadCompleted> {
+        self.queue.subscribe_completed()
+    }
+
+    /// Wait until the download completes, fails or is cancelled and return
+    /// its final row.
+    pub async fn wait(&self, id: &str) -> Result<Download, CraneError> {
+        loop {
+            self.queue.check_completed().await?;
+            let download = self.queue.db().get_download(id)?;
+            if matches!(
+                download.status,
+                DownloadStatus::Completed | DownloadStatus::Failed | DownloadStatus::Cancelled
+            ) {
+                return Ok(download);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// The underlying queue, for settings the builder doesn't cover.
+    pub fn queue(&self) -> &QueueManager {
+        &self.queue
+    }
+}
+
+impl Drop for Crane {
+    fn drop(&mut self) {
+        self.monitor.abort();
+    }
+}
@@ -1,18 +1,49 @@
 use std::time::Duration;
 
-use crate::metadata::mime::{categorize_extension, categorize_mime};
-use crate::metadata::sanitize_filename;
-use crate::network::safe_redirect_policy;
-use crate::types::{CraneError, FileCategory, UrlAnalysis};
+use futures_util::StreamExt;
+use tokio_util::sync::CancellationToken;
 
-const USER_AGENT: &str = "Crane/0.1.0";
+use crate::engine::download::{DEFAULT_CONNECT_TIMEOUT_MS, USER_AGENT};
+use crate::metadata::html_refresh::{find_redirect_target, MAX_PAGE_BYTES};
+use crate::metadata::mime::{categorize_extension, categorize_mime};
+use crate::metadata::{sanitize_filename, with_mime_extension};
+use crate::network::{
+    apply_proxy, is_content_encoded, request_identity_encoding, safe_redirect_policy,
+};
+use crate::types::{Auth, CraneError, DownloadOptions, FileCategory, UrlAnalysis};
 
 /// Optional headers/cookies to include in the analysis request.
 /// Needed for authenticated downloads (Google Drive, Dropbox, etc.)
 /// where the server requires cookies to return proper Content-Disposition.
+#[derive(Default)]
 pub struct AnalyzeOptions {
     pub cookies: Option<String>,
     pub headers: Option<std::collections::HashMap<String, String>>,
+    pub auth: Option<Auth>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<Vec<String>>,
+    pub ipfs_gateway: Option<String>,
+    pub follow_html_refresh: bool,
+    pub max_redirects: Option<u32>,
+    pub connect_timeout_ms: Option<u64>,
+    pub read_timeout_ms: Option<u64>,
+}
+
+impl From<&DownloadOptions> for AnalyzeOptions {
+    fn from(options: &DownloadOptions) -> Self {
+        Self {
+            cookies: options.cookies.clone(),
+            headers: options.headers.clone(),
+            auth: options.auth.clone(),
+            proxy: options.proxy.clone(),
+            no_proxy: options.no_proxy.clone(),
+            ipfs_gateway: options.ipfs_gateway.clone(),
+            follow_html_refresh: options.follow_html_refresh,
+            max_redirects: options.max_redirects,
+            connect_timeout_ms: options.connect_timeout_ms,
+            read_timeout_ms: options.read_timeout_ms,
+        }
+    }
 }
 
 pub async fn analyze_url(input_url: &str) -> Result<UrlAnalysis, CraneError> {
@@ -23,7 +54,10 @@ pub async fn analyze_url_with_options(
     input_url: &str,
     options: Option<&AnalyzeOptions>,
 ) -> Result<UrlAnalysis, CraneError> {
+    let gateway = options.and_then(|o| o.ipfs_gateway.as_deref());
+    let input_url = &*crate::protocol::ipfs::resolve_url(input_url, gateway)?;
     let parsed = url::Url::parse(input_url)?;
+    crate::protocol::reject_torrent(&parsed)?;
     match parsed.scheme() {
         "http" | "https" => analyze_http(input_url, &parsed, options).await,
         _ => {
@@ -33,11 +67,26 @@ pub async fn analyze_url_with_options(
     }
 }
 
+/// `analyze_url_with_options` that gives up with `CraneError::Cancelled` as
+/// soon as `cancel` fires, dropping any request still in flight.
+pub async fn analyze_url_cancellable(
+    input_url: &str,
+    options: Option<&AnalyzeOptions>,
+    cancel: &CancellationToken,
+) -> Result<UrlAnalysis, CraneError> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(CraneError::Cancelled),
+        analysis = analyze_url_with_options(input_url, options) => analysis,
+    }
+}
+
 /// Apply optional cookies/headers to a request builder.
 fn apply_analyze_options(
-    mut request: reqwest::RequestBuilder,
+    request: reqwest::RequestBuilder,
     options: Option<&AnalyzeOptions>,
 ) -> reqwest::RequestBuilder {
+    let mut request = request_identity_encoding(request, options.and_then(|o| o.headers.as_ref()));
     if let Some(opts) = options {
         if let Some(ref cookies) = opts.cookies {
             if !cookies.is_empty() {
@@ -49,6 +98,9 @@ fn apply_analyze_options(
                 request = request.header(key.as_str(), value.as_str());
             }
         }
+        if let Some(ref auth) = opts.auth {
+            request = crate::engine::download::apply_auth(request, auth);
+        }
     }
     request
 }
@@ -58,14 +110,89 @@ async fn analyze_http(
     parsed: &url::Url,
     options: Option<&AnalyzeOptions>,
 ) -> Result<UrlAnalysis, CraneError> {
-    let client = reqwest::Client::builder()
+    let client = analysis_client(options)?;
+    let analysis = probe_http(&client, input_url, parsed, options).await?;
+
+    // An HTML page where a file was expected may be a download page that
+    // redirects to the real file; follow it once if asked to
+    let follow = options.is_some_and(|o| o.follow_html_refresh);
+    if !follow
+        || crate::metadata::validate_content_type(analysis.mime_type.as_deref(), &analysis.filename)
+            .is_ok()
+    {
+        return Ok(analysis);
+    }
+    match fetch_redirect_target(&client, &analysis.url, options).await {
+        Some(target) => {
+            crate::protocol::reject_torrent(&target)?;
+            probe_http(&client, target.as_str(), &target, options).await
+        }
+        None => Ok(analysis),
+    }
+}
+
+/// Client for analysis-time requests: proxy, redirect limit and timeouts
+/// from `options`, and an overall cap so a stalled server can't hold up an
+/// add.
+pub(crate) fn analysis_client(
+    options: Option<&AnalyzeOptions>,
+) -> Result<reqwest::Client, CraneError> {
+    let mut builder = reqwest::Client::builder()
         .user_agent(USER_AGENT)
-        .connect_timeout(Duration::from_secs(10))
         .timeout(Duration::from_secs(30))
-        .redirect(safe_redirect_policy())
-        .build()
-        .map_err(CraneError::Network)?;
+        .redirect(safe_redirect_policy(options.and_then(|o| o.max_redirects)));
+    let connect = options
+        .and_then(|o| o.connect_timeout_ms)
+        .unwrap_or(DEFAULT_CONNECT_TIMEOUT_MS);
+    if connect > 0 {
+        builder = builder.connect_timeout(Duration::from_millis(connect));
+    }
+    if let Some(read) = options.and_then(|o| o.read_timeout_ms).filter(|&ms| ms > 0) {
+        builder = builder.read_timeout(Duration::from_millis(read));
+    }
+    apply_proxy(
+        builder,
+        options.and_then(|o| o.proxy.as_deref()),
+        options.and_then(|o| o.no_proxy.as_deref()),
+    )?
+    .build()
+    .map_err(CraneError::Network)
+}
 
+/// Download (the start of) an HTML page and look for where it redirects.
+async fn fetch_redirect_target(
+    client: &reqwest::Client,
+    page_url: &str,
+    options: Option<&AnalyzeOptions>,
+) -> Option<url::Url> {
+    let base = url::Url::parse(page_url).ok()?;
+    let response = apply_analyze_options(client.get(page_url), options)
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let mut stream = response.bytes_stream();
+    let mut body = Vec::new();
+    while let Some(Ok(bytes)) = stream.next().await {
+        body.extend_from_slice(&bytes);
+        if body.len() >= MAX_PAGE_BYTES {
+            body.truncate(MAX_PAGE_BYTES);
+            break;
+        }
+    }
+    find_redirect_target(&String::from_utf8_lossy(&body), &base)
+}
+
+/// HEAD (or range GET) `input_url` and build its analysis.
+async fn probe_http(
+    client: &reqwest::Client,
+    input_url: &str,
+    parsed: &url::Url,
+    options: Option<&AnalyzeOptions>,
+) -> Result<UrlAnalysis, CraneError> {
     // Try HEAD first; fall back to a range-limited GET if the server doesn't
     // support HEAD (some CDN/speed-test servers drop HEAD with an empty reply,
     // or return 405/404 for HEAD while supporting GET).
@@ -79,6 +206,8 @@ async fn analyze_http(
         }
     };
     let final_url = response.url().to_string();
+    let redirected_to = (response.url() != parsed).then(|| final_url.clone());
+    let http_version = format!("{:?}", response.version());
     let status = response.status();
 
     if !status.is_success() {
@@ -100,12 +229,8 @@ async fn analyze_http(
 
     // For 206 responses, extract the total size from Content-Range header
     // (Content-Range: bytes 0-0/TOTAL), since Content-Length is just the range size.
-    let total_size = if used_range_get {
-        headers
-            .get("content-range")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.rsplit('/').next())
-            .and_then(|v| v.parse::<u64>().ok())
+    let mut total_size = if used_range_get {
+        content_range_total(headers)
     } else {
         headers
             .get("content-length")
@@ -119,28 +244,42 @@ async fn analyze_http(
         .map(|v| v.split(';').next().unwrap_or(v).trim().to_string());
 
     // Determine resumability: 206 from Range GET, Accept-Ranges header, or
-    // probe with a Range GET if HEAD didn't confirm either way.
-    let resumable = if used_range_get || head_says_resumable {
+    // probe with a Range GET if HEAD didn't confirm either way. A server that
+    // compresses despite `Accept-Encoding: identity` gets one connection:
+    // ranges of the encoded stream aren't offsets into the file.
+    let resumable = if is_content_encoded(headers) {
+        false
+    } else if used_range_get || (head_says_resumable && total_size.is_some()) {
         true
     } else {
-        // HEAD succeeded but didn't indicate range support — many servers omit
-        // Accept-Ranges from HEAD responses. Probe with a Range GET to confirm.
+        // HEAD succeeded but didn't indicate range support or the size — many
+        // servers omit Accept-Ranges or Content-Length from HEAD responses.
+        // Probe with a Range GET: a 206 confirms ranges and its Content-Range
+        // carries the total.
         let probe_req =
             apply_analyze_options(client.get(&final_url).header("Range", "bytes=0-0"), options);
         match probe_req.send().await {
-            Ok(probe) => probe.status() == reqwest::StatusCode::PARTIAL_CONTENT,
-            Err(_) => false,
+            Ok(probe) if probe.status() == reqwest::StatusCode::PARTIAL_CONTENT => {
+                total_size = total_size.or_else(|| content_range_total(probe.headers()));
+                true
+            }
+            _ => head_says_resumable,
         }
     };
 
-    let server = headers
-        .get("server")
-        .and_then(|v| v.to_str().ok())
-        .map(|v| v.to_string());
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+    };
+    let server = header("server");
+    let etag = header("etag");
+    let last_modified = header("last-modified");
 
     let raw_filename =
         extract_filename_from_headers(headers).unwrap_or_else(|| extract_filename_from_url(parsed));
-    let filename = sanitize_filename(&raw_filename);
+    let filename = with_mime_extension(&sanitize_filename(&raw_filename), mime_type.as_deref());
 
     let category = match &mime_type {
         Some(mime) => {
@@ -162,6 +301,11 @@ async fn analyze_http(
         resumable,
         category,
         server,
+        etag,
+        last_modified,
+        accept_ranges: head_says_resumable,
+        http_version: Some(http_version),
+        redirected_to,
     })
 }
 
@@ -222,25 +366,17 @@ fn extract_filename_from_url(parsed: &url::Url) -> String {
     let path = parsed.path();
     let segment = path.rsplit('/').next().unwrap_or("");
 
-    match urlencoding::decode(segment) {
-        Ok(decoded) => {
-            let name = decoded.into_owned();
-            if name.is_empty() {
-                "download".to_string()
-            } else if has_file_extension(&name) {
-                name
-            } else {
-                // Path segment has no recognizable extension — might be base64-encoded
-                try_base64_decode_filename(&name).unwrap_or(name)
-            }
-        }
-        Err(_) => {
-            if segment.is_empty() {
-                "download".to_string()
-            } else {
-                segment.to_string()
-            }
-        }
+    // Bytes that aren't UTF-8 (e.g. Latin-1 `%E9`) become U+FFFD rather than
+    // leaving the whole name percent-encoded
+    let decoded = urlencoding::decode_binary(segment.as_bytes());
+    let name = String::from_utf8_lossy(&decoded).into_owned();
+    if name.is_empty() {
+        "download".to_string()
+    } else if has_file_extension(&name) {
+        name
+    } else {
+        // Path segment has no recognizable extension — might be base64-encoded
+        try_base64_decode_filename(&name).unwrap_or(name)
     }
 }
 
@@ -263,6 +399,16 @@ fn extract_filename_from_query_params(parsed: &url::Url) -> Option<String> {
     None
 }
 
+/// Total size from a `Content-Range: bytes 0-0/TOTAL` header; `None` when
+/// it is missing or the total is unknown (`*`).
+fn content_range_total(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    headers
+        .get("content-range")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit('/').next())
+        .and_then(|v| v.parse::<u64>().ok())
+}
+
 fn has_file_extension(name: &str) -> bool {
     std::path::Path::new(name)
         .extension()
@@ -382,6 +528,97 @@ mod tests {
         assert_eq!(result.total_size, None);
     }
 
+    #[tokio::test]
+    async fn test_size_recovered_from_range_probe() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/sizeless.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "application/octet-stream")
+                    .insert_header("Accept-Ranges", "bytes"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/sizeless.bin"))
+            .and(wiremock::matchers::header("Range", "bytes=0-0"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("Content-Range", "bytes 0-0/10485760")
+                    .set_body_bytes(vec![0u8]),
+            )
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/sizeless.bin", server.uri());
+        let result = analyze_url(&url).await.unwrap();
+
+        assert_eq!(result.total_size, Some(10485760));
+        assert!(result.resumable);
+        let connections = crate::engine::multi::planned_connections(
+            &url,
+            &result,
+            8,
+            crate::engine::multi::MIN_CHUNK_SIZE,
+        );
+        assert!(connections > 1, "planned {connections} connection(s)");
+    }
+
+    #[tokio::test]
+    async fn test_server_capabilities_reported() {
+        use wiremock::matchers::header;
+
+        // The mock server plays an HTTP proxy so the redirect can use public
+        // hostnames; redirects to loopback addresses are refused
+        let proxy = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(header("host", "short.crane.invalid"))
+            .respond_with(
+                ResponseTemplate::new(302)
+                    .insert_header("Location", "http://cdn.crane.invalid/file.bin"),
+            )
+            .mount(&proxy)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(header("host", "cdn.crane.invalid"))
+            .and(path("/file.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", "2048")
+                    .insert_header("Accept-Ranges", "bytes"),
+            )
+            .mount(&proxy)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(header("host", "plain.crane.invalid"))
+            .respond_with(ResponseTemplate::new(200).insert_header("Content-Length", "2048"))
+            .mount(&proxy)
+            .await;
+        let options = AnalyzeOptions {
+            proxy: Some(proxy.uri()),
+            ..Default::default()
+        };
+
+        let result =
+            analyze_url_with_options("http://short.crane.invalid/file.bin", Some(&options))
+                .await
+                .unwrap();
+        assert!(result.accept_ranges);
+        assert_eq!(result.http_version.as_deref(), Some("HTTP/1.1"));
+        assert_eq!(
+            result.redirected_to.as_deref(),
+            Some("http://cdn.crane.invalid/file.bin")
+        );
+
+        let result =
+            analyze_url_with_options("http://plain.crane.invalid/file.bin", Some(&options))
+                .await
+                .unwrap();
+        assert!(!result.accept_ranges);
+        assert_eq!(result.redirected_to, None);
+    }
+
     #[tokio::test]
     async fn test_no_accept_ranges() {
         let server = MockServer::start().await;
@@ -418,6 +655,24 @@ mod tests {
         assert_eq!(result.filename, "my-document.pdf");
     }
 
+    #[tokio::test]
+    async fn test_extensionless_filename_gets_mime_extension() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/files/report"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("Content-Type", "application/pdf"),
+            )
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/files/report", server.uri());
+        let result = analyze_url(&url).await.unwrap();
+
+        assert_eq!(result.filename, "report.pdf");
+        assert_eq!(result.category, FileCategory::Documents);
+    }
+
     #[tokio::test]
     async fn test_filename_url_decoded() {
         let server = MockServer::start().await;
@@ -506,6 +761,43 @@ mod tests {
         assert!(result.url.contains("/file.zip"));
     }
 
+    #[tokio::test]
+    async fn test_captures_etag_and_last_modified() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/file.zip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "application/zip")
+                    .insert_header("ETag", "\"5f3a-1b2c\"")
+                    .insert_header("Last-Modified", "Wed, 21 Oct 2026 07:28:00 GMT"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .and(path("/plain.zip"))
+            .respond_with(
+                ResponseTemplate::new(200).insert_header("Content-Type", "application/zip"),
+            )
+            .mount(&server)
+            .await;
+
+        let result = analyze_url(&format!("{}/file.zip", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(result.etag.as_deref(), Some("\"5f3a-1b2c\""));
+        assert_eq!(
+            result.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2026 07:28:00 GMT")
+        );
+
+        let result = analyze_url(&format!("{}/plain.zip", server.uri()))
+            .await
+            .unwrap();
+        assert_eq!(result.etag, None);
+        assert_eq!(result.last_modified, None);
+    }
+
     #[tokio::test]
     async fn test_content_disposition_path_traversal_sanitized() {
         let server = MockServer::start().await;
@@ -631,6 +923,17 @@ mod tests {
         assert_eq!(extract_filename_from_url(&url), "app.dmg");
     }
 
+    #[test]
+    fn test_extract_filename_percent_decodes_path() {
+        let url =
+            url::Url::parse("https://example.com/files/Q3%20Report%20%282024%29.pdf").unwrap();
+        assert_eq!(extract_filename_from_url(&url), "Q3 Report (2024).pdf");
+        let url = url::Url::parse("https://example.com/caf%E9.pdf").unwrap();
+        assert_eq!(extract_filename_from_url(&url), "caf\u{FFFD}.pdf");
+        let url = url::Url::parse("http://münchen.example/Stra%C3%9Fe.pdf").unwrap();
+        assert_eq!(extract_filename_from_url(&url), "Straße.pdf");
+    }
+
     #[test]
     fn test_extract_filename_ignores_non_filename_params() {
         let url = url::Url::parse("https://example.com/installer.pkg?ref=homepage&id=42").unwrap();
@@ -644,4 +947,115 @@ mod tests {
         assert!(!has_file_extension("noextension"));
         assert!(!has_file_extension(""));
     }
+
+    #[tokio::test]
+    async fn test_ipfs_url_analyzed_through_gateway() {
+        const CID: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path(format!("/ipfs/{CID}/paper.pdf")))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", "2048")
+                    .insert_header("Content-Type", "application/pdf"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let options = AnalyzeOptions {
+            ipfs_gateway: Some(server.uri()),
+            ..Default::default()
+        };
+        let result = analyze_url_with_options(&format!("ipfs://{CID}/paper.pdf"), Some(&options))
+            .await
+            .unwrap();
+
+        assert_eq!(result.filename, "paper.pdf");
+        assert_eq!(result.total_size, Some(2048));
+    }
+
+    /// `/get/app.zip` answers with an HTML page that meta-refreshes to the
+    /// real file at `/files/app.zip`.
+    async fn mount_refresh_page(server: &MockServer) {
+        let page = r#"<html><head><meta http-equiv="refresh" content="0; url=/files/app.zip"></head></html>"#;
+        for verb in ["HEAD", "GET"] {
+            Mock::given(method(verb))
+                .and(path("/get/app.zip"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_raw(page, "text/html; charset=utf-8"),
+                )
+                .mount(server)
+                .await;
+        }
+        Mock::given(method("HEAD"))
+            .and(path("/files/app.zip"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", "4096")
+                    .insert_header("Content-Type", "application/zip")
+                    .insert_header("Accept-Ranges", "bytes"),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_html_refresh_followed_when_enabled() {
+        let server = MockServer::start().await;
+        mount_refresh_page(&server).await;
+
+        let url = format!("{}/get/app.zip", server.uri());
+        let options = AnalyzeOptions {
+            follow_html_refresh: true,
+            ..Default::default()
+        };
+        let result = analyze_url_with_options(&url, Some(&options))
+            .await
+            .unwrap();
+
+        assert_eq!(result.url, format!("{}/files/app.zip", server.uri()));
+        assert_eq!(result.filename, "app.zip");
+        assert_eq!(result.total_size, Some(4096));
+        assert_eq!(result.mime_type, Some("application/zip".to_string()));
+        assert!(result.resumable);
+    }
+
+    #[tokio::test]
+    async fn test_html_refresh_ignored_by_default() {
+        let server = MockServer::start().await;
+        mount_refresh_page(&server).await;
+
+        let url = format!("{}/get/app.zip", server.uri());
+        let result = analyze_url(&url).await.unwrap();
+
+        assert_eq!(result.url, url);
+        assert_eq!(result.mime_type, Some("text/html".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_content_encoded_response_is_not_resumable() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/page.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Length", "512")
+                    .insert_header("Content-Encoding", "gzip")
+                    .insert_header("Accept-Ranges", "bytes"),
+            )
+            .mount(&server)
+            .await;
+
+        let result = analyze_url(&format!("{}/page.txt", server.uri()))
+            .await
+            .unwrap();
+        assert!(!result.resumable);
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(
+            requests[0].headers.get("accept-encoding").unwrap(),
+            "identity"
+        );
+    }
 }
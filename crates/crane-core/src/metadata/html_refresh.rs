@@ -0,0 +1,224 @@
+// Find the real file behind an HTML "your download will start shortly" page.
+
+/// Largest page body scanned for a redirect target.
+pub(crate) const MAX_PAGE_BYTES: usize = 512 * 1024;
+
+/// Where an HTML download page points: its `<meta http-equiv="refresh">`
+/// URL, otherwise its only `<a download>` link, otherwise its only link to
+/// a non-HTML file. Relative targets resolve against `base`; only http(s)
+/// targets are returned.
+pub fn find_redirect_target(html: &str, base: &url::Url) -> Option<url::Url> {
+    let mut download_links = Vec::new();
+    let mut file_links = Vec::new();
+
+    for (name, attrs) in tags(html) {
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+        };
+        match name.as_str() {
+            "meta" if attr("http-equiv").is_some_and(|v| v.eq_ignore_ascii_case("refresh")) => {
+                if let Some(target) = attr("content")
+                    .and_then(refresh_url)
+                    .and_then(|u| resolve(base, u))
+                {
+                    return Some(target);
+                }
+            }
+            "a" => {
+                let Some(target) = attr("href").and_then(|u| resolve(base, u)) else {
+                    continue;
+                };
+                if attr("download").is_some() {
+                    push_unique(&mut download_links, target);
+                } else if links_to_file(&target) {
+                    push_unique(&mut file_links, target);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match (download_links.len(), file_links.len()) {
+        (1, _) => download_links.pop(),
+        (0, 1) => file_links.pop(),
+        _ => None,
+    }
+}
+
+/// The URL part of a refresh `content` value such as `0; url='/file.zip'`.
+fn refresh_url(content: &str) -> Option<&str> {
+    let (_, rest) = content.split_once([';', ','])?;
+    let rest = rest.trim();
+    let rest = match rest.get(..3) {
+        Some(key) if key.eq_ignore_ascii_case("url") => {
+            rest[3..].trim_start().strip_prefix('=')?.trim()
+        }
+        _ => rest,
+    };
+    let url = rest.trim_matches(|c| c == '"' || c == '\'').trim();
+    (!url.is_empty()).then_some(url)
+}
+
+fn resolve(base: &url::Url, href: &str) -> Option<url::Url> {
+    let target = base.join(href.trim()).ok()?;
+    matches!(target.scheme(), "http" | "https").then_some(target)
+}
+
+/// Whether the link's last path segment has a file extension other than
+/// an HTML one.
+fn links_to_file(target: &url::Url) -> bool {
+    let segment = target.path().rsplit('/').next().unwrap_or("");
+    match segment.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !ext.is_empty() => !matches!(
+            ext.to_ascii_lowercase().as_str(),
+            "html" | "htm" | "xhtml" | "php" | "asp" | "aspx" | "jsp"
+        ),
+        _ => false,
+    }
+}
+
+fn push_unique(links: &mut Vec<url::Url>, target: url::Url) {
+    if !links.contains(&target) {
+        links.push(target);
+    }
+}
+
+/// Opening tags as lowercase names with their (lowercase-keyed) attributes.
+fn tags(html: &str) -> impl Iterator<Item = (String, Vec<(String, String)>)> + '_ {
+    html.split('<').skip(1).filter_map(|tag| {
+        let tag = tag.split('>').next().unwrap_or("");
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        if name_end == 0 {
+            return None;
+        }
+        Some((
+            tag[..name_end].to_ascii_lowercase(),
+            attributes(&tag[name_end..]),
+        ))
+    })
+}
+
+fn attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return attrs;
+        }
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = "";
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            (value, rest) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    match body.find(quote) {
+                        Some(end) => (&body[..end], &body[end + 1..]),
+                        None => (body, ""),
+                    }
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    after.split_at(end)
+                }
+            };
+        }
+        if !name.is_empty() {
+            attrs.push((name, decode_entities(value)));
+        }
+    }
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> url::Url {
+        url::Url::parse("https://dl.example.com/get/app.zip?id=7").unwrap()
+    }
+
+    fn target(html: &str) -> Option<String> {
+        find_redirect_target(html, &base()).map(|u| u.to_string())
+    }
+
+    #[test]
+    fn test_meta_refresh_variants() {
+        let pages = [
+            r#"<meta http-equiv="refresh" content="0; url=https://cdn.example.com/app.zip">"#,
+            r#"<META CONTENT="3;URL='https://cdn.example.com/app.zip'" HTTP-EQUIV="Refresh" />"#,
+            "<meta http-equiv=refresh content=\"0, url = https://cdn.example.com/app.zip\">",
+        ];
+        for page in pages {
+            assert_eq!(
+                target(page).as_deref(),
+                Some("https://cdn.example.com/app.zip"),
+                "{page}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_meta_refresh_relative_with_entities() {
+        let page = r#"<html><head>
+            <meta http-equiv="refresh" content="0;url=/files/app.zip?token=a&amp;v=2">
+            </head><body><a href="/other.zip">mirror</a></body></html>"#;
+        assert_eq!(
+            target(page).as_deref(),
+            Some("https://dl.example.com/files/app.zip?token=a&v=2")
+        );
+    }
+
+    #[test]
+    fn test_single_download_anchor() {
+        let page = r#"<a href="/help.html">Help</a>
+            <a href="/mirror1/app.zip">Mirror</a>
+            <a class="btn" download href="/files/app.zip">Download</a>"#;
+        assert_eq!(
+            target(page).as_deref(),
+            Some("https://dl.example.com/files/app.zip")
+        );
+    }
+
+    #[test]
+    fn test_single_file_link() {
+        let page = r#"<a href="index.html">Home</a><a href="app-1.2.tar.gz">Get it</a>"#;
+        assert_eq!(
+            target(page).as_deref(),
+            Some("https://dl.example.com/get/app-1.2.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_or_missing_target() {
+        // Several candidate files: no way to tell which one is meant
+        assert_eq!(
+            target(r#"<a href="a.zip">A</a><a href="b.zip">B</a>"#),
+            None
+        );
+        // Refresh without a URL and non-http links are ignored
+        assert_eq!(target(r#"<meta http-equiv="refresh" content="30">"#), None);
+        assert_eq!(
+            target(r#"<a download href="javascript:start()">Go</a>"#),
+            None
+        );
+        assert_eq!(target("<p>Please log in</p>"), None);
+    }
+}
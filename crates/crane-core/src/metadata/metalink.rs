@@ -0,0 +1,417 @@
+// Metalink 4.0 (RFC 5854) parsing.
+
+use tokio_util::sync::CancellationToken;
+
+use crate::hash::HashAlgorithm;
+use crate::metadata::analyzer::{analysis_client, AnalyzeOptions};
+use crate::types::{CraneError, ExpectedHash};
+
+const METALINK_NS: &str = "urn:ietf:params:xml:ns:metalink";
+
+/// Metalink documents are small XML manifests; refuse anything larger.
+const MAX_METALINK_SIZE: usize = 1024 * 1024;
+
+/// A parsed Metalink document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metalink {
+    pub files: Vec<MetalinkFile>,
+}
+
+/// One `<file>` entry: a single logical file with its mirrors and digests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetalinkFile {
+    pub name: String,
+    pub size: Option<u64>,
+    pub urls: Vec<MetalinkUrl>,
+    pub hashes: Vec<MetalinkHash>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetalinkUrl {
+    pub url: String,
+    /// Lower values are preferred (RFC 5854 §4.2.16). Unset sorts last.
+    pub priority: Option<u32>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetalinkHash {
+    /// Hash type as written in the document, e.g. `"sha-256"`.
+    pub hash_type: String,
+    pub value: String,
+}
+
+impl MetalinkFile {
+    /// Mirror URLs ordered by priority (most preferred first).
+    pub fn mirrors(&self) -> Vec<&str> {
+        let mut urls: Vec<&MetalinkUrl> = self.urls.iter().collect();
+        urls.sort_by_key(|u| u.priority.unwrap_or(u32::MAX));
+        urls.into_iter().map(|u| u.url.as_str()).collect()
+    }
+
+    /// The strongest published digest we can verify, if any.
+    pub fn strongest_hash(&self) -> Option<ExpectedHash> {
+        self.hashes
+            .iter()
+            .filter_map(|h| {
+                // IANA hash names (RFC 5854 §4.2.4)
+                let algorithm = match h.hash_type.to_ascii_lowercase().as_str() {
+                    "sha-512" => HashAlgorithm::Sha512,
+                    "sha-256" => HashAlgorithm::Sha256,
+                    "sha-1" => HashAlgorithm::Sha1,
+                    "md5" => HashAlgorithm::Md5,
+                    _ => return None,
+                };
+                Some(ExpectedHash {
+                    algorithm,
+                    value: h.value.to_ascii_lowercase(),
+                })
+            })
+            .max_by_key(|h| hash_strength(h.algorithm))
+    }
+}
+
+fn hash_strength(algorithm: HashAlgorithm) -> u8 {
+    match algorithm {
+        HashAlgorithm::Md5 | HashAlgorithm::Auto => 0,
+        HashAlgorithm::Sha1 => 1,
+        HashAlgorithm::Sha256 => 2,
+        HashAlgorithm::Sha512 => 3,
+    }
+}
+
+/// Whether a URL points at a Metalink document (by path extension).
+pub fn is_metalink_url(url: &str) -> bool {
+    let Ok(parsed) = url::Url::parse(url) else {
+        return false;
+    };
+    let path = parsed.path().to_ascii_lowercase();
+    path.ends_with(".metalink") || path.ends_with(".meta4")
+}
+
+/// Parse a Metalink 4.0 XML document.
+pub fn parse_metalink(xml: &str) -> Result<Metalink, CraneError> {
+    let doc = roxmltree::Document::parse(xml)
+        .map_err(|e| CraneError::Metalink(format!("invalid XML: {e}")))?;
+
+    let root = doc.root_element();
+    if !root.has_tag_name((METALINK_NS, "metalink")) {
+        return Err(CraneError::Metalink(
+            "not a Metalink 4.0 document".to_string(),
+        ));
+    }
+
+    let mut files = Vec::new();
+    for file_node in root
+        .children()
+        .filter(|n| n.has_tag_name((METALINK_NS, "file")))
+    {
+        let name = file_node
+            .attribute("name")
+            .ok_or_else(|| CraneError::Metalink("<file> is missing a name".to_string()))?
+            .to_string();
+
+        let mut file = MetalinkFile {
+            name,
+            size: None,
+            urls: Vec::new(),
+            hashes: Vec::new(),
+        };
+
+        for child in file_node.children().filter(|n| n.is_element()) {
+            if child.tag_name().namespace() != Some(METALINK_NS) {
+                continue;
+            }
+            let text = child.text().unwrap_or("").trim();
+            match child.tag_name().name() {
+                "size" => {
+                    file.size = Some(
+                        text.parse()
+                            .map_err(|_| CraneError::Metalink(format!("invalid size: {text}")))?,
+                    );
+                }
+                "url" if !text.is_empty() => file.urls.push(MetalinkUrl {
+                    url: text.to_string(),
+                    priority: child.attribute("priority").and_then(|p| p.parse().ok()),
+                    location: child.attribute("location").map(str::to_string),
+                }),
+                "hash" => {
+                    if let Some(hash_type) = child.attribute("type") {
+                        file.hashes.push(MetalinkHash {
+                            hash_type: hash_type.to_string(),
+                            value: text.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        files.push(file);
+    }
+
+    if files.is_empty() {
+        return Err(CraneError::Metalink(
+            "document contains no <file> entries".to_string(),
+        ));
+    }
+
+    Ok(Metalink { files })
+}
+
+/// Download and parse a Metalink document with the same client settings as
+/// URL analysis. Gives up with `CraneError::Cancelled` once `cancel` fires.
+pub async fn fetch_metalink(
+    url: &str,
+    options: Option<&AnalyzeOptions>,
+    cancel: &CancellationToken,
+) -> Result<Metalink, CraneError> {
+    let client = analysis_client(options)?;
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(CraneError::Cancelled),
+        metalink = read_metalink(&client, url) => metalink,
+    }
+}
+
+async fn read_metalink(client: &reqwest::Client, url: &str) -> Result<Metalink, CraneError> {
+    let response = client.get(url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CraneError::Http {
+            status: status.as_u16(),
+            message: status.canonical_reason().unwrap_or("Unknown").to_string(),
+        });
+    }
+
+    // Checked before and while reading, so a hostile server can't stream
+    // an unbounded body into memory
+    let too_large =
+        || CraneError::Metalink(format!("document larger than {MAX_METALINK_SIZE} bytes"));
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_METALINK_SIZE as u64)
+    {
+        return Err(too_large());
+    }
+    let mut response = response;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_METALINK_SIZE {
+            return Err(too_large());
+        }
+    }
+
+    let xml = std::str::from_utf8(&body)
+        .map_err(|e| CraneError::Metalink(format!("document is not UTF-8: {e}")))?;
+    parse_metalink(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <published>2024-01-01T00:00:00Z</published>
+  <file name="example.iso">
+    <size>14471447</size>
+    <hash type="md5">0123456789ABCDEF0123456789ABCDEF</hash>
+    <hash type="sha-256">f0ad929cd259957e160ea442eb80986b5f01b0a2b3b3a2e2e3b3c2ad6d9f1a3c</hash>
+    <url location="de" priority="2">http://mirror-b.example.com/example.iso</url>
+    <url location="us" priority="1">https://mirror-a.example.com/example.iso</url>
+    <url>ftp://ftp.example.com/example.iso</url>
+  </file>
+</metalink>"#;
+
+    #[tokio::test]
+    async fn test_fetch_refuses_oversized_document() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/big.meta4"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(vec![b' '; MAX_METALINK_SIZE + 1]),
+            )
+            .mount(&server)
+            .await;
+
+        let err = fetch_metalink(
+            &format!("{}/big.meta4", server.uri()),
+            None,
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, CraneError::Metalink(ref m) if m.contains("larger")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_goes_through_configured_proxy() {
+        use wiremock::matchers::{header, method};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let proxy = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("host", "mirrors.crane.invalid"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(SAMPLE))
+            .expect(1)
+            .mount(&proxy)
+            .await;
+        let options = AnalyzeOptions {
+            proxy: Some(proxy.uri()),
+            ..Default::default()
+        };
+
+        let metalink = fetch_metalink(
+            "http://mirrors.crane.invalid/example.meta4",
+            Some(&options),
+            &CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(metalink.files[0].name, "example.iso");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stops_when_cancelled() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(SAMPLE)
+                    .set_delay(std::time::Duration::from_secs(20)),
+            )
+            .mount(&server)
+            .await;
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+
+        let err = fetch_metalink(&format!("{}/slow.meta4", server.uri()), None, &cancel)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CraneError::Cancelled));
+    }
+
+    #[test]
+    fn test_parse_sample_metalink() {
+        let metalink = parse_metalink(SAMPLE).unwrap();
+        assert_eq!(metalink.files.len(), 1);
+
+        let file = &metalink.files[0];
+        assert_eq!(file.name, "example.iso");
+        assert_eq!(file.size, Some(14471447));
+        assert_eq!(file.urls.len(), 3);
+        assert_eq!(file.hashes.len(), 2);
+        assert_eq!(file.urls[0].location.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn test_mirrors_sorted_by_priority() {
+        let metalink = parse_metalink(SAMPLE).unwrap();
+        assert_eq!(
+            metalink.files[0].mirrors(),
+            vec![
+                "https://mirror-a.example.com/example.iso",
+                "http://mirror-b.example.com/example.iso",
+                "ftp://ftp.example.com/example.iso",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strongest_hash_prefers_sha256() {
+        let metalink = parse_metalink(SAMPLE).unwrap();
+        let hash = metalink.files[0].strongest_hash().unwrap();
+        assert_eq!(hash.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(
+            hash.value,
+            "f0ad929cd259957e160ea442eb80986b5f01b0a2b3b3a2e2e3b3c2ad6d9f1a3c"
+        );
+    }
+
+    #[test]
+    fn test_strongest_hash_ignores_unsupported_types() {
+        let xml = r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="a.bin">
+    <hash type="sha-384">abcd</hash>
+    <hash type="md5">ABCD</hash>
+    <url>https://example.com/a.bin</url>
+  </file>
+</metalink>"#;
+        let file = &parse_metalink(xml).unwrap().files[0];
+        let hash = file.strongest_hash().unwrap();
+        assert_eq!(hash.algorithm, HashAlgorithm::Md5);
+        assert_eq!(hash.value, "abcd");
+    }
+
+    #[test]
+    fn test_strongest_hash_maps_sha1_and_sha512() {
+        let xml = r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="a.bin">
+    <hash type="sha-1">1111</hash>
+    <hash type="SHA-512">5555</hash>
+    <hash type="sha-256">2222</hash>
+    <url>https://example.com/a.bin</url>
+  </file>
+</metalink>"#;
+        let file = &parse_metalink(xml).unwrap().files[0];
+        let hash = file.strongest_hash().unwrap();
+        assert_eq!(hash.algorithm, HashAlgorithm::Sha512);
+        assert_eq!(hash.value, "5555");
+
+        let xml = r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="a.bin">
+    <hash type="sha-1">1111</hash>
+    <url>https://example.com/a.bin</url>
+  </file>
+</metalink>"#;
+        let file = &parse_metalink(xml).unwrap().files[0];
+        assert_eq!(
+            file.strongest_hash().unwrap().algorithm,
+            HashAlgorithm::Sha1
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_namespace() {
+        let xml = r#"<metalink version="3.0" xmlns="http://www.metalinker.org/"></metalink>"#;
+        assert!(matches!(
+            parse_metalink(xml).unwrap_err(),
+            CraneError::Metalink(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_xml() {
+        assert!(parse_metalink("<metalink").is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_empty_document() {
+        let xml = r#"<metalink xmlns="urn:ietf:params:xml:ns:metalink"/>"#;
+        let err = parse_metalink(xml).unwrap_err();
+        assert!(err.to_string().contains("no <file>"));
+    }
+
+    #[test]
+    fn test_is_metalink_url() {
+        assert!(is_metalink_url("https://example.com/files/ubuntu.meta4"));
+        assert!(is_metalink_url("https://example.com/files/ubuntu.METALINK"));
+        assert!(is_metalink_url(
+            "https://example.com/ubuntu.meta4?mirror=eu"
+        ));
+        assert!(!is_metalink_url("https://example.com/ubuntu.iso"));
+        assert!(!is_metalink_url("not a url"));
+    }
+}
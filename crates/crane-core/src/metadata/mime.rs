@@ -72,6 +72,161 @@ pub fn categorize_extension(filename: &str) -> FileCategory {
     }
 }
 
+/// Canonical file extension (without the dot) for a MIME type, for naming
+/// files the server didn't give one. Parameters such as `; charset=` are
+/// ignored. `None` for types without a single obvious extension.
+pub fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    let mime = mime.split(';').next().unwrap_or("").trim().to_lowercase();
+
+    let ext = match mime.as_str() {
+        "application/pdf" => "pdf",
+        "application/msword" => "doc",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "application/vnd.ms-excel" => "xls",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "application/vnd.ms-powerpoint" => "ppt",
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation" => "pptx",
+        "application/epub+zip" => "epub",
+        "application/rtf" => "rtf",
+        "text/plain" => "txt",
+        "text/csv" => "csv",
+
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "video/x-matroska" | "application/x-matroska" => "mkv",
+        "video/quicktime" => "mov",
+        "video/x-msvideo" => "avi",
+
+        "audio/mpeg" => "mp3",
+        "audio/flac" => "flac",
+        "audio/wav" | "audio/x-wav" => "wav",
+        "audio/ogg" => "ogg",
+        "audio/mp4" => "m4a",
+
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+
+        "application/zip" => "zip",
+        "application/x-rar-compressed" => "rar",
+        "application/x-7z-compressed" => "7z",
+        "application/gzip" | "application/x-gzip" => "gz",
+        "application/x-tar" => "tar",
+        "application/x-bzip2" => "bz2",
+        "application/x-xz" => "xz",
+        "application/zstd" => "zst",
+
+        "application/x-msdownload" | "application/vnd.microsoft.portable-executable" => "exe",
+        "application/x-msi" => "msi",
+        "application/x-apple-diskimage" => "dmg",
+        "application/vnd.debian.binary-package" => "deb",
+        "application/x-rpm" => "rpm",
+        "application/x-iso9660-image" => "iso",
+        "application/vnd.android.package-archive" => "apk",
+
+        _ => return None,
+    };
+    Some(ext)
+}
+
+/// Bytes of a file's start that `categorize_content` looks at.
+pub const SNIFF_LEN: usize = 64;
+
+/// Category from a file's leading bytes (magic numbers), for when the
+/// server's MIME type was generic or wrong. `None` if nothing matches.
+pub fn categorize_content(head: &[u8]) -> Option<FileCategory> {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+
+    if at(4, b"ftyp") {
+        let brand = head.get(8..12).unwrap_or_default();
+        return Some(match brand {
+            b"M4A " | b"M4B " => FileCategory::Audio,
+            b"heic" | b"heix" | b"mif1" | b"avif" => FileCategory::Images,
+            _ => FileCategory::Video,
+        });
+    }
+    if at(0, b"RIFF") {
+        return match head.get(8..12)? {
+            b"AVI " => Some(FileCategory::Video),
+            b"WAVE" => Some(FileCategory::Audio),
+            b"WEBP" => Some(FileCategory::Images),
+            _ => None,
+        };
+    }
+
+    let signatures: &[(&[u8], FileCategory)] = &[
+        (b"\x1A\x45\xDF\xA3", FileCategory::Video), // Matroska / WebM
+        (b"FLV\x01", FileCategory::Video),
+        (b"ID3", FileCategory::Audio),
+        (b"\xFF\xFB", FileCategory::Audio), // MP3 frame
+        (b"\xFF\xF3", FileCategory::Audio),
+        (b"\xFF\xF2", FileCategory::Audio),
+        (b"fLaC", FileCategory::Audio),
+        (b"OggS", FileCategory::Audio),
+        (b"\x89PNG\r\n\x1A\n", FileCategory::Images),
+        (b"\xFF\xD8\xFF", FileCategory::Images),
+        (b"GIF87a", FileCategory::Images),
+        (b"GIF89a", FileCategory::Images),
+        (b"%PDF-", FileCategory::Documents),
+        (b"PK\x03\x04", FileCategory::Archives),
+        (b"\x1F\x8B", FileCategory::Archives),
+        (b"7z\xBC\xAF\x27\x1C", FileCategory::Archives),
+        (b"Rar!\x1A\x07", FileCategory::Archives),
+        (b"\xFD7zXZ\x00", FileCategory::Archives),
+        (b"BZh", FileCategory::Archives),
+        (b"\x28\xB5\x2F\xFD", FileCategory::Archives), // zstd
+        (b"!<arch>\ndebian", FileCategory::Software),
+        (b"\x7FELF", FileCategory::Software),
+        (b"MZ", FileCategory::Software),
+    ];
+    signatures
+        .iter()
+        .find(|(magic, _)| at(0, magic))
+        .map(|(_, category)| category.clone())
+}
+
+/// Final category for a finished download, given the server's MIME type,
+/// the category it was added with and what `categorize_content` found.
+///
+/// The file's content overrides a category that is `Other` or was derived
+/// from the server's MIME type, since servers often mislabel files. A
+/// category chosen by the user or taken from the file extension is kept.
+/// So is the server's answer when the content is a container it can also
+/// describe: a ZIP that is a .docx or .apk, an MP4 or Ogg holding only
+/// audio, and so on.
+pub fn reconcile_category(
+    mime: Option<&str>,
+    current: FileCategory,
+    sniffed: Option<FileCategory>,
+) -> FileCategory {
+    let Some(sniffed) = sniffed else {
+        return current;
+    };
+    if current == FileCategory::Other {
+        return sniffed;
+    }
+    let Some(mime) = mime.filter(|m| categorize_mime(m) == current) else {
+        return current;
+    };
+    // Plain text is never a container
+    let textual = mime.to_lowercase().starts_with("text/");
+    let container = !textual
+        && matches!(
+            (&sniffed, &current),
+            (FileCategory::Archives, FileCategory::Documents)
+                | (FileCategory::Archives, FileCategory::Software)
+                | (FileCategory::Video, FileCategory::Audio)
+                | (FileCategory::Audio, FileCategory::Video)
+        );
+    if container {
+        current
+    } else {
+        sniffed
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,4 +394,101 @@ mod tests {
             FileCategory::Software,
         );
     }
+
+    #[test]
+    fn test_content_video_audio_images() {
+        let mut mp4 = vec![0, 0, 0, 0x20];
+        mp4.extend_from_slice(b"ftypisom\0\0\x02\0");
+        assert_eq!(categorize_content(&mp4), Some(FileCategory::Video));
+
+        let mut m4a = vec![0, 0, 0, 0x20];
+        m4a.extend_from_slice(b"ftypM4A \0\0\0\0");
+        assert_eq!(categorize_content(&m4a), Some(FileCategory::Audio));
+
+        assert_eq!(
+            categorize_content(b"RIFF\x24\0\0\0WAVEfmt "),
+            Some(FileCategory::Audio)
+        );
+        assert_eq!(
+            categorize_content(b"\x1A\x45\xDF\xA3\x9F\x42\x86\x81"),
+            Some(FileCategory::Video)
+        );
+        assert_eq!(
+            categorize_content(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR"),
+            Some(FileCategory::Images)
+        );
+    }
+
+    #[test]
+    fn test_content_documents_archives_software() {
+        assert_eq!(
+            categorize_content(b"%PDF-1.7\n"),
+            Some(FileCategory::Documents)
+        );
+        assert_eq!(
+            categorize_content(b"PK\x03\x04\x14\0"),
+            Some(FileCategory::Archives)
+        );
+        assert_eq!(
+            categorize_content(b"MZ\x90\0\x03\0"),
+            Some(FileCategory::Software)
+        );
+    }
+
+    #[test]
+    fn test_reconcile_png_zip_mp4_against_server_mime() {
+        let png = categorize_content(b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR");
+        let zip = categorize_content(b"PK\x03\x04\x14\0\0\0\x08\0");
+        let mut mp4 = vec![0, 0, 0, 0x18];
+        mp4.extend_from_slice(b"ftypmp42\0\0\0\0mp42isom");
+        let mp4 = categorize_content(&mp4);
+
+        // Generic or wrong Content-Type: the content decides
+        let octet = Some("application/octet-stream");
+        assert_eq!(
+            reconcile_category(octet, FileCategory::Other, png.clone()),
+            FileCategory::Images
+        );
+        assert_eq!(
+            reconcile_category(Some("text/plain"), FileCategory::Documents, zip.clone()),
+            FileCategory::Archives
+        );
+        assert_eq!(
+            reconcile_category(Some("image/jpeg"), FileCategory::Images, mp4.clone()),
+            FileCategory::Video
+        );
+
+        // Containers the server described more precisely keep its category
+        let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        assert_eq!(
+            reconcile_category(Some(docx), FileCategory::Documents, zip.clone()),
+            FileCategory::Documents
+        );
+        assert_eq!(
+            reconcile_category(Some("audio/mp4"), FileCategory::Audio, mp4.clone()),
+            FileCategory::Audio
+        );
+
+        // Categories not from the MIME type (user, extension) are left alone,
+        // as is everything when the content is unrecognised
+        assert_eq!(
+            reconcile_category(octet, FileCategory::Software, zip),
+            FileCategory::Software
+        );
+        assert_eq!(
+            reconcile_category(None, FileCategory::Video, png),
+            FileCategory::Video
+        );
+        assert_eq!(
+            reconcile_category(Some("text/plain"), FileCategory::Documents, None),
+            FileCategory::Documents
+        );
+    }
+
+    #[test]
+    fn test_content_unknown() {
+        assert_eq!(categorize_content(b""), None);
+        assert_eq!(categorize_content(b"RIFF\0\0\0\0XXXX"), None);
+        assert_eq!(categorize_content(&[0xAA; 64]), None);
+    }
 }
@@ -1,7 +1,9 @@
 pub mod analyzer;
+pub mod html_refresh;
+pub mod metalink;
 pub mod mime;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Sanitize a filename to prevent path traversal attacks.
 ///
@@ -68,10 +70,125 @@ pub fn sanitize_filename(name: &str) -> String {
     }
 }
 
+/// `filename` with the canonical extension for `mime` appended when it has
+/// no sensible one of its own, so a server-named `report` served as
+/// `application/pdf` is saved as `report.pdf`. An extension counts as
+/// sensible if it is short, alphanumeric and not all digits (`v1.2` has
+/// none). Unknown MIME types leave the name alone.
+pub fn with_mime_extension(filename: &str, mime: Option<&str>) -> String {
+    let Some(ext) = mime.and_then(mime::extension_for_mime) else {
+        return filename.to_string();
+    };
+    let sensible = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| {
+            (1..=5).contains(&e.len())
+                && e.chars().all(|c| c.is_ascii_alphanumeric())
+                && e.chars().any(|c| c.is_ascii_alphabetic())
+        });
+    if sensible {
+        filename.to_string()
+    } else {
+        format!("{filename}.{ext}")
+    }
+}
+
+/// `path` if it is free, otherwise the first free `stem (n).ext` next to
+/// it. Compound `.tar.*` extensions are kept together.
+pub fn next_free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let split = match name.to_ascii_lowercase().rfind(".tar.") {
+        Some(pos) if pos > 0 => pos,
+        _ => name.rfind('.').filter(|&pos| pos > 0).unwrap_or(name.len()),
+    };
+    let (stem, ext) = name.split_at(split);
+    (1..)
+        .map(|n| path.with_file_name(format!("{stem} ({n}){ext}")))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded suffix search")
+}
+
+/// Set the permission bits of a finished download to `mode` (e.g. `0o600`)
+/// when one is configured. Does nothing on platforms without Unix modes.
+pub async fn apply_file_mode(
+    path: &Path,
+    mode: Option<u32>,
+) -> Result<(), crate::types::CraneError> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_next_free_path_suffixes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+
+        // Free names are returned unchanged
+        assert_eq!(next_free_path(&dir.join("a.zip")), dir.join("a.zip"));
+
+        std::fs::write(dir.join("a.zip"), b"").unwrap();
+        assert_eq!(next_free_path(&dir.join("a.zip")), dir.join("a (1).zip"));
+        std::fs::write(dir.join("a (1).zip"), b"").unwrap();
+        assert_eq!(next_free_path(&dir.join("a.zip")), dir.join("a (2).zip"));
+
+        std::fs::write(dir.join("src.tar.gz"), b"").unwrap();
+        assert_eq!(
+            next_free_path(&dir.join("src.tar.gz")),
+            dir.join("src (1).tar.gz")
+        );
+
+        std::fs::write(dir.join("README"), b"").unwrap();
+        assert_eq!(next_free_path(&dir.join("README")), dir.join("README (1)"));
+    }
+
+    #[test]
+    fn test_with_mime_extension_appends_for_extensionless() {
+        assert_eq!(
+            with_mime_extension("report", Some("application/pdf")),
+            "report.pdf"
+        );
+        assert_eq!(
+            with_mime_extension("release-1.0", Some("application/zip; charset=binary")),
+            "release-1.0.zip"
+        );
+        // Unknown or missing types leave the name alone
+        assert_eq!(
+            with_mime_extension("report", Some("application/octet-stream")),
+            "report"
+        );
+        assert_eq!(with_mime_extension("report", None), "report");
+    }
+
+    #[test]
+    fn test_with_mime_extension_keeps_existing_extension() {
+        assert_eq!(
+            with_mime_extension("report.pdf", Some("application/pdf")),
+            "report.pdf"
+        );
+        // A different but sensible extension is trusted over the server
+        assert_eq!(
+            with_mime_extension("10GB.bin", Some("application/zip")),
+            "10GB.bin"
+        );
+    }
+
     #[test]
     fn test_validate_content_type_html_for_zip() {
         let result = validate_content_type(Some("text/html"), "archive.zip");
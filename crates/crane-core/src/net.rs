@@ -0,0 +1,433 @@
+// Minimal HTTP interface for running Crane headless (feature `net`).
+//
+//   GET  /downloads   every download, as JSON (without cookies or headers)
+//   POST /downloads   `{"url": ..., "options": {...}}`, answers `{"id": ...}`
+//   GET  /events      Server-Sent Events stream of `DownloadProgress`
+//
+// Every request must name a loopback `Host` (and `Origin`, when a browser
+// sends one), so pages on other sites can't reach it through DNS rebinding,
+// and present `Authorization: Bearer <token>`. Adding a download also needs
+// a JSON content type. Bind it to loopback.
+
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, Limited, StreamBody};
+use hyper::body::{Frame, Incoming};
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::types::{CraneError, DownloadOptions};
+use crate::Crane;
+
+type Body = BoxBody<Bytes, Infallible>;
+
+/// Largest `POST /downloads` body accepted.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+struct AddRequest {
+    url: String,
+    #[serde(default)]
+    options: DownloadOptions,
+}
+
+/// Serve `crane` to connections on `listener`. `token` is the bearer token
+/// every request must present. Runs until accepting fails or the future
+/// is dropped.
+pub async fn serve(
+    crane: Arc<Crane>,
+    listener: TcpListener,
+    token: String,
+) -> Result<(), CraneError> {
+    let token: Arc<str> = token.into();
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let crane = crane.clone();
+        let token = token.clone();
+        tokio::spawn(async move {
+            let service =
+                hyper::service::service_fn(move |req| handle(crane.clone(), token.clone(), req));
+            let _ = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+async fn handle(
+    crane: Arc<Crane>,
+    token: Arc<str>,
+    req: Request<Incoming>,
+) -> Result<Response<Body>, Infallible> {
+    if !from_local_client(&req) {
+        return Ok(error(StatusCode::FORBIDDEN, "non-local host or origin"));
+    }
+    if !has_bearer_token(&req, &token) {
+        return Ok(error(
+            StatusCode::UNAUTHORIZED,
+            "missing or wrong bearer token",
+        ));
+    }
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/downloads") => list(&crane),
+        (&Method::POST, "/downloads") => add(&crane, req).await,
+        (&Method::GET, "/events") => events(&crane),
+        _ => error(StatusCode::NOT_FOUND, "not found"),
+    };
+    Ok(response)
+}
+
+/// Every download, minus the cookies and headers that can carry forwarded
+/// credentials.
+fn list(crane: &Crane) -> Response<Body> {
+    match crane.list() {
+        Ok(downloads) => {
+            let downloads: Vec<_> = downloads
+                .into_iter()
+                .map(|mut dl| {
+                    dl.cookies = None;
+                    dl.headers = None;
+                    dl
+                })
+                .collect();
+            json(StatusCode::OK, &downloads)
+        }
+        Err(err) => error(StatusCode::INTERNAL_SERVER_ERROR, err),
+    }
+}
+
+async fn add(crane: &Crane, req: Request<Incoming>) -> Response<Body> {
+    if !is_json(&req) {
+        return error(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "content type must be application/json",
+        );
+    }
+    let body = match Limited::new(req.into_body(), MAX_REQUEST_BYTES)
+        .collect()
+        .await
+    {
+        Ok(body) => body.to_bytes(),
+        Err(err) => return error(StatusCode::BAD_REQUEST, err),
+    };
+    let request: AddRequest = match serde_json::from_slice(&body) {
+        Ok(request) => request,
+        Err(err) => return error(StatusCode::BAD_REQUEST, err),
+    };
+    match crane.add(&request.url, request.options).await {
+        Ok(id) => json(StatusCode::CREATED, &serde_json::json!({ "id": id })),
+        Err(err) => error(StatusCode::UNPROCESSABLE_ENTITY, err),
+    }
+}
+
+/// `Host` must name a loopback address; `Origin`, if sent, must too.
+fn from_local_client(req: &Request<Incoming>) -> bool {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<hyper::http::uri::Authority>().ok());
+    if !host.is_some_and(|a| is_loopback_name(a.host())) {
+        return false;
+    }
+    match req.headers().get(header::ORIGIN) {
+        None => true,
+        Some(origin) => origin
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<hyper::Uri>().ok())
+            .and_then(|uri| uri.host().map(is_loopback_name))
+            .unwrap_or(false),
+    }
+}
+
+fn is_loopback_name(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn has_bearer_token(req: &Request<Incoming>, token: &str) -> bool {
+    let Some(presented) = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Compare without an early exit so timing doesn't leak a prefix match
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn is_json(req: &Request<Incoming>) -> bool {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// One `data:` frame per progress event until the client disconnects.
+fn events(crane: &Crane) -> Response<Body> {
+    let frames = futures_util::stream::unfold(crane.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(progress) => {
+                    let data = serde_json::to_string(&progress).unwrap_or_default();
+                    let frame = Frame::data(Bytes::from(format!("data: {data}\n\n")));
+                    return Some((Ok::<_, Infallible>(frame), rx));
+                }
+                // A slow client misses events rather than holding up others
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(StreamBody::new(frames).boxed())
+        .unwrap()
+}
+
+fn json(status: StatusCode, value: &impl serde::Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(body)).boxed())
+        .unwrap()
+}
+
+fn error(status: StatusCode, message: impl Display) -> Response<Body> {
+    json(status, &serde_json::json!({ "error": message.to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DownloadProgress;
+    use futures_util::StreamExt;
+    use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TOKEN: &str = "test-token";
+
+    async fn start(crane: Crane) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(Arc::new(crane), listener, TOKEN.to_string()));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_sse_streams_progress_of_added_download() {
+        let server = MockServer::start().await;
+        for verb in ["HEAD", "GET"] {
+            Mock::given(method(verb))
+                .and(path("/file.bin"))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_bytes(vec![0xAB; 65_536])
+                        .insert_header("content-type", "application/octet-stream"),
+                )
+                .mount(&server)
+                .await;
+        }
+        let tmp = TempDir::new().unwrap();
+        let base = start(Crane::builder().save_dir(tmp.path()).build().unwrap()).await;
+        let client = reqwest::Client::new();
+
+        let events = client
+            .get(format!("{base}/events"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            events.headers()[reqwest::header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+
+        let added = client
+            .post(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(
+                serde_json::json!({
+                    "url": format!("{}/file.bin", server.uri()),
+                    "options": { "cookies": "session=secret" },
+                })
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(added.status(), reqwest::StatusCode::CREATED);
+        let added: serde_json::Value =
+            serde_json::from_slice(&added.bytes().await.unwrap()).unwrap();
+        let id = added["id"].as_str().unwrap().to_string();
+
+        // Read frames until one reports the whole file for this download
+        let mut stream = events.bytes_stream();
+        let mut buffer = String::new();
+        let finished = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while let Some(chunk) = stream.next().await {
+                buffer.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+                while let Some(end) = buffer.find("\n\n") {
+                    let frame = buffer[..end].to_string();
+                    buffer.drain(..end + 2);
+                    let data = frame.strip_prefix("data: ").expect("SSE data frame");
+                    let progress: DownloadProgress = serde_json::from_str(data).unwrap();
+                    if progress.download_id == id && progress.downloaded_size == 65_536 {
+                        return;
+                    }
+                }
+            }
+            panic!("event stream ended");
+        })
+        .await;
+        assert!(finished.is_ok(), "no final progress frame for {id}");
+
+        let listed = client
+            .get(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let listed: Vec<serde_json::Value> = serde_json::from_slice(&listed).unwrap();
+        let listed = listed
+            .iter()
+            .find(|dl| dl["id"] == id.as_str())
+            .expect("added download listed");
+        assert!(listed["cookies"].is_null());
+        assert!(listed["headers"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_requests() {
+        let tmp = TempDir::new().unwrap();
+        let base = start(Crane::builder().save_dir(tmp.path()).build().unwrap()).await;
+        let client = reqwest::Client::new();
+
+        let malformed = client
+            .post(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body("not json")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(malformed.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let unknown = client
+            .get(format!("{base}/nope"))
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_add_requires_token_json_and_local_origin() {
+        let tmp = TempDir::new().unwrap();
+        let base = start(Crane::builder().save_dir(tmp.path()).build().unwrap()).await;
+        let client = reqwest::Client::new();
+        let body = serde_json::json!({ "url": "https://example.com/file.bin" }).to_string();
+        let post = || {
+            client
+                .post(format!("{base}/downloads"))
+                .body(body.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+        };
+
+        let no_token = post().send().await.unwrap();
+        assert_eq!(no_token.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let wrong_token = post().bearer_auth("nope").send().await.unwrap();
+        assert_eq!(wrong_token.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        // A form post is what a cross-site page can send without preflight
+        let form = client
+            .post(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::CONTENT_TYPE, "text/plain")
+            .body(body.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(form.status(), reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let foreign_origin = post()
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::ORIGIN, "https://evil.example")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(foreign_origin.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let rebound_host = client
+            .get(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::HOST, "evil.example")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rebound_host.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let local_origin = client
+            .get(format!("{base}/downloads"))
+            .bearer_auth(TOKEN)
+            .header(reqwest::header::ORIGIN, "http://localhost:3000")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(local_origin.status(), reqwest::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_reads_require_token() {
+        let tmp = TempDir::new().unwrap();
+        let base = start(Crane::builder().save_dir(tmp.path()).build().unwrap()).await;
+        let client = reqwest::Client::new();
+
+        for route in ["/downloads", "/events"] {
+            let no_token = client.get(format!("{base}{route}")).send().await.unwrap();
+            assert_eq!(
+                no_token.status(),
+                reqwest::StatusCode::UNAUTHORIZED,
+                "{route}"
+            );
+            let wrong_token = client
+                .get(format!("{base}{route}"))
+                .bearer_auth("nope")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                wrong_token.status(),
+                reqwest::StatusCode::UNAUTHORIZED,
+                "{route}"
+            );
+        }
+    }
+}
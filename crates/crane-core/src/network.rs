@@ -105,8 +105,9 @@ fn is_public_ipv6(ip: Ipv6Addr) -> bool {
 /// Validate that a URL is safe to fetch (public host, http/https scheme).
 /// Returns `Ok(())` if safe, `Err` with a descriptive error otherwise.
 pub fn validate_url_safe(url: &url::Url) -> Result<(), CraneError> {
+    crate::protocol::reject_torrent(url)?;
     match url.scheme() {
-        "http" | "https" | "ftp" | "ftps" => {}
+        "http" | "https" | "ftp" | "ftps" | "sftp" | "data" | "ipfs" => {}
         scheme => return Err(CraneError::UnsupportedScheme(scheme.to_string())),
     }
 
@@ -119,12 +120,33 @@ pub fn validate_url_safe(url: &url::Url) -> Result<(), CraneError> {
     Ok(())
 }
 
-/// Build a reqwest redirect policy that blocks redirects to private/internal hosts.
-pub fn safe_redirect_policy() -> reqwest::redirect::Policy {
-    reqwest::redirect::Policy::custom(|attempt| {
+/// Host of `url` as recorded in `source_domain`: lowercase ASCII, with
+/// internationalized names in punycode. Hosts of non-special schemes (e.g.
+/// `sftp://`) come back from `url` percent-encoded and are decoded first.
+pub fn source_domain(url: &url::Url) -> Option<String> {
+    match url.host()? {
+        url::Host::Domain(domain) => {
+            let decoded = urlencoding::decode(domain).ok()?;
+            url::Host::parse(&decoded).ok().map(|host| host.to_string())
+        }
+        host => Some(host.to_string()),
+    }
+}
+
+/// Redirects followed when no limit is configured.
+pub const DEFAULT_MAX_REDIRECTS: u32 = 10;
+
+/// Build a reqwest redirect policy that blocks redirects to private/internal
+/// hosts and gives up after `max_redirects` hops (`None` uses
+/// `DEFAULT_MAX_REDIRECTS`).
+pub fn safe_redirect_policy(max_redirects: Option<u32>) -> reqwest::redirect::Policy {
+    let max_redirects = max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS) as usize;
+    reqwest::redirect::Policy::custom(move |attempt| {
         // Limit redirect depth
-        if attempt.previous().len() > 10 {
-            return attempt.error(std::io::Error::other("too many redirects"));
+        if attempt.previous().len() > max_redirects {
+            return attempt.error(std::io::Error::other(format!(
+                "too many redirects (max {max_redirects})"
+            )));
         }
 
         // Extract URL info before consuming `attempt`
@@ -154,6 +176,59 @@ pub fn safe_redirect_policy() -> reqwest::redirect::Policy {
     })
 }
 
+/// Route a client through `proxy` (`http://`, `https://`, `socks5://` or
+/// `socks5h://`, with optional `user:pass@`), bypassing hosts listed in
+/// `no_proxy`. With `socks5h://` the proxy resolves target hostnames.
+///
+/// `None` leaves reqwest's defaults alone (which honour `HTTP_PROXY` and
+/// friends); an empty string forces a direct connection.
+pub fn apply_proxy(
+    builder: reqwest::ClientBuilder,
+    proxy: Option<&str>,
+    no_proxy: Option<&[String]>,
+) -> Result<reqwest::ClientBuilder, CraneError> {
+    let Some(proxy_url) = proxy else {
+        return Ok(builder);
+    };
+    if proxy_url.is_empty() {
+        return Ok(builder.no_proxy());
+    }
+
+    let mut proxy = reqwest::Proxy::all(proxy_url)?;
+    if let Some(hosts) = no_proxy.filter(|h| !h.is_empty()) {
+        proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&hosts.join(",")));
+    }
+    Ok(builder.proxy(proxy))
+}
+
+/// Ask for the file as stored (`Accept-Encoding: identity`) unless the
+/// caller's own `headers` already choose an encoding. A compressed transfer's
+/// length and byte ranges describe the compressed stream, not the file.
+pub fn request_identity_encoding(
+    request: reqwest::RequestBuilder,
+    headers: Option<&std::collections::HashMap<String, String>>,
+) -> reqwest::RequestBuilder {
+    let custom =
+        headers.is_some_and(|h| h.keys().any(|k| k.eq_ignore_ascii_case("accept-encoding")));
+    if custom {
+        request
+    } else {
+        request.header(reqwest::header::ACCEPT_ENCODING, "identity")
+    }
+}
+
+/// Whether a response body is sent with a `Content-Encoding` other than
+/// `identity` (gzip, deflate, br, ...).
+pub fn is_content_encoded(headers: &reqwest::header::HeaderMap) -> bool {
+    headers
+        .get_all(reqwest::header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|e| !e.is_empty() && !e.eq_ignore_ascii_case("identity"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,13 +318,178 @@ mod tests {
         let ftps = url::Url::parse("ftps://example.com/file.txt").unwrap();
         assert!(validate_url_safe(&ftps).is_ok());
 
+        let sftp = url::Url::parse("sftp://example.com/file.txt").unwrap();
+        assert!(validate_url_safe(&sftp).is_ok());
+
+        let data = url::Url::parse("data:text/plain,hello").unwrap();
+        assert!(validate_url_safe(&data).is_ok());
+
+        let ipfs = url::Url::parse(
+            "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi/a.txt",
+        )
+        .unwrap();
+        assert!(validate_url_safe(&ipfs).is_ok());
+
         let gopher = url::Url::parse("gopher://example.com/file.txt").unwrap();
         assert!(validate_url_safe(&gopher).is_err());
 
+        let magnet =
+            url::Url::parse("magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a")
+                .unwrap();
+        assert!(matches!(
+            validate_url_safe(&magnet),
+            Err(CraneError::TorrentUnsupported(_))
+        ));
+
         let metadata = url::Url::parse("http://169.254.169.254/latest/meta-data/").unwrap();
         assert!(validate_url_safe(&metadata).is_err());
 
         let localhost = url::Url::parse("http://localhost:8080/api").unwrap();
         assert!(validate_url_safe(&localhost).is_err());
     }
+
+    #[test]
+    fn test_is_content_encoded() {
+        use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+
+        let encoded = |values: &[&str]| {
+            let mut headers = HeaderMap::new();
+            for v in values {
+                headers.append(CONTENT_ENCODING, HeaderValue::from_str(v).unwrap());
+            }
+            is_content_encoded(&headers)
+        };
+        assert!(!encoded(&[]));
+        assert!(!encoded(&["identity"]));
+        assert!(!encoded(&["Identity, "]));
+        assert!(encoded(&["gzip"]));
+        assert!(encoded(&["identity, br"]));
+        assert!(encoded(&["identity", "deflate"]));
+    }
+
+    /// Minimal SOCKS5 server (no auth, CONNECT only) that records the target
+    /// address of each request and tunnels every connection to `upstream`.
+    #[cfg(feature = "socks-tests")]
+    async fn spawn_socks5_mock(
+        upstream: std::net::SocketAddr,
+    ) -> (
+        std::net::SocketAddr,
+        std::sync::Arc<std::sync::Mutex<Vec<(u8, String)>>>,
+    ) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            while let Ok((mut conn, _)) = listener.accept().await {
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let mut head = [0u8; 2];
+                    conn.read_exact(&mut head).await?;
+                    let mut methods = vec![0u8; head[1] as usize];
+                    conn.read_exact(&mut methods).await?;
+                    conn.write_all(&[5, 0]).await?;
+
+                    let mut req = [0u8; 4];
+                    conn.read_exact(&mut req).await?;
+                    let target = match req[3] {
+                        1 => {
+                            let mut ip = [0u8; 4];
+                            conn.read_exact(&mut ip).await?;
+                            std::net::Ipv4Addr::from(ip).to_string()
+                        }
+                        3 => {
+                            let len = conn.read_u8().await? as usize;
+                            let mut name = vec![0u8; len];
+                            conn.read_exact(&mut name).await?;
+                            String::from_utf8_lossy(&name).into_owned()
+                        }
+                        _ => {
+                            let mut ip = [0u8; 16];
+                            conn.read_exact(&mut ip).await?;
+                            std::net::Ipv6Addr::from(ip).to_string()
+                        }
+                    };
+                    conn.read_u16().await?;
+                    log.lock().unwrap().push((req[3], target));
+
+                    let mut server = tokio::net::TcpStream::connect(upstream).await?;
+                    conn.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+                    tokio::io::copy_bidirectional(&mut conn, &mut server).await?;
+                    Ok::<_, std::io::Error>(())
+                });
+            }
+        });
+        (addr, seen)
+    }
+
+    #[cfg(feature = "socks-tests")]
+    #[tokio::test]
+    async fn test_socks5_remote_dns_defers_resolution_to_proxy() {
+        use crate::config::types::{ProxyConfig, ProxyMode};
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/file.bin"))
+            .and(header("host", "files.crane.invalid"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"via socks".to_vec()))
+            .mount(&server)
+            .await;
+        let (socks_addr, seen) = spawn_socks5_mock(*server.address()).await;
+
+        let mut config = ProxyConfig {
+            mode: ProxyMode::Socks5,
+            host: Some(socks_addr.ip().to_string()),
+            port: Some(socks_addr.port()),
+            socks5_remote_dns: true,
+            ..Default::default()
+        };
+        let get = |proxy_url: String| async move {
+            let client = apply_proxy(reqwest::Client::builder(), Some(&proxy_url), None)
+                .unwrap()
+                .build()
+                .unwrap();
+            client
+                .get("http://files.crane.invalid/file.bin")
+                .send()
+                .await
+        };
+
+        // `.invalid` never resolves locally, so this only works if the proxy
+        // receives the hostname itself (ATYP 3) rather than an address
+        let response = get(config.proxy_url().unwrap()).await.unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"via socks");
+        assert_eq!(
+            seen.lock().unwrap().as_slice(),
+            &[(3, "files.crane.invalid".to_string())]
+        );
+
+        // Local resolution fails before the proxy is ever asked for the host
+        config.socks5_remote_dns = false;
+        assert!(get(config.proxy_url().unwrap()).await.is_err());
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_source_domain_is_ascii() {
+        let domain = |s: &str| source_domain(&url::Url::parse(s).unwrap());
+        assert_eq!(
+            domain("http://münchen.example/datei.pdf").as_deref(),
+            Some("xn--mnchen-3ya.example")
+        );
+        assert_eq!(
+            domain("sftp://m%C3%BCnchen.example/datei.pdf").as_deref(),
+            Some("xn--mnchen-3ya.example")
+        );
+        assert_eq!(
+            domain("https://CDN.Example.com/a.bin").as_deref(),
+            Some("cdn.example.com")
+        );
+        assert_eq!(domain("http://[::1]:8080/a.bin").as_deref(), Some("[::1]"));
+        assert_eq!(domain("data:text/plain,hi"), None);
+    }
 }
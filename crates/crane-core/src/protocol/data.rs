@@ -0,0 +1,298 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use base64::Engine;
+use tokio_util::sync::CancellationToken;
+
+use crate::bandwidth::BandwidthLimiter;
+use crate::metadata::mime::categorize_mime;
+use crate::types::{
+    CraneError, DownloadOptions, DownloadPhase, DownloadProgress, DownloadResult, UrlAnalysis,
+};
+
+use super::ProtocolHandler;
+
+/// Media type assumed when a data URL omits it (RFC 2397).
+const DEFAULT_MIME: &str = "text/plain";
+
+/// Decoded contents of a `data:` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataUrl {
+    /// Lowercased `type/subtype`, without parameters.
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl DataUrl {
+    /// Data URLs carry no name; derive `download.<ext>` from the media type.
+    pub fn filename(&self) -> String {
+        match extension_for_mime(&self.mime_type) {
+            Some(ext) => format!("download.{ext}"),
+            None => "download".to_string(),
+        }
+    }
+}
+
+/// Parse and decode a `data:[<mediatype>][;base64],<data>` URL.
+///
+/// - The media type defaults to `text/plain`; parameters such as
+///   `charset` are accepted and dropped.
+/// - The payload is percent-decoded, then base64-decoded when the
+///   `;base64` flag is present (whitespace and missing padding tolerated).
+pub fn parse_data_url(url: &str) -> Result<DataUrl, CraneError> {
+    let malformed = |reason: &str| CraneError::DataUri(reason.to_string());
+
+    let rest = url
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &url[5..])
+        .ok_or_else(|| malformed("missing data: scheme"))?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| malformed("missing ',' before payload"))?;
+
+    let mut params = header.split(';');
+    let media_type = params.next().unwrap_or("").trim();
+    let is_base64 = params.any(|p| p.trim().eq_ignore_ascii_case("base64"));
+
+    let mime_type = if media_type.is_empty() {
+        DEFAULT_MIME.to_string()
+    } else {
+        match media_type.split_once('/') {
+            Some((ty, sub)) if !ty.is_empty() && !sub.is_empty() => media_type.to_lowercase(),
+            _ => return Err(malformed(&format!("invalid media type '{media_type}'"))),
+        }
+    };
+
+    let decoded = urlencoding::decode_binary(payload.as_bytes()).into_owned();
+    let data = if is_base64 {
+        let compact: Vec<u8> = decoded
+            .into_iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(&compact)
+            .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(&compact))
+            .map_err(|e| malformed(&format!("invalid base64 payload: {e}")))?
+    } else {
+        decoded
+    };
+
+    Ok(DataUrl { mime_type, data })
+}
+
+/// File extension for common data URL media types.
+fn extension_for_mime(mime: &str) -> Option<&'static str> {
+    Some(match mime {
+        "text/plain" => "txt",
+        "text/html" => "html",
+        "text/css" => "css",
+        "text/csv" => "csv",
+        "text/javascript" | "application/javascript" => "js",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/octet-stream" => "bin",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        "audio/mpeg" => "mp3",
+        "audio/wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        _ => return None,
+    })
+}
+
+/// Handles `data:` URLs by decoding the payload in memory. No network
+/// request is ever made.
+pub struct DataHandler;
+
+#[async_trait]
+impl ProtocolHandler for DataHandler {
+    async fn analyze(&self, url: &str) -> Result<UrlAnalysis, CraneError> {
+        let parsed = parse_data_url(url)?;
+
+        Ok(UrlAnalysis {
+            url: url.to_string(),
+            filename: parsed.filename(),
+            total_size: Some(parsed.data.len() as u64),
+            category: categorize_mime(&parsed.mime_type),
+            mime_type: Some(parsed.mime_type),
+            resumable: false,
+            server: None,
+            etag: None,
+            last_modified: None,
+            accept_ranges: false,
+            http_version: None,
+            redirected_to: None,
+        })
+    }
+
+    async fn download(
+        &self,
+        download_id: &str,
+        url: &str,
+        save_path: &Path,
+        options: &DownloadOptions,
+        _resume_from: u64,
+        cancel_token: CancellationToken,
+        on_progress: Arc<dyn Fn(&DownloadProgress) + Send + Sync>,
+        _limiter: Option<Arc<BandwidthLimiter>>,
+    ) -> Result<DownloadResult, CraneError> {
+        // SAFETY: see FtpHandler::download — async_trait drops the `for<'a>`
+        // bound on the callback; the Arc is 'static so restoring it is sound.
+        let on_progress: Arc<dyn Fn(&DownloadProgress) + Send + Sync> =
+            unsafe { std::mem::transmute(on_progress) };
+
+        let start_time = Instant::now();
+        let parsed = parse_data_url(url)?;
+        if cancel_token.is_cancelled() {
+            return Err(CraneError::DataUri("Download cancelled".to_string()));
+        }
+
+        if let Some(parent) = save_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(save_path, &parsed.data).await?;
+        crate::metadata::apply_file_mode(save_path, options.file_mode).await?;
+
+        let size = parsed.data.len() as u64;
+        on_progress(&DownloadProgress {
+            download_id: download_id.to_string(),
+            downloaded_size: size,
+            total_size: Some(size),
+            speed: 0.0,
+            instant_speed: 0.0,
+            eta_seconds: Some(0),
+            connections: vec![],
+            phase: DownloadPhase::Downloading,
+        });
+
+        Ok(DownloadResult {
+            downloaded_bytes: size,
+            elapsed_ms: start_time.elapsed().as_millis() as u64,
+            final_path: save_path.to_path_buf(),
+            hash_verified: None,
+        })
+    }
+
+    fn supports_multi_connection(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// 1x1 transparent PNG.
+    const PNG_BASE64: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_parse_base64_png() {
+        let parsed = parse_data_url(&format!("data:image/png;base64,{PNG_BASE64}")).unwrap();
+        assert_eq!(parsed.mime_type, "image/png");
+        assert_eq!(parsed.data.len(), 70);
+        assert_eq!(&parsed.data[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_parse_percent_encoded_text() {
+        let parsed = parse_data_url("data:text/plain;charset=utf-8,Hello%2C%20World%21").unwrap();
+        assert_eq!(parsed.mime_type, "text/plain");
+        assert_eq!(parsed.data, b"Hello, World!");
+
+        let parsed = parse_data_url("data:,plain").unwrap();
+        assert_eq!(parsed.mime_type, "text/plain");
+        assert_eq!(parsed.data, b"plain");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for url in [
+            "data:text/plain;base64",
+            "data:text/plain;base64,@@@@",
+            "data:notamime,abc",
+            "https://example.com/a,b",
+        ] {
+            match parse_data_url(url) {
+                Err(CraneError::DataUri(_)) => {}
+                other => panic!("{url}: expected DataUri error, got {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_analyze_uses_mime_for_name_and_category() {
+        let analysis = DataHandler
+            .analyze(&format!("data:image/png;base64,{PNG_BASE64}"))
+            .await
+            .unwrap();
+        assert_eq!(analysis.filename, "download.png");
+        assert_eq!(analysis.total_size, Some(70));
+        assert_eq!(analysis.mime_type.as_deref(), Some("image/png"));
+        assert_eq!(analysis.category, crate::types::FileCategory::Images);
+        assert!(!analysis.resumable);
+    }
+
+    #[tokio::test]
+    async fn test_download_writes_decoded_payload() {
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("nested").join("hello.txt");
+        let reported = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let reported_clone = reported.clone();
+
+        let result = DataHandler
+            .download(
+                "dl-1",
+                "data:text/plain,line%201%0Aline%202",
+                &save,
+                &DownloadOptions::default(),
+                0,
+                CancellationToken::new(),
+                Arc::new(move |p: &DownloadProgress| {
+                    assert_eq!(p.download_id, "dl-1");
+                    reported_clone.store(p.downloaded_size, std::sync::atomic::Ordering::SeqCst);
+                }),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&save).unwrap(), b"line 1\nline 2");
+        assert_eq!(result.downloaded_bytes, 13);
+        assert_eq!(result.final_path, save);
+        assert_eq!(reported.load(std::sync::atomic::Ordering::SeqCst), 13);
+    }
+
+    #[tokio::test]
+    async fn test_start_download_dispatches_data_url() {
+        let tmp = TempDir::new().unwrap();
+        let save = tmp.path().join("pixel.png");
+        let handle = crate::engine::multi::start_download(
+            "data-1",
+            &format!("data:image/png;base64,{PNG_BASE64}"),
+            &save,
+            &DownloadOptions::default(),
+            |_: &DownloadProgress| {},
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let result = handle.wait().await.unwrap();
+        assert_eq!(result.downloaded_bytes, 70);
+        let written = std::fs::read(&save).unwrap();
+        assert_eq!(written.len(), 70);
+        assert_eq!(&written[..4], b"\x89PNG");
+    }
+}
@@ -8,7 +8,10 @@ use crate::bandwidth::BandwidthLimiter;
 use crate::metadata::mime::categorize_extension;
 use crate::metadata::sanitize_filename;
 use crate::network::is_public_host;
-use crate::types::{CraneError, DownloadOptions, DownloadProgress, DownloadResult, UrlAnalysis};
+use crate::types::{
+    CraneError, DownloadOptions, DownloadPhase, DownloadProgress, DownloadResult, FtpMode,
+    UrlAnalysis,
+};
 
 use super::ProtocolHandler;
 
@@ -113,6 +116,63 @@ pub(crate) fn parse_ftp_url(url: &str) -> Result<FtpUrlParts, CraneError> {
 /// Connection timeout for FTP/FTPS connections.
 const FTP_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
+/// suppaftp's data connection mode for `mode`.
+fn data_mode(mode: FtpMode) -> suppaftp::Mode {
+    match mode {
+        FtpMode::Passive => suppaftp::Mode::Passive,
+        FtpMode::Active => suppaftp::Mode::Active,
+    }
+}
+
+/// Directory holding `path`, for listing it.
+fn parent_dir(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+/// Size of the file `filename` in a `LIST` or `MLSD` reply. Servers list a
+/// file path either by name or by full path; directories never match.
+fn size_from_listing(lines: &[String], filename: &str) -> Option<u64> {
+    use suppaftp::list::File;
+
+    lines
+        .iter()
+        // The MLSx parser accepts almost any line, so it goes last
+        .filter_map(|line| {
+            File::try_from(line.as_str())
+                .or_else(|_| File::from_mlsx_line(line))
+                .ok()
+        })
+        .find(|entry| entry.is_file() && entry.name().rsplit('/').next() == Some(filename))
+        .map(|entry| entry.size() as u64)
+}
+
+/// File size via `SIZE`, falling back to a `LIST` of the file and then an
+/// `MLSD` of its directory for servers that don't support `SIZE`. A macro
+/// for the same reason as `ftp_download_stream!`.
+macro_rules! ftp_file_size {
+    ($ftp:ident, $parts:expr) => {{
+        match $ftp.size(&$parts.path).await {
+            Ok(size) => Some(size as u64),
+            Err(_) => {
+                let listed = match $ftp.list(Some(&$parts.path)).await {
+                    Ok(lines) => size_from_listing(&lines, &$parts.filename),
+                    Err(_) => None,
+                };
+                match listed {
+                    Some(size) => Some(size),
+                    None => match $ftp.mlsd(Some(parent_dir(&$parts.path))).await {
+                        Ok(lines) => size_from_listing(&lines, &$parts.filename),
+                        Err(_) => None,
+                    },
+                }
+            }
+        }
+    }};
+}
+
 /// Shared streaming download body for FTP connections.
 ///
 /// Both `AsyncFtpStream` and `AsyncRustlsFtpStream` are type aliases for
@@ -124,13 +184,13 @@ const FTP_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(
 /// This macro expects `$ftp` to already be connected, logged in, and in
 /// binary mode. It handles resume, streaming, progress, finalize, and rename.
 macro_rules! ftp_download_stream {
-    ($ftp:ident, $parts:expr, $save_path:expr, $resume_from:expr,
+    ($ftp:ident, $download_id:expr, $parts:expr, $save_path:expr, $resume_from:expr,
      $cancel_token:expr, $on_progress:expr, $limiter:expr) => {{
         use futures_util::io::AsyncReadExt;
         use tokio::io::AsyncWriteExt;
 
         let start_time = std::time::Instant::now();
-        let total_size = $ftp.size(&$parts.path).await.ok().map(|s| s as u64);
+        let total_size = ftp_file_size!($ftp, $parts);
 
         // Resume from offset if requested
         let mut downloaded: u64 = $resume_from;
@@ -185,13 +245,13 @@ macro_rules! ftp_download_stream {
                 break;
             }
 
-            file.write_all(&buf[..n]).await?;
-
             // Bandwidth limiting
             if let Some(ref lim) = $limiter {
                 lim.acquire(n as u64).await;
             }
 
+            file.write_all(&buf[..n]).await?;
+
             downloaded += n as u64;
 
             // Report progress
@@ -213,12 +273,14 @@ macro_rules! ftp_download_stream {
 
             {
                 let progress = DownloadProgress {
-                    download_id: String::new(), // Filled by caller
+                    download_id: $download_id.to_string(),
                     downloaded_size: downloaded,
                     total_size,
                     speed,
+                    instant_speed: speed,
                     eta_seconds: eta,
                     connections: vec![],
+                    phase: DownloadPhase::Downloading,
                 };
                 $on_progress(&progress);
             }
@@ -251,7 +313,17 @@ pub struct FtpHandler;
 #[async_trait]
 impl ProtocolHandler for FtpHandler {
     async fn analyze(&self, url: &str) -> Result<UrlAnalysis, CraneError> {
+        self.analyze_with_options(url, &DownloadOptions::default())
+            .await
+    }
+
+    async fn analyze_with_options(
+        &self,
+        url: &str,
+        options: &DownloadOptions,
+    ) -> Result<UrlAnalysis, CraneError> {
         let parts = parse_ftp_url(url)?;
+        let mode = options.ftp_mode.unwrap_or_default();
 
         // SSRF protection: block private/internal network addresses
         if !is_public_host(&parts.host) {
@@ -262,9 +334,9 @@ impl ProtocolHandler for FtpHandler {
 
         // Connect and analyze based on TLS requirement
         let (total_size, resumable) = if parts.use_tls {
-            analyze_ftps(&addr, &parts).await?
+            analyze_ftps(&addr, &parts, mode).await?
         } else {
-            analyze_ftp(&addr, &parts).await?
+            analyze_ftp(&addr, &parts, mode).await?
         };
 
         let filename = sanitize_filename(&parts.filename);
@@ -278,14 +350,20 @@ impl ProtocolHandler for FtpHandler {
             resumable,
             category,
             server: None,
+            etag: None,
+            last_modified: None,
+            accept_ranges: false,
+            http_version: None,
+            redirected_to: None,
         })
     }
 
     async fn download(
         &self,
+        download_id: &str,
         url: &str,
         save_path: &Path,
-        _options: &DownloadOptions,
+        options: &DownloadOptions,
         resume_from: u64,
         cancel_token: CancellationToken,
         on_progress: Arc<dyn Fn(&DownloadProgress) + Send + Sync>,
@@ -300,6 +378,7 @@ impl ProtocolHandler for FtpHandler {
             unsafe { std::mem::transmute(on_progress) };
 
         let parts = parse_ftp_url(url)?;
+        let mode = data_mode(options.ftp_mode.unwrap_or_default());
 
         if !is_public_host(&parts.host) {
             return Err(CraneError::PrivateNetwork(parts.host.clone()));
@@ -348,6 +427,7 @@ impl ProtocolHandler for FtpHandler {
                     ftp.transfer_type(FileType::Binary)
                         .await
                         .map_err(|e| CraneError::Ftp(format!("failed to set binary mode: {e}")))?;
+                    ftp.set_mode(mode);
                     Ok::<_, CraneError>(ftp)
                 }
                 .await;
@@ -356,6 +436,7 @@ impl ProtocolHandler for FtpHandler {
                     Ok(mut ftp) => {
                         ftp_download_stream!(
                             ftp,
+                            download_id,
                             parts,
                             save_path,
                             resume_from,
@@ -381,6 +462,7 @@ impl ProtocolHandler for FtpHandler {
                     ftp.transfer_type(FileType::Binary)
                         .await
                         .map_err(|e| CraneError::Ftp(format!("failed to set binary mode: {e}")))?;
+                    ftp.set_mode(mode);
                     Ok::<_, CraneError>(ftp)
                 }
                 .await;
@@ -389,6 +471,7 @@ impl ProtocolHandler for FtpHandler {
                     Ok(mut ftp) => {
                         ftp_download_stream!(
                             ftp,
+                            download_id,
                             parts,
                             save_path,
                             resume_from,
@@ -402,7 +485,10 @@ impl ProtocolHandler for FtpHandler {
             };
 
             match result {
-                Ok(r) => return Ok(r),
+                Ok(r) => {
+                    crate::metadata::apply_file_mode(save_path, options.file_mode).await?;
+                    return Ok(r);
+                }
                 Err(e) => {
                     eprintln!(
                         "FTP download attempt {}/{} failed: {e}",
@@ -432,7 +518,11 @@ impl ProtocolHandler for FtpHandler {
 }
 
 /// Analyze an FTP (non-TLS) connection: get file size and check resumability.
-async fn analyze_ftp(addr: &str, parts: &FtpUrlParts) -> Result<(Option<u64>, bool), CraneError> {
+async fn analyze_ftp(
+    addr: &str,
+    parts: &FtpUrlParts,
+    mode: FtpMode,
+) -> Result<(Option<u64>, bool), CraneError> {
     use suppaftp::types::FileType;
     use suppaftp::AsyncFtpStream;
 
@@ -448,15 +538,12 @@ async fn analyze_ftp(addr: &str, parts: &FtpUrlParts) -> Result<(Option<u64>, bo
     ftp.transfer_type(FileType::Binary)
         .await
         .map_err(|e| CraneError::Ftp(format!("failed to set binary mode: {e}")))?;
+    ftp.set_mode(data_mode(mode));
 
-    // Try to get file size (SIZE command)
-    let total_size = match ftp.size(&parts.path).await {
-        Ok(size) => Some(size as u64),
-        Err(_) => None,
-    };
+    let total_size = ftp_file_size!(ftp, parts);
 
-    // Check resumability via REST 0
-    let resumable = ftp.resume_transfer(0).await.is_ok();
+    // Resuming needs REST and a known size to finish against
+    let resumable = total_size.is_some() && ftp.resume_transfer(0).await.is_ok();
 
     let _ = ftp.quit().await;
 
@@ -464,7 +551,11 @@ async fn analyze_ftp(addr: &str, parts: &FtpUrlParts) -> Result<(Option<u64>, bo
 }
 
 /// Analyze an FTPS (TLS) connection: get file size and check resumability.
-async fn analyze_ftps(addr: &str, parts: &FtpUrlParts) -> Result<(Option<u64>, bool), CraneError> {
+async fn analyze_ftps(
+    addr: &str,
+    parts: &FtpUrlParts,
+    mode: FtpMode,
+) -> Result<(Option<u64>, bool), CraneError> {
     use suppaftp::types::FileType;
     use suppaftp::{AsyncRustlsConnector, AsyncRustlsFtpStream};
 
@@ -488,15 +579,12 @@ async fn analyze_ftps(addr: &str, parts: &FtpUrlParts) -> Result<(Option<u64>, b
     ftp.transfer_type(FileType::Binary)
         .await
         .map_err(|e| CraneError::Ftp(format!("failed to set binary mode: {e}")))?;
+    ftp.set_mode(data_mode(mode));
 
-    // Try to get file size (SIZE command)
-    let total_size = match ftp.size(&parts.path).await {
-        Ok(size) => Some(size as u64),
-        Err(_) => None,
-    };
+    let total_size = ftp_file_size!(ftp, parts);
 
-    // Check resumability via REST 0
-    let resumable = ftp.resume_transfer(0).await.is_ok();
+    // Resuming needs REST and a known size to finish against
+    let resumable = total_size.is_some() && ftp.resume_transfer(0).await.is_ok();
 
     let _ = ftp.quit().await;
 
@@ -578,6 +666,119 @@ mod tests {
         assert!(err.contains("unsupported scheme"));
     }
 
+    #[test]
+    fn test_size_from_listing() {
+        let lines = |ls: &[&str]| ls.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let posix = lines(&["-rw-r--r--    1 ftp      ftp          4096 Jan 01 12:00 file.bin"]);
+        assert_eq!(size_from_listing(&posix, "file.bin"), Some(4096));
+        assert_eq!(size_from_listing(&posix, "other.bin"), None);
+
+        let full_path =
+            lines(&["-rw-r--r--    1 ftp      ftp           512 Jan 01 12:00 /pub/file.bin"]);
+        assert_eq!(size_from_listing(&full_path, "file.bin"), Some(512));
+
+        let mlsd = lines(&[
+            "type=dir;modify=20260101120000; file.bin",
+            "type=file;size=2048;modify=20260101120000; file.bin",
+        ]);
+        assert_eq!(size_from_listing(&mlsd, "file.bin"), Some(2048));
+
+        assert_eq!(parent_dir("/pub/file.bin"), "/pub");
+        assert_eq!(parent_dir("/file.bin"), "/");
+    }
+
+    /// Scripted FTP server that refuses `SIZE` but lists `file.bin` as
+    /// 4096 bytes. Records the commands it receives.
+    #[cfg(feature = "ftp-tests")]
+    async fn spawn_ftp_without_size() -> (
+        std::net::SocketAddr,
+        std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    ) {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            let (conn, _) = listener.accept().await?;
+            let (read, mut write) = conn.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 ready\r\n").await?;
+            let mut passive: Option<TcpListener> = None;
+            let mut active: Option<std::net::SocketAddr> = None;
+            while let Some(line) = lines.next_line().await? {
+                let (cmd, arg) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+                log.lock().unwrap().push(cmd.to_string());
+                let reply = match cmd {
+                    "USER" => "331 password please".to_string(),
+                    "PASS" => "230 logged in".to_string(),
+                    "TYPE" => "200 binary".to_string(),
+                    "REST" => "350 restarting".to_string(),
+                    "PASV" => {
+                        let data = TcpListener::bind("127.0.0.1:0").await?;
+                        let port = data.local_addr()?.port();
+                        passive = Some(data);
+                        format!(
+                            "227 Entering Passive Mode (127,0,0,1,{},{})",
+                            port / 256,
+                            port % 256
+                        )
+                    }
+                    "PORT" => {
+                        let n: Vec<u16> = arg.split(',').map(|v| v.parse().unwrap()).collect();
+                        active = Some(std::net::SocketAddr::from((
+                            [n[0] as u8, n[1] as u8, n[2] as u8, n[3] as u8],
+                            n[4] * 256 + n[5],
+                        )));
+                        "200 port ok".to_string()
+                    }
+                    "LIST" => {
+                        write.write_all(b"150 listing\r\n").await?;
+                        let mut data = match (passive.take(), active.take()) {
+                            (Some(listener), _) => listener.accept().await?.0,
+                            (None, Some(target)) => TcpStream::connect(target).await?,
+                            (None, None) => unreachable!("LIST without PASV or PORT"),
+                        };
+                        data.write_all(
+                            b"-rw-r--r--    1 ftp      ftp          4096 Jan 01 12:00 file.bin\r\n",
+                        )
+                        .await?;
+                        data.shutdown().await?;
+                        "226 done".to_string()
+                    }
+                    "QUIT" => {
+                        write.write_all(b"221 bye\r\n").await?;
+                        break;
+                    }
+                    _ => "502 not implemented".to_string(),
+                };
+                write.write_all(format!("{reply}\r\n").as_bytes()).await?;
+            }
+            Ok::<_, std::io::Error>(())
+        });
+        (addr, seen)
+    }
+
+    #[cfg(feature = "ftp-tests")]
+    #[tokio::test]
+    async fn test_analyze_falls_back_to_list_when_size_refused() {
+        for (mode, data_cmd) in [(FtpMode::Passive, "PASV"), (FtpMode::Active, "PORT")] {
+            let (addr, seen) = spawn_ftp_without_size().await;
+            let parts = parse_ftp_url(&format!("ftp://{addr}/pub/file.bin")).unwrap();
+
+            let (total_size, resumable) =
+                analyze_ftp(&addr.to_string(), &parts, mode).await.unwrap();
+
+            assert_eq!(total_size, Some(4096));
+            assert!(resumable);
+            let seen = seen.lock().unwrap();
+            assert!(seen.iter().any(|c| c == "SIZE"));
+            assert!(seen.iter().any(|c| c == data_cmd), "{mode:?}: {seen:?}");
+        }
+    }
+
     #[tokio::test]
     async fn test_analyze_connection_refused() {
         let handler = FtpHandler;
@@ -603,6 +804,7 @@ mod tests {
         let handler = FtpHandler;
         let result = handler
             .download(
+                "",
                 "ftp://10.0.0.1/file.txt",
                 Path::new("/tmp/test.txt"),
                 &DownloadOptions::default(),
@@ -641,4 +843,32 @@ mod tests {
         let handler = crate::protocol::handler_for_url("gopher://example.com/file.txt");
         assert!(handler.is_err());
     }
+
+    #[test]
+    fn test_handler_for_url_magnet_is_torrent_error() {
+        let result = crate::protocol::handler_for_url(
+            "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a&dn=ubuntu.iso",
+        );
+        match result {
+            Err(CraneError::TorrentUnsupported(_)) => {}
+            Err(e) => panic!("expected TorrentUnsupported, got {e:?}"),
+            Ok(_) => panic!("magnet link should be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_handler_for_url_torrent_file_is_torrent_error() {
+        for url in [
+            "https://releases.example.com/ubuntu-24.04.iso.torrent",
+            "http://example.com/files/Movie.TORRENT?dl=1",
+        ] {
+            match crate::protocol::handler_for_url(url) {
+                Err(CraneError::TorrentUnsupported(_)) => {}
+                Err(e) => panic!("{url}: expected TorrentUnsupported, got {e:?}"),
+                Ok(_) => panic!("{url}: .torrent URL should be rejected"),
+            }
+        }
+        // Only the file name counts, not the rest of the path
+        assert!(crate::protocol::handler_for_url("https://example.com/torrent/file.iso").is_ok());
+    }
 }
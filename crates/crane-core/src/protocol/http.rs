@@ -16,8 +16,18 @@ impl ProtocolHandler for HttpHandler {
         crate::metadata::analyzer::analyze_url(url).await
     }
 
+    async fn analyze_with_options(
+        &self,
+        url: &str,
+        options: &DownloadOptions,
+    ) -> Result<UrlAnalysis, CraneError> {
+        let analyze_options = crate::metadata::analyzer::AnalyzeOptions::from(options);
+        crate::metadata::analyzer::analyze_url_with_options(url, Some(&analyze_options)).await
+    }
+
     async fn download(
         &self,
+        _download_id: &str,
         _url: &str,
         _save_path: &Path,
         _options: &DownloadOptions,
@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+
+use crate::types::CraneError;
+
+/// Public gateway used when none is configured.
+pub const DEFAULT_GATEWAY: &str = "https://ipfs.io";
+
+const BASE32_LOWER: &str = "abcdefghijklmnopqrstuvwxyz234567";
+const BASE36_LOWER: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+const BASE58_BTC: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Whether `url` uses the `ipfs://` scheme.
+pub fn is_ipfs_url(url: &str) -> bool {
+    url.get(..7)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("ipfs://"))
+}
+
+/// Syntactic CID check: CIDv0 (`Qm…`, 46 base58 chars) or a CIDv1 in
+/// base32 (`b…`), base36 (`k…`) or base58btc (`z…`) multibase.
+pub fn is_valid_cid(cid: &str) -> bool {
+    let all_in = |s: &str, alphabet: &str| s.chars().all(|c| alphabet.contains(c));
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        return all_in(cid, BASE58_BTC);
+    }
+    let Some(prefix) = cid.chars().next() else {
+        return false;
+    };
+    let body = &cid[1..];
+    body.len() >= 8
+        && match prefix {
+            'b' => all_in(body, BASE32_LOWER),
+            'k' => all_in(body, BASE36_LOWER),
+            'z' => all_in(body, BASE58_BTC),
+            _ => false,
+        }
+}
+
+/// Rewrite `ipfs://<cid>[/<path>]` to `<gateway>/ipfs/<cid>[/<path>]`.
+///
+/// Query strings are kept; fragments are dropped. `gateway` must be an
+/// http(s) base URL and may end with a slash.
+pub fn gateway_url(url: &str, gateway: &str) -> Result<String, CraneError> {
+    if !is_ipfs_url(url) {
+        return Err(CraneError::Ipfs(format!("not an ipfs:// URL: {url}")));
+    }
+    let rest = &url[7..];
+    let rest = rest.split_once('#').map_or(rest, |(before, _)| before);
+    let split = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (cid, tail) = rest.split_at(split);
+    if !is_valid_cid(cid) {
+        return Err(CraneError::Ipfs(format!("invalid CID '{cid}'")));
+    }
+
+    let base = gateway.trim().trim_end_matches('/');
+    match url::Url::parse(base) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        _ => return Err(CraneError::Ipfs(format!("invalid gateway '{gateway}'"))),
+    }
+    Ok(format!("{base}/ipfs/{cid}{tail}"))
+}
+
+/// Resolve `url` for download: `ipfs://` links go through `gateway`
+/// (blank or `None` uses [`DEFAULT_GATEWAY`]); anything else is returned
+/// unchanged.
+pub fn resolve_url<'a>(url: &'a str, gateway: Option<&str>) -> Result<Cow<'a, str>, CraneError> {
+    if !is_ipfs_url(url) {
+        return Ok(Cow::Borrowed(url));
+    }
+    let gateway = gateway
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .unwrap_or(DEFAULT_GATEWAY);
+    gateway_url(url, gateway).map(Cow::Owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID_V0: &str = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+    const CID_V1: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+    #[test]
+    fn test_cid_only_uses_default_gateway() {
+        let url = format!("ipfs://{CID_V1}");
+        assert_eq!(
+            resolve_url(&url, None).unwrap(),
+            format!("https://ipfs.io/ipfs/{CID_V1}")
+        );
+    }
+
+    #[test]
+    fn test_cid_with_path() {
+        let url = format!("ipfs://{CID_V0}/docs/readme.txt?download=1#top");
+        assert_eq!(
+            resolve_url(&url, None).unwrap(),
+            format!("https://ipfs.io/ipfs/{CID_V0}/docs/readme.txt?download=1")
+        );
+    }
+
+    #[test]
+    fn test_custom_gateway() {
+        let url = format!("ipfs://{CID_V1}/a.bin");
+        assert_eq!(
+            resolve_url(&url, Some("https://gw.example.org/")).unwrap(),
+            format!("https://gw.example.org/ipfs/{CID_V1}/a.bin")
+        );
+        // Blank gateway falls back to the default
+        assert_eq!(
+            resolve_url(&url, Some("  ")).unwrap(),
+            format!("https://ipfs.io/ipfs/{CID_V1}/a.bin")
+        );
+        match gateway_url(&url, "ftp://gw.example.org") {
+            Err(CraneError::Ipfs(_)) => {}
+            other => panic!("expected Ipfs error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_cid_rejected() {
+        for cid in ["", "not-a-cid", "Qm123", "bafy!!!!!!!!", "BAFYBEIGDYRZT5SF"] {
+            let url = format!("ipfs://{cid}/file.txt");
+            match resolve_url(&url, None) {
+                Err(CraneError::Ipfs(_)) => {}
+                other => panic!("{url}: expected Ipfs error, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_ipfs_urls_pass_through() {
+        let url = "https://example.com/file.bin";
+        assert!(matches!(resolve_url(url, None).unwrap(), Cow::Borrowed(u) if u == url));
+    }
+}
@@ -1,5 +1,8 @@
+pub mod data;
 pub mod ftp;
 pub mod http;
+pub mod ipfs;
+pub mod sftp;
 
 use std::path::Path;
 use std::sync::Arc;
@@ -14,8 +17,19 @@ use crate::types::{CraneError, DownloadOptions, DownloadProgress, DownloadResult
 pub trait ProtocolHandler: Send + Sync {
     async fn analyze(&self, url: &str) -> Result<UrlAnalysis, CraneError>;
 
+    /// Analyze with per-download options available (e.g. credentials).
+    /// Protocols that don't need them fall back to [`ProtocolHandler::analyze`].
+    async fn analyze_with_options(
+        &self,
+        url: &str,
+        _options: &DownloadOptions,
+    ) -> Result<UrlAnalysis, CraneError> {
+        self.analyze(url).await
+    }
+
     async fn download(
         &self,
+        download_id: &str,
         url: &str,
         save_path: &Path,
         options: &DownloadOptions,
@@ -28,11 +42,30 @@ pub trait ProtocolHandler: Send + Sync {
     fn supports_multi_connection(&self) -> bool;
 }
 
+/// Reject BitTorrent links (`magnet:` URIs and `.torrent` files) with
+/// [`CraneError::TorrentUnsupported`] rather than a generic scheme error.
+pub fn reject_torrent(url: &url::Url) -> Result<(), CraneError> {
+    if url.scheme() == "magnet" {
+        return Err(CraneError::TorrentUnsupported("magnet link".to_string()));
+    }
+    let is_torrent_file = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .is_some_and(|name| name.to_ascii_lowercase().ends_with(".torrent"));
+    if is_torrent_file {
+        return Err(CraneError::TorrentUnsupported(".torrent file".to_string()));
+    }
+    Ok(())
+}
+
 pub fn handler_for_url(url: &str) -> Result<Box<dyn ProtocolHandler>, CraneError> {
     let parsed = url::Url::parse(url)?;
+    reject_torrent(&parsed)?;
     match parsed.scheme() {
         "http" | "https" => Ok(Box::new(http::HttpHandler)),
         "ftp" | "ftps" => Ok(Box::new(ftp::FtpHandler)),
+        "sftp" => Ok(Box::new(sftp::SftpHandler)),
+        "data" => Ok(Box::new(data::DataHandler)),
         scheme => Err(CraneError::UnsupportedScheme(scheme.to_string())),
     }
 }
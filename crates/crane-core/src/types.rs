@@ -6,7 +6,9 @@ use serde::{Deserialize, Serialize};
 //                │              │
 //                │              ├→ paused → downloading (resume)
 //                │              │
-//                │              └→ failed → downloading (retry)
+//                │              ├→ failed → downloading (retry)
+//                │              │
+//                │              └→ cancelled → downloading (retry)
 //                │
 //                └→ queued → downloading (when slot opens)
 
@@ -19,6 +21,7 @@ pub enum DownloadStatus {
     Paused,
     Completed,
     Failed,
+    Cancelled,
     Queued,
 }
 
@@ -52,6 +55,7 @@ impl DownloadStatus {
             Self::Paused => "paused",
             Self::Completed => "completed",
             Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
             Self::Queued => "queued",
         }
     }
@@ -64,6 +68,7 @@ impl DownloadStatus {
             "paused" => Ok(Self::Paused),
             "completed" => Ok(Self::Completed),
             "failed" => Ok(Self::Failed),
+            "cancelled" => Ok(Self::Cancelled),
             "queued" => Ok(Self::Queued),
             _ => Err(CraneError::Database(format!(
                 "Unknown download status: {s}"
@@ -99,6 +104,37 @@ impl FileCategory {
     }
 }
 
+/// Scheduling tier for queued downloads: every `High` download starts before
+/// any `Normal` one, and `Normal` before `Low`. `queue_position` orders
+/// downloads within a tier.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+
+    pub fn from_db_str(s: &str) -> Result<Self, CraneError> {
+        match s {
+            "high" => Ok(Self::High),
+            "normal" => Ok(Self::Normal),
+            "low" => Ok(Self::Low),
+            _ => Err(CraneError::Database(format!("Unknown priority: {s}"))),
+        }
+    }
+}
+
 impl ConnectionStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -132,6 +168,22 @@ pub struct UrlAnalysis {
     pub resumable: bool,
     pub category: FileCategory,
     pub server: Option<String>,
+    /// `ETag` of the analyzed response, quotes included.
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// `Last-Modified` of the analyzed response, as sent.
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// Whether the server advertised `Accept-Ranges: bytes`.
+    #[serde(default)]
+    pub accept_ranges: bool,
+    /// HTTP version of the analyzed response (`"HTTP/1.1"`, `"HTTP/2.0"`);
+    /// `None` for other protocols.
+    #[serde(default)]
+    pub http_version: Option<String>,
+    /// Where redirects led, when the analyzed URL was redirected.
+    #[serde(default)]
+    pub redirected_to: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -161,6 +213,34 @@ pub struct Download {
     pub started_at: Option<String>,
     pub completed_at: Option<String>,
     pub updated_at: String,
+    /// RFC 3339 time before which a pending download must not start.
+    pub scheduled_at: Option<String>,
+    /// Where `url` ended up after redirects, when that differs from `url`.
+    pub final_url: Option<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Validators the partial data was fetched against; resumed ranges send
+    /// them as `If-Range`.
+    #[serde(default)]
+    pub etag: Option<String>,
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// Bytes/sec over the final run, recorded when the download completes.
+    #[serde(default)]
+    pub average_speed: Option<f64>,
+    /// Fastest recorded speed in bytes/sec, at least `average_speed`.
+    #[serde(default)]
+    pub peak_speed: Option<f64>,
+    /// `Auth::scheme` of the credentials the download was added with, so a
+    /// restart knows they must be supplied again.
+    #[serde(default)]
+    pub auth_scheme: Option<String>,
+    /// SHA-256 of the finished file, recorded when content dedup is on.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// Fallback mirrors not tried yet, as a JSON array of URLs.
+    #[serde(default)]
+    pub mirrors: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -172,14 +252,32 @@ pub struct ConnectionInfo {
     pub status: ConnectionStatus,
 }
 
+/// What a download is doing once its bytes are on disk.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadPhase {
+    #[default]
+    Downloading,
+    /// Concatenating chunk files; `downloaded_size` counts bytes merged.
+    Merging,
+    /// Hashing the finished file against an expected digest.
+    Verifying,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DownloadProgress {
     pub download_id: String,
     pub downloaded_size: u64,
     pub total_size: Option<u64>,
+    /// Smoothed bytes/sec; see `DownloadOptions::speed_smoothing`.
     pub speed: f64,
+    /// Bytes/sec over the latest sample only.
+    #[serde(default)]
+    pub instant_speed: f64,
     pub eta_seconds: Option<u64>,
     pub connections: Vec<ConnectionProgress>,
+    #[serde(default)]
+    pub phase: DownloadPhase,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -196,17 +294,239 @@ pub struct ExpectedHash {
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// What to do when a download's save path already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionStrategy {
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Save as `name (1).ext`, `name (2).ext`, … instead.
+    Rename,
+    /// Don't download it again; the add returns `CraneError::SkippedExisting`.
+    Skip,
+    /// Refuse with `CraneError::FileExists`.
+    Error,
+}
+
+/// How an FTP/FTPS server is asked to open data connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FtpMode {
+    /// The client connects to a port the server opens (`PASV`).
+    #[default]
+    Passive,
+    /// The server connects back to the client (`PORT`), for legacy servers
+    /// that don't support passive mode.
+    Active,
+}
+
+/// How a finished download whose content matches an earlier one is
+/// replaced to save space.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// Keep every copy; no hash is computed.
+    #[default]
+    Off,
+    /// Replace the copy with a hard link to the earlier file.
+    Hardlink,
+    /// Replace the copy with a symbolic link to the earlier file.
+    Symlink,
+}
+
+/// HTTP credentials sent as the `Authorization` header.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+    Basic { user: String, pass: String },
+    Bearer { token: String },
+}
+
+impl Auth {
+    /// Scheme name recorded with the download; never the secret itself.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Auth::Basic { .. } => "basic",
+            Auth::Bearer { .. } => "bearer",
+        }
+    }
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Auth::Basic { user, .. } => f
+                .debug_struct("Basic")
+                .field("user", user)
+                .field("pass", &"[REDACTED]")
+                .finish(),
+            Auth::Bearer { .. } => f
+                .debug_struct("Bearer")
+                .field("token", &"[REDACTED]")
+                .finish(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct DownloadOptions {
     pub save_path: Option<String>,
     pub filename: Option<String>,
     pub connections: Option<u32>,
     pub category: Option<FileCategory>,
+    /// Queue tier; `None` is `Priority::Normal`.
+    pub priority: Option<Priority>,
     pub referrer: Option<String>,
     pub cookies: Option<String>,
     pub user_agent: Option<String>,
     pub headers: Option<std::collections::HashMap<String, String>>,
+    /// HTTP Basic or Bearer credentials. Not persisted; only the scheme is.
+    #[serde(default)]
+    pub auth: Option<Auth>,
     pub expected_hash: Option<ExpectedHash>,
+    /// Private key file for SFTP authentication.
+    pub ssh_key_path: Option<String>,
+    /// Password for SFTP authentication (overrides any password in the URL).
+    pub password: Option<String>,
+    /// Trust and record an SFTP host key that isn't in `known_hosts` yet
+    /// (OpenSSH `accept-new`). A changed key is still rejected.
+    #[serde(default)]
+    pub ssh_accept_new_host_keys: bool,
+    /// FTP data connection mode; `None` uses passive.
+    pub ftp_mode: Option<FtpMode>,
+    /// Proxy URL for this download; `Some("")` forces a direct connection.
+    pub proxy: Option<String>,
+    /// Hosts that bypass `proxy`.
+    pub no_proxy: Option<Vec<String>>,
+    /// Verify against `<url>.sha256` (or `.md5`) when no `expected_hash` is given.
+    #[serde(default)]
+    pub verify_from_sidecar: bool,
+    /// Retry/backoff for transient failures; `None` uses the engine default.
+    pub retry_policy: Option<crate::engine::retry::RetryPolicy>,
+    /// Stage chunk files here instead of next to the destination.
+    pub temp_dir: Option<std::path::PathBuf>,
+    /// HTTP gateway base for `ipfs://` URLs; `None` uses the public default.
+    pub ipfs_gateway: Option<String>,
+    /// Connect timeout; `None` uses the engine default, `Some(0)` disables it.
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest wait for the next read before a stalled connection fails
+    /// (and is retried); `None` uses the engine default, `Some(0)` disables it.
+    pub read_timeout_ms: Option<u64>,
+    /// Write multi-connection chunks in place into a pre-allocated file
+    /// instead of merging per-chunk files afterwards.
+    #[serde(default)]
+    pub segmented_writes: bool,
+    /// Hash an in-place download with parallel reads of disjoint ranges
+    /// when verifying it. Only applies with `segmented_writes`.
+    #[serde(default)]
+    pub parallel_verify: bool,
+    /// Per-connection progress saved by an earlier run; lets a segmented
+    /// download resume after a restart.
+    #[serde(skip)]
+    pub resume_connections: Option<Vec<ConnectionInfo>>,
+    /// `ETag` and `Last-Modified` saved by an earlier run. Ranges resumed
+    /// from existing data send one as `If-Range`, so a file that changed
+    /// on the server is fetched again instead of being spliced together.
+    #[serde(skip)]
+    pub resume_etag: Option<String>,
+    #[serde(skip)]
+    pub resume_last_modified: Option<String>,
+    /// Where the URL resolved to when the download first started. Every
+    /// request goes straight there, so a redirect that rotates between
+    /// backends can't split one file across them; the original URL is only
+    /// analyzed again if this one stops answering.
+    #[serde(skip)]
+    pub resolved_url: Option<String>,
+    /// When a non-HTML file is answered with an HTML page, follow the page's
+    /// meta refresh (or its one download link) once and fetch that instead.
+    #[serde(default)]
+    pub follow_html_refresh: bool,
+    /// What to do if the save path is taken; `None` uses the queue default.
+    pub collision: Option<CollisionStrategy>,
+    /// Use HTTP/2 where the server supports it (ALPN on https, h2c prior
+    /// knowledge on plain http) so chunks share one multiplexed connection.
+    #[serde(default)]
+    pub prefer_http2: bool,
+    /// Redirects to follow before failing; `None` uses the engine default.
+    pub max_redirects: Option<u32>,
+    /// Milliseconds between progress callbacks; `None` uses the engine
+    /// default (250ms). Values under 50ms are raised to 50ms.
+    pub progress_interval_ms: Option<u64>,
+    /// Weight of each new speed sample in the smoothed speed and ETA, from
+    /// 0.01 (steadiest) to 1.0 (raw); `None` uses 0.3.
+    pub speed_smoothing: Option<f64>,
+    /// Smallest byte range worth its own connection; `None` uses 256KB.
+    /// Values under 4KB are raised to 4KB.
+    pub min_chunk_size: Option<u64>,
+    /// Milliseconds between starting each connection of a multi-connection
+    /// download, so servers don't see them all at once; `None` or 0 starts
+    /// them together.
+    pub connection_stagger_ms: Option<u64>,
+    /// Unix permission bits for the finished file (e.g. `0o600`); `None`
+    /// leaves them to the umask. Ignored on Windows.
+    pub file_mode: Option<u32>,
+    /// Fetch only bytes `start..=end` (to the end of the file when `end` is
+    /// `None`) and save just that slice. Single-connection HTTP only.
+    #[serde(default)]
+    pub range: Option<(u64, Option<u64>)>,
+    /// Refuse the add with `CraneError::AlreadyDownloaded` when the save path
+    /// already holds a file of the analyzed size (and `expected_hash`, if
+    /// given).
+    #[serde(default)]
+    pub skip_if_downloaded: bool,
+    /// Other URLs serving the same file (a Metalink's mirrors), switched to
+    /// in order when the download fails.
+    #[serde(skip)]
+    pub mirrors: Vec<String>,
+}
+
+impl std::fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DownloadOptions")
+            .field("save_path", &self.save_path)
+            .field("filename", &self.filename)
+            .field("connections", &self.connections)
+            .field("category", &self.category)
+            .field("priority", &self.priority)
+            .field("referrer", &self.referrer)
+            .field("cookies", &self.cookies.as_ref().map(|_| "[REDACTED]"))
+            .field("user_agent", &self.user_agent)
+            .field("headers", &self.headers.as_ref().map(|_| "[REDACTED]"))
+            .field("auth", &self.auth)
+            .field("expected_hash", &self.expected_hash)
+            .field("ssh_key_path", &self.ssh_key_path)
+            .field("password", &self.password.as_ref().map(|_| "[REDACTED]"))
+            .field("ssh_accept_new_host_keys", &self.ssh_accept_new_host_keys)
+            .field("ftp_mode", &self.ftp_mode)
+            .field("proxy", &self.proxy)
+            .field("no_proxy", &self.no_proxy)
+            .field("verify_from_sidecar", &self.verify_from_sidecar)
+            .field("retry_policy", &self.retry_policy)
+            .field("temp_dir", &self.temp_dir)
+            .field("ipfs_gateway", &self.ipfs_gateway)
+            .field("connect_timeout_ms", &self.connect_timeout_ms)
+            .field("read_timeout_ms", &self.read_timeout_ms)
+            .field("segmented_writes", &self.segmented_writes)
+            .field("parallel_verify", &self.parallel_verify)
+            .field("resume_connections", &self.resume_connections)
+            .field("resume_etag", &self.resume_etag)
+            .field("resume_last_modified", &self.resume_last_modified)
+            .field("resolved_url", &self.resolved_url)
+            .field("follow_html_refresh", &self.follow_html_refresh)
+            .field("collision", &self.collision)
+            .field("prefer_http2", &self.prefer_http2)
+            .field("max_redirects", &self.max_redirects)
+            .field("progress_interval_ms", &self.progress_interval_ms)
+            .field("speed_smoothing", &self.speed_smoothing)
+            .field("min_chunk_size", &self.min_chunk_size)
+            .field("connection_stagger_ms", &self.connection_stagger_ms)
+            .field("file_mode", &self.file_mode)
+            .field("range", &self.range)
+            .field("skip_if_downloaded", &self.skip_if_downloaded)
+            .field("mirrors", &self.mirrors)
+            .finish()
+    }
 }
 
 /// Result returned after a successful download
@@ -252,24 +572,63 @@ pub enum CraneError {
     #[error("Content-Type mismatch: expected {expected}, got {actual}")]
     ContentTypeMismatch { expected: String, actual: String },
 
+    #[error("Truncated response: expected {expected} bytes, got {received}")]
+    Truncated { expected: u64, received: u64 },
+
+    #[error("Server ignored the byte range request: {0}")]
+    RangeIgnored(String),
+
+    #[error("File changed on the server since the download started: {0}")]
+    SourceChanged(String),
+
     #[error("Unsupported URL scheme: {0}")]
     UnsupportedScheme(String),
 
+    #[error("BitTorrent is not supported ({0}); open magnet links and .torrent files in a BitTorrent client")]
+    TorrentUnsupported(String),
+
     #[error("Duplicate URL: {0}")]
     DuplicateUrl(String),
 
     #[error("Queue full: maximum {max} downloads allowed")]
     QueueFull { max: u32 },
 
+    #[error("File too large: {size} bytes exceeds the {max}-byte limit")]
+    FileTooLarge { size: u64, max: u64 },
+
     #[error("Path traversal rejected: {0}")]
     PathTraversal(String),
 
+    #[error("File already exists: {0}")]
+    FileExists(String),
+
+    #[error("Skipped, file already exists: {0}")]
+    SkippedExisting(String),
+
+    #[error("Already downloaded: {0}")]
+    AlreadyDownloaded(String),
+
+    #[error("Cancelled")]
+    Cancelled,
+
     #[error("Request to private/internal network blocked: {0}")]
     PrivateNetwork(String),
 
     #[error("FTP error: {0}")]
     Ftp(String),
 
+    #[error("SFTP error: {0}")]
+    Sftp(String),
+
+    #[error("Metalink error: {0}")]
+    Metalink(String),
+
+    #[error("Invalid data URI: {0}")]
+    DataUri(String),
+
+    #[error("IPFS error: {0}")]
+    Ipfs(String),
+
     #[error("Database error: {0}")]
     Database(String),
 }
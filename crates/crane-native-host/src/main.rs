@@ -1,12 +1,18 @@
 use crane_core::db::Database;
 use crane_core::metadata::analyzer::extract_filename_from_url_str;
 use crane_core::metadata::sanitize_filename;
-use crane_core::types::{Download, DownloadStatus, FileCategory};
+use crane_core::network::source_domain;
+use crane_core::protocol::data::parse_data_url;
+use crane_core::types::{Download, DownloadStatus, FileCategory, Priority};
 use std::io::{self, Read, Write};
 use std::path::PathBuf;
 
 const MAX_MESSAGE_SIZE: u32 = 1_048_576; // 1 MB
 
+/// Most items one "batch" message may carry. Allows about 1 KB of reply per
+/// item so the batch response also stays under `MAX_MESSAGE_SIZE`.
+const MAX_BATCH_ITEMS: usize = MAX_MESSAGE_SIZE as usize / 1024;
+
 fn has_file_extension(name: &str) -> bool {
     std::path::Path::new(name)
         .extension()
@@ -138,6 +144,29 @@ fn filter_sensitive_cookies(cookies: &str) -> String {
         .join("; ")
 }
 
+/// The "accepted" response for a download row, telling the extension where
+/// the file will land and whether something is already there.
+fn accepted_response(download: &Download) -> serde_json::Value {
+    serde_json::json!({
+        "type": "accepted",
+        "downloadId": download.id,
+        "filename": download.filename,
+        "savePath": download.save_path,
+        "alreadyExists": std::path::Path::new(&download.save_path).exists()
+    })
+}
+
+/// An "error" response. `code` is a stable, machine-readable reason
+/// (`missing_field`, `invalid_url`, `unsupported_scheme`, ...) the extension
+/// can branch on; `message` is for display.
+fn error_response(code: &str, message: impl Into<String>) -> serde_json::Value {
+    serde_json::json!({
+        "type": "error",
+        "code": code,
+        "message": message.into()
+    })
+}
+
 /// Handle a single incoming native message and produce a response.
 fn handle_message(msg: &serde_json::Value, db: &Database, save_dir: &str) -> serde_json::Value {
     let msg_type = msg.get("type").and_then(|v| v.as_str()).unwrap_or("");
@@ -150,12 +179,8 @@ fn handle_message(msg: &serde_json::Value, db: &Database, save_dir: &str) -> ser
             })
         }
         "download" => handle_download(msg, db, save_dir),
-        other => {
-            serde_json::json!({
-                "type": "error",
-                "message": format!("Unknown message type: '{other}'")
-            })
-        }
+        "batch" => handle_batch(msg, db, save_dir),
+        other => error_response("unknown_type", format!("Unknown message type: '{other}'")),
     }
 }
 
@@ -164,10 +189,7 @@ fn handle_download(msg: &serde_json::Value, db: &Database, save_dir: &str) -> se
     let url_str = match msg.get("url").and_then(|v| v.as_str()) {
         Some(u) => u,
         None => {
-            return serde_json::json!({
-                "type": "error",
-                "message": "Missing required field: 'url'"
-            });
+            return error_response("missing_field", "Missing required field: 'url'");
         }
     };
 
@@ -175,25 +197,41 @@ fn handle_download(msg: &serde_json::Value, db: &Database, save_dir: &str) -> se
     let parsed_url = match url::Url::parse(url_str) {
         Ok(u) => u,
         Err(e) => {
-            return serde_json::json!({
-                "type": "error",
-                "message": format!("Invalid URL: {e}")
-            });
+            return error_response("invalid_url", format!("Invalid URL: {e}"));
         }
     };
 
-    // Only allow http/https/ftp/ftps URLs
+    // BitTorrent gets its own error so the extension can explain it
+    if let Err(e) = crane_core::protocol::reject_torrent(&parsed_url) {
+        return error_response("torrent_unsupported", e.to_string());
+    }
+
+    // Only allow http/https/ftp/ftps/data URLs
     match parsed_url.scheme() {
-        "http" | "https" | "ftp" | "ftps" => {}
+        "http" | "https" | "ftp" | "ftps" | "data" => {}
         scheme => {
-            return serde_json::json!({
-                "type": "error",
-                "message": format!("Unsupported URL scheme: '{scheme}'. Only http, https, ftp, and ftps are allowed.")
-            });
+            return error_response(
+                "unsupported_scheme",
+                format!("Unsupported URL scheme: '{scheme}'. Only http, https, ftp, ftps, and data are allowed."),
+            );
         }
     }
 
-    let source_domain = parsed_url.host_str().map(|h| h.to_string());
+    // data: URLs carry their own payload; decode once to validate and size it
+    let data_url = if parsed_url.scheme() == "data" {
+        match parse_data_url(url_str) {
+            Ok(data_url) => Some(data_url),
+            Err(e) => return error_response("invalid_url", e.to_string()),
+        }
+    } else {
+        None
+    };
+    let url_filename = || match data_url {
+        Some(ref data_url) => data_url.filename(),
+        None => extract_filename_from_url_str(url_str),
+    };
+
+    let source_domain = source_domain(&parsed_url);
 
     // Use provided filename if it looks like a real file (has extension),
     // otherwise derive from URL query params / path with base64 decoding.
@@ -209,23 +247,27 @@ fn handle_download(msg: &serde_json::Value, db: &Database, save_dir: &str) -> se
         Some(name) => {
             // Extension-provided name has no file extension — check if the URL
             // can give us a better name (e.g. base64-encoded _fn query param)
-            let url_name = extract_filename_from_url_str(url_str);
+            let url_name = url_filename();
             if has_file_extension(&url_name) {
                 url_name
             } else {
                 name // URL isn't better, keep the extension-provided name
             }
         }
-        None => extract_filename_from_url_str(url_str),
+        None => url_filename(),
     };
     let filename = sanitize_filename(&raw_filename);
 
-    let file_size = msg.get("fileSize").and_then(|v| v.as_u64());
+    let file_size = msg
+        .get("fileSize")
+        .and_then(|v| v.as_u64())
+        .or_else(|| data_url.as_ref().map(|d| d.data.len() as u64));
 
     let mime_type = msg
         .get("mimeType")
         .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+        .map(|s| s.to_string())
+        .or_else(|| data_url.as_ref().map(|d| d.mime_type.clone()));
 
     let referrer = msg
         .get("referrer")
@@ -251,22 +293,21 @@ fn handle_download(msg: &serde_json::Value, db: &Database, save_dir: &str) -> se
     };
 
     // Dedup: return existing download if same URL is already pending/active
-    if let Ok(Some(id)) = db.find_active_download_id(url_str) {
-        return serde_json::json!({
-            "type": "accepted",
-            "downloadId": id
-        });
+    if let Ok(Some(existing)) = db
+        .find_active_download_id(url_str)
+        .and_then(|id| id.map(|id| db.get_download(&id)).transpose())
+    {
+        return accepted_response(&existing);
     }
 
     let category = categorize_mime(mime_type.as_deref());
 
     let save_path = PathBuf::from(save_dir).join(&filename);
 
-    let id = uuid::Uuid::new_v4().to_string();
     let now = chrono::Utc::now().to_rfc3339();
 
     let download = Download {
-        id: id.clone(),
+        id: uuid::Uuid::new_v4().to_string(),
         url: url_str.to_string(),
         filename,
         save_path: save_path.to_string_lossy().to_string(),
@@ -291,22 +332,59 @@ fn handle_download(msg: &serde_json::Value, db: &Database, save_dir: &str) -> se
         started_at: None,
         completed_at: None,
         updated_at: now,
+        scheduled_at: None,
+        final_url: None,
+        priority: Priority::Normal,
+        etag: None,
+        last_modified: None,
+        average_speed: None,
+        peak_speed: None,
+        auth_scheme: None,
+        content_hash: None,
+        mirrors: None,
     };
 
     match db.insert_download(&download) {
-        Ok(()) => {
-            serde_json::json!({
-                "type": "accepted",
-                "downloadId": id
-            })
-        }
-        Err(e) => {
-            serde_json::json!({
-                "type": "error",
-                "message": format!("Failed to insert download: {e}")
-            })
+        Ok(()) => accepted_response(&download),
+        Err(e) => error_response("db_error", format!("Failed to insert download: {e}")),
+    }
+}
+
+/// Handle a "batch" message: each item is a download message on its own, so
+/// one bad URL only fails its own entry in `results`.
+fn handle_batch(msg: &serde_json::Value, db: &Database, save_dir: &str) -> serde_json::Value {
+    let items = match msg.get("items").and_then(|v| v.as_array()) {
+        Some(items) => items,
+        None => {
+            return error_response("missing_field", "Missing required field: 'items'");
         }
+    };
+
+    if items.len() > MAX_BATCH_ITEMS {
+        return error_response(
+            "batch_too_large",
+            format!(
+                "Batch too large: {} items (max {MAX_BATCH_ITEMS})",
+                items.len()
+            ),
+        );
     }
+
+    let results: Vec<serde_json::Value> = items
+        .iter()
+        .map(|item| {
+            if item.is_object() {
+                handle_download(item, db, save_dir)
+            } else {
+                error_response("invalid_item", "Batch item must be an object")
+            }
+        })
+        .collect();
+
+    serde_json::json!({
+        "type": "batch",
+        "results": results
+    })
 }
 
 fn main() {
@@ -472,6 +550,8 @@ mod tests {
 
         // Filename should be sanitized to just "authorized_keys"
         assert_eq!(dl.filename, "authorized_keys");
+        assert_eq!(response["filename"], "authorized_keys");
+        assert_eq!(response["savePath"], dl.save_path.as_str());
         assert!(
             dl.save_path.starts_with("/downloads"),
             "save_path should stay within /downloads, got: {}",
@@ -530,6 +610,7 @@ mod tests {
         let response = handle_message(&msg, &db, "/tmp");
 
         assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "missing_field");
         assert!(response["message"].as_str().unwrap().contains("url"));
     }
 
@@ -541,6 +622,7 @@ mod tests {
         let response = handle_message(&msg, &db, "/tmp");
 
         assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "unknown_type");
         let err_msg = response["message"].as_str().unwrap();
         assert!(err_msg.contains("Unknown message type"));
         assert!(err_msg.contains("foobar"));
@@ -574,6 +656,92 @@ mod tests {
         assert_eq!(response["type"], "accepted");
     }
 
+    #[test]
+    fn test_handle_download_idn_and_encoded_filename() {
+        let db = Database::open_in_memory().unwrap();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "http://münchen.example/datei.pdf"
+        });
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "accepted");
+        let dl = db
+            .get_download(response["downloadId"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(dl.source_domain.as_deref(), Some("xn--mnchen-3ya.example"));
+        assert_eq!(dl.filename, "datei.pdf");
+
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "https://example.com/docs/Jahres%20Bericht%20%C3%9Cbersicht.pdf"
+        });
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "accepted");
+        let dl = db
+            .get_download(response["downloadId"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(dl.filename, "Jahres Bericht Übersicht.pdf");
+    }
+
+    #[test]
+    fn test_handle_download_accepts_data_url() {
+        let db = Database::open_in_memory().unwrap();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "data:text/plain;base64,aGVsbG8gd29ybGQ="
+        });
+
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "accepted");
+
+        let dl = db
+            .get_download(response["downloadId"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(dl.filename, "download.txt");
+        assert_eq!(dl.total_size, Some(11));
+        assert_eq!(dl.mime_type.as_deref(), Some("text/plain"));
+        assert_eq!(dl.category, FileCategory::Documents);
+        assert_eq!(dl.source_domain, None);
+    }
+
+    #[test]
+    fn test_handle_download_rejects_malformed_data_url() {
+        let db = Database::open_in_memory().unwrap();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "data:image/png;base64,not*base64"
+        });
+
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "error");
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid data URI"));
+    }
+
+    #[test]
+    fn test_handle_download_rejects_torrent_links() {
+        let db = Database::open_in_memory().unwrap();
+        for url in [
+            "magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a",
+            "https://example.com/ubuntu.iso.torrent",
+        ] {
+            let msg = serde_json::json!({ "type": "download", "url": url });
+            let response = handle_message(&msg, &db, "/downloads");
+            assert_eq!(response["type"], "error");
+            assert_eq!(response["code"], "torrent_unsupported", "{url}");
+            assert!(
+                response["message"]
+                    .as_str()
+                    .unwrap()
+                    .contains("BitTorrent is not supported"),
+                "{url}: {response}"
+            );
+        }
+        assert!(db.list_downloads().unwrap().is_empty());
+    }
+
     #[test]
     fn test_handle_download_rejects_gopher() {
         let db = Database::open_in_memory().unwrap();
@@ -584,6 +752,7 @@ mod tests {
 
         let response = handle_message(&msg, &db, "/downloads");
         assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "unsupported_scheme");
         assert!(response["message"]
             .as_str()
             .unwrap()
@@ -612,6 +781,7 @@ mod tests {
 
         let response = handle_message(&msg, &db, "/downloads");
         assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "unsupported_scheme");
         assert!(response["message"]
             .as_str()
             .unwrap()
@@ -762,4 +932,149 @@ mod tests {
 
         assert_ne!(id1, id2, "Should create new download after completion");
     }
+
+    #[test]
+    fn test_handle_download_accepted_reports_destination() {
+        let db = Database::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let save_dir = dir.path().to_string_lossy().to_string();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "https://example.com/report.pdf",
+            "filename": "report.pdf"
+        });
+
+        let response = handle_message(&msg, &db, &save_dir);
+
+        assert_eq!(response["type"], "accepted");
+        assert_eq!(response["filename"], "report.pdf");
+        let expected_path = dir.path().join("report.pdf");
+        assert_eq!(
+            response["savePath"],
+            expected_path.to_string_lossy().as_ref()
+        );
+        assert_eq!(response["alreadyExists"], false);
+    }
+
+    #[test]
+    fn test_handle_download_reports_already_exists() {
+        let db = Database::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file.zip"), b"old").unwrap();
+        let save_dir = dir.path().to_string_lossy().to_string();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "https://example.com/file.zip",
+            "filename": "file.zip"
+        });
+
+        let r1 = handle_message(&msg, &db, &save_dir);
+        assert_eq!(r1["type"], "accepted");
+        assert_eq!(r1["alreadyExists"], true);
+
+        // The dedup path reports the existing row's destination too
+        let r2 = handle_message(&msg, &db, &save_dir);
+        assert_eq!(r2["downloadId"], r1["downloadId"]);
+        assert_eq!(r2["filename"], "file.zip");
+        assert_eq!(r2["savePath"], r1["savePath"]);
+        assert_eq!(r2["alreadyExists"], true);
+    }
+
+    #[test]
+    fn test_handle_batch_partial_success() {
+        let db = Database::open_in_memory().unwrap();
+        let msg = serde_json::json!({
+            "type": "batch",
+            "items": [
+                { "url": "https://example.com/a.zip" },
+                { "url": "gopher://example.com/b.zip" },
+                { "filename": "no-url.bin" },
+                "https://example.com/not-an-object.zip",
+                { "url": "https://example.com/c.pdf", "filename": "c.pdf" }
+            ]
+        });
+
+        let response = handle_message(&msg, &db, "/downloads");
+
+        assert_eq!(response["type"], "batch");
+        let results = response["results"].as_array().unwrap();
+        assert_eq!(results.len(), 5);
+        let types: Vec<&str> = results
+            .iter()
+            .map(|r| r["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["accepted", "error", "error", "error", "accepted"]);
+        assert_eq!(results[1]["code"], "unsupported_scheme");
+        assert_eq!(results[2]["code"], "missing_field");
+        assert_eq!(results[3]["code"], "invalid_item");
+        assert!(results[1]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Unsupported URL scheme"));
+        assert!(results[2]["message"].as_str().unwrap().contains("url"));
+
+        // Only the valid items were inserted
+        let downloads = db.list_downloads().unwrap();
+        assert_eq!(downloads.len(), 2);
+        for result in [&results[0], &results[4]] {
+            let dl = db
+                .get_download(result["downloadId"].as_str().unwrap())
+                .unwrap();
+            assert_eq!(result["filename"], dl.filename.as_str());
+        }
+    }
+
+    #[test]
+    fn test_handle_batch_rejects_oversized_or_missing_items() {
+        let db = Database::open_in_memory().unwrap();
+        let items: Vec<serde_json::Value> = (0..=MAX_BATCH_ITEMS)
+            .map(|i| serde_json::json!({ "url": format!("https://example.com/{i}.bin") }))
+            .collect();
+        let msg = serde_json::json!({ "type": "batch", "items": items });
+
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "batch_too_large");
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .contains("Batch too large"));
+        assert!(db.list_downloads().unwrap().is_empty());
+
+        let response = handle_message(&serde_json::json!({ "type": "batch" }), &db, "/downloads");
+        assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "missing_field");
+        assert!(response["message"].as_str().unwrap().contains("items"));
+    }
+
+    #[test]
+    fn test_error_codes_for_invalid_urls_and_db_failure() {
+        let db = Database::open_in_memory().unwrap();
+        for url in ["not a url", "data:text/plain;base64,%%%"] {
+            let msg = serde_json::json!({ "type": "download", "url": url });
+            let response = handle_message(&msg, &db, "/downloads");
+            assert_eq!(response["type"], "error");
+            assert_eq!(response["code"], "invalid_url", "{url}: {response}");
+        }
+
+        // A database that can't take the row
+        let tmp = tempfile::TempDir::new().unwrap();
+        let db_path = tmp.path().join("crane.db");
+        let db = Database::open(&db_path).unwrap();
+        rusqlite::Connection::open(&db_path)
+            .unwrap()
+            .execute_batch("DROP TABLE downloads;")
+            .unwrap();
+        let msg = serde_json::json!({
+            "type": "download",
+            "url": "https://example.com/file.zip"
+        });
+        let response = handle_message(&msg, &db, "/downloads");
+        assert_eq!(response["type"], "error");
+        assert_eq!(response["code"], "db_error");
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to insert download"));
+    }
 }
@@ -1,17 +1,27 @@
-use crane_core::metadata::analyzer;
+use crane_core::db::downloads::{DownloadFilter, DownloadPage};
+use crane_core::events::LowDiskSpace;
 use crane_core::network::validate_url_safe;
-use crane_core::types::{Download, DownloadOptions, DownloadProgress, UrlAnalysis};
+use crane_core::queue::{DownloadPreview, QueueStats};
+use crane_core::types::{Download, DownloadOptions, DownloadProgress, FileCategory, Priority};
 use tauri::State;
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::state::AppState;
 
 #[tauri::command]
-pub async fn analyze_url(url: String) -> Result<UrlAnalysis, String> {
+pub async fn analyze_url(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<DownloadPreview, String> {
     // Validate URL before making any HTTP requests (SSRF prevention)
     let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
     validate_url_safe(&parsed).map_err(|e| e.to_string())?;
 
-    analyzer::analyze_url(&url).await.map_err(|e| e.to_string())
+    state
+        .queue
+        .preview(&url, DownloadOptions::default())
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -32,19 +42,63 @@ pub async fn add_download(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn schedule_download(
+    state: State<'_, AppState>,
+    url: String,
+    when: String,
+    options: Option<DownloadOptions>,
+) -> Result<String, String> {
+    let parsed = url::Url::parse(&url).map_err(|e| e.to_string())?;
+    validate_url_safe(&parsed).map_err(|e| e.to_string())?;
+
+    let when = chrono::DateTime::parse_from_rfc3339(&when)
+        .map_err(|e| format!("invalid schedule time: {e}"))?
+        .with_timezone(&chrono::Utc);
+    let opts = options.unwrap_or_default();
+    state
+        .queue
+        .schedule(&url, &state.default_save_dir, opts, when)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn pause_download(state: State<'_, AppState>, id: String) -> Result<(), String> {
     state.queue.pause(&id).await.map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn resume_download(state: State<'_, AppState>, id: String) -> Result<(), String> {
-    state.queue.resume(&id).await.map_err(|e| e.to_string())
+pub async fn resume_download(
+    state: State<'_, AppState>,
+    id: String,
+    connections: Option<u32>,
+) -> Result<(), String> {
+    state
+        .queue
+        .resume_with_connections(&id, connections)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-pub async fn cancel_download(state: State<'_, AppState>, id: String) -> Result<(), String> {
-    state.queue.cancel(&id).await.map_err(|e| e.to_string())
+pub async fn cancel_download(
+    state: State<'_, AppState>,
+    id: String,
+    keep_partial: Option<bool>,
+) -> Result<(), String> {
+    if keep_partial.unwrap_or(false) {
+        state.queue.cancel_keep_partial(&id).await
+    } else {
+        state.queue.cancel(&id).await
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Abort an `add_download` for `url` that is still analyzing it.
+#[tauri::command]
+pub async fn cancel_pending_add(state: State<'_, AppState>, url: String) -> Result<bool, String> {
+    Ok(state.queue.cancel_pending_add(&url))
 }
 
 #[tauri::command]
@@ -52,6 +106,37 @@ pub async fn get_downloads(state: State<'_, AppState>) -> Result<Vec<Download>,
     state.queue.list_downloads().map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_downloads_paged(
+    state: State<'_, AppState>,
+    filter: Option<DownloadFilter>,
+    offset: u32,
+    limit: u32,
+) -> Result<DownloadPage, String> {
+    state
+        .queue
+        .db()
+        .list_downloads_paged(&filter.unwrap_or_default(), offset, limit)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn search_downloads(
+    state: State<'_, AppState>,
+    query: String,
+) -> Result<Vec<Download>, String> {
+    state
+        .queue
+        .db()
+        .search_downloads(&query)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_queue_stats(state: State<'_, AppState>) -> Result<QueueStats, String> {
+    state.queue.stats().await.map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_download(state: State<'_, AppState>, id: String) -> Result<Download, String> {
     state
@@ -61,6 +146,19 @@ pub async fn get_download(state: State<'_, AppState>, id: String) -> Result<Down
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn get_speed_history(
+    state: State<'_, AppState>,
+    id: String,
+    limit: Option<u32>,
+) -> Result<Vec<(String, f64)>, String> {
+    state
+        .queue
+        .db()
+        .get_recent_speed_history(&id, limit.unwrap_or(120))
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn retry_download(state: State<'_, AppState>, id: String) -> Result<(), String> {
     state.queue.retry(&id).await.map_err(|e| e.to_string())
@@ -89,6 +187,18 @@ pub async fn resume_all_downloads(state: State<'_, AppState>) -> Result<Vec<Stri
     state.queue.resume_all().await.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn cancel_all_downloads(
+    state: State<'_, AppState>,
+    delete_queued: bool,
+) -> Result<Vec<String>, String> {
+    state
+        .queue
+        .cancel_all(delete_queued)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn delete_completed(state: State<'_, AppState>) -> Result<u64, String> {
     state
@@ -98,6 +208,46 @@ pub async fn delete_completed(state: State<'_, AppState>) -> Result<u64, String>
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn recategorize_download(
+    state: State<'_, AppState>,
+    id: String,
+    category: FileCategory,
+) -> Result<(), String> {
+    state
+        .queue
+        .recategorize(&id, category)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_download_priority(
+    state: State<'_, AppState>,
+    id: String,
+    priority: Priority,
+) -> Result<(), String> {
+    state
+        .queue
+        .set_priority(&id, priority)
+        .map_err(|e| e.to_string())
+}
+
+/// Move a pending or queued download to another folder. Returns the new
+/// destination path.
+#[tauri::command]
+pub async fn set_download_save_path(
+    state: State<'_, AppState>,
+    id: String,
+    dir: String,
+) -> Result<String, String> {
+    state
+        .queue
+        .set_save_path(&id, &dir)
+        .await
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn subscribe_progress(
     state: State<'_, AppState>,
@@ -106,18 +256,52 @@ pub async fn subscribe_progress(
 ) -> Result<(), String> {
     let queue = state.queue.clone();
     let id = download_id.clone();
+    let mut events = queue.subscribe();
+
+    tauri::async_runtime::spawn(async move {
+        let mut liveness = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(progress) if progress.download_id == id => {
+                        if on_progress.send(progress).is_err() {
+                            break; // Frontend disconnected
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+                _ = liveness.tick() => {
+                    if queue.get_progress(&id).await.is_none() {
+                        break; // Download no longer active
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Stream low-space warnings. They repeat while space stays low, so they go
+/// through a channel like progress rather than an app event.
+#[tauri::command]
+pub async fn subscribe_low_disk_space(
+    state: State<'_, AppState>,
+    on_warning: tauri::ipc::Channel<LowDiskSpace>,
+) -> Result<(), String> {
+    let mut events = state.queue.subscribe_low_disk_space();
 
     tauri::async_runtime::spawn(async move {
-        let mut interval = tokio::time::interval(std::time::Duration::from_millis(250));
         loop {
-            interval.tick().await;
-            match queue.get_progress(&id).await {
-                Some(progress) => {
-                    if on_progress.send(progress).is_err() {
+            match events.recv().await {
+                Ok(warning) => {
+                    if on_warning.send(warning).is_err() {
                         break; // Frontend disconnected
                     }
                 }
-                None => break, // Download no longer active
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
             }
         }
     });
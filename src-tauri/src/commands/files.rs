@@ -1,4 +1,5 @@
 use crane_core::hash::{self, HashAlgorithm};
+use crane_core::types::ExpectedHash;
 use tauri::State;
 
 use crate::state::AppState;
@@ -48,6 +49,26 @@ pub async fn calculate_hash(
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn verify_file(
+    state: State<'_, AppState>,
+    path: String,
+    algorithm: String,
+    expected: String,
+) -> Result<bool, String> {
+    let expected = ExpectedHash {
+        algorithm: algorithm
+            .parse::<HashAlgorithm>()
+            .map_err(|e| e.to_string())?,
+        value: expected,
+    };
+    state
+        .queue
+        .verify_file(std::path::Path::new(&path), &expected)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn get_download_path(state: State<'_, AppState>, id: String) -> Result<String, String> {
     let dl = state
@@ -29,6 +29,99 @@ fn validate_settings_path(path: &std::path::Path) -> Result<(), String> {
     Ok(())
 }
 
+/// Push `config` to the running queue so changes take effect without a
+/// restart.
+async fn apply_to_queue(state: &AppState, config: &AppConfig) -> Result<(), String> {
+    state
+        .queue
+        .set_max_concurrent(config.downloads.max_concurrent)
+        .await
+        .map_err(|e| e.to_string())?;
+    state
+        .queue
+        .set_bandwidth_limit(config.downloads.bandwidth_limit);
+    state
+        .queue
+        .set_speed_schedule(config.network.speed_schedule.clone())
+        .await;
+    state.queue.set_proxy(config.network.proxy.clone());
+    state
+        .queue
+        .set_retry_policy(config.downloads.retry_policy.clone());
+    state.queue.set_auto_retry(config.downloads.auto_retry);
+    state.queue.set_temp_dir(config.downloads.temp_dir_path());
+    state
+        .queue
+        .set_ipfs_gateway(config.network.ipfs_gateway.clone());
+    state
+        .queue
+        .set_category_folders(config.file_organization.category_folder_paths());
+    // Downloads this session still go to the folder chosen at startup
+    state.queue.set_download_dirs(vec![
+        state.default_save_dir.clone().into(),
+        config.general.download_location.clone().into(),
+    ]);
+    state.queue.set_timeouts(config.network.timeouts);
+    state.queue.set_prefer_http2(config.network.prefer_http2);
+    state.queue.set_ftp_mode(config.network.ftp_mode);
+    state.queue.set_max_redirects(config.network.max_redirects);
+    state
+        .queue
+        .set_progress_interval_ms(config.downloads.progress_interval_ms);
+    state
+        .queue
+        .set_speed_smoothing(config.downloads.speed_smoothing);
+    state
+        .queue
+        .set_min_chunk_size(config.downloads.min_chunk_size);
+    state
+        .queue
+        .set_connection_stagger_ms(config.downloads.connection_stagger_ms);
+    state.queue.set_max_history(config.downloads.max_history);
+    state
+        .queue
+        .set_max_file_size(config.downloads.max_file_size);
+    state
+        .queue
+        .set_low_disk_space_threshold(config.downloads.low_disk_space_threshold);
+    state
+        .queue
+        .set_pause_on_low_disk_space(config.downloads.pause_on_low_disk_space);
+    state
+        .queue
+        .set_delete_pruned_files(config.downloads.delete_pruned_files);
+    state.queue.set_file_mode(config.general.file_mode);
+    state
+        .queue
+        .set_segmented_writes(config.downloads.segmented_writes);
+    state
+        .queue
+        .set_parallel_verify(config.downloads.parallel_verify);
+    state
+        .queue
+        .set_follow_html_refresh(config.downloads.follow_html_refresh);
+    state
+        .queue
+        .set_collision_strategy(config.file_organization.collision_strategy());
+    state
+        .queue
+        .set_sniff_category(config.file_organization.sniff_category);
+    state
+        .queue
+        .set_skip_if_downloaded(config.file_organization.skip_if_downloaded);
+    state.queue.set_dedup_mode(config.file_organization.dedup);
+    state
+        .queue
+        .set_max_connections_per_host(config.downloads.max_connections_per_host);
+    state
+        .queue
+        .set_on_complete_command(config.downloads.on_complete_command.clone());
+    state
+        .queue
+        .set_user_agents(config.network.user_agents.clone());
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_settings(state: State<'_, AppState>) -> Result<AppConfig, String> {
     let config = state.config.lock().await;
@@ -41,7 +134,8 @@ pub async fn update_settings(
     settings: serde_json::Value,
 ) -> Result<(), String> {
     let mut config = state.config.lock().await;
-    config.update(settings).map_err(|e| e.to_string())
+    config.update(settings).map_err(|e| e.to_string())?;
+    apply_to_queue(&state, config.get()).await
 }
 
 #[tauri::command]
@@ -74,11 +168,27 @@ pub async fn import_settings(state: State<'_, AppState>, path: String) -> Result
     validate_settings_path(import_path)?;
 
     let mut config = state.config.lock().await;
-    config.import_from(import_path).map_err(|e| e.to_string())
+    config.import_from(import_path).map_err(|e| e.to_string())?;
+    apply_to_queue(&state, config.get()).await
+}
+
+#[tauri::command]
+pub async fn preview_settings_import(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<AppConfig, String> {
+    let import_path = std::path::Path::new(&path);
+    validate_settings_path(import_path)?;
+
+    let config = state.config.lock().await;
+    config
+        .import_preview(import_path)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 pub async fn reset_settings(state: State<'_, AppState>) -> Result<(), String> {
     let mut config = state.config.lock().await;
-    config.reset().map_err(|e| e.to_string())
+    config.reset().map_err(|e| e.to_string())?;
+    apply_to_queue(&state, config.get()).await
 }
@@ -5,14 +5,44 @@ mod notifications;
 mod state;
 mod tray;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use crane_core::db::Database;
 use crane_core::queue::QueueManager;
 use state::AppState;
-use tauri::{Emitter, Manager};
+use tauri::{Emitter, Manager, RunEvent};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// How long in-flight downloads get to stop and flush before the app exits.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Re-emit each event from `events` to the frontend as `name` until the
+/// queue closes the channel.
+fn forward_events<T>(app: &tauri::AppHandle, name: &'static str, mut events: Receiver<T>)
+where
+    T: Clone + serde::Serialize + Send + 'static,
+{
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let _ = app.emit(name, event);
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
 
 fn main() {
+    // Set once downloads are paused, so the exit we re-issue goes through
+    let exit_ready = Arc::new(AtomicBool::new(false));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
@@ -49,45 +79,21 @@ fn main() {
                 }
             };
 
-            // Extract queue/bandwidth settings before moving config_manager
-            let max_concurrent = config_manager.get().downloads.max_concurrent;
-            let bandwidth_limit = config_manager.get().downloads.bandwidth_limit;
-            let speed_schedule = config_manager.get().network.speed_schedule.clone();
+            // Queue settings all come from config; see `QueueManager::from_config`
             let auto_resume = config_manager.get().downloads.auto_resume;
-
-            let config = Arc::new(tokio::sync::Mutex::new(config_manager));
-
-            // Create queue manager with bandwidth settings from config
-            let queue = Arc::new(QueueManager::new(
+            let queue = Arc::new(QueueManager::from_config(
                 db,
-                max_concurrent,
-                bandwidth_limit,
-                speed_schedule,
+                config_manager.get(),
+                std::path::Path::new(&save_dir),
             ));
+            let config = Arc::new(tokio::sync::Mutex::new(config_manager));
 
-            // Recover downloads interrupted by crash/force-close
-            if let Err(e) = queue.recover_interrupted() {
+            // Recover downloads interrupted by crash/force-close. Downloads the
+            // user paused stay paused until they resume them.
+            if let Err(e) = queue.recover_interrupted(auto_resume) {
                 eprintln!("[startup] Recovery error: {e}");
             }
 
-            // Auto-resume paused downloads if configured
-            if auto_resume {
-                let resume_queue = queue.clone();
-                tauri::async_runtime::spawn(async move {
-                    match resume_queue.resume_all().await {
-                        Ok(resumed) => {
-                            if !resumed.is_empty() {
-                                eprintln!(
-                                    "[startup] Auto-resumed {} paused downloads",
-                                    resumed.len()
-                                );
-                            }
-                        }
-                        Err(e) => eprintln!("[startup] Auto-resume error: {e}"),
-                    }
-                });
-            }
-
             // Spawn completion + pending monitor with notifications
             let monitor_queue = queue.clone();
             let monitor_save_dir = save_dir.clone();
@@ -109,6 +115,29 @@ fn main() {
                             &finished,
                         )
                         .await;
+                        if !finished.is_empty() {
+                            match monitor_queue.prune_history().await {
+                                Ok(pruned) if pruned > 0 => changed = true,
+                                Ok(_) => {}
+                                Err(e) => eprintln!("prune_history error: {e}"),
+                            }
+                        }
+                    }
+                    match monitor_queue.check_auto_retry() {
+                        Ok(retried) => {
+                            if !retried.is_empty() {
+                                changed = true;
+                            }
+                        }
+                        Err(e) => eprintln!("check_auto_retry error: {e}"),
+                    }
+                    match monitor_queue.check_pause_window().await {
+                        Ok(toggled) => {
+                            if !toggled.is_empty() {
+                                changed = true;
+                            }
+                        }
+                        Err(e) => eprintln!("check_pause_window error: {e}"),
                     }
                     match monitor_queue.check_pending(&monitor_save_dir).await {
                         Ok(started) => {
@@ -124,6 +153,19 @@ fn main() {
                 }
             });
 
+            // One-off transitions go out as app events; progress and
+            // low-space warnings stream through IPC channels instead
+            forward_events(
+                app.handle(),
+                "download-completed",
+                queue.subscribe_completed(),
+            );
+            forward_events(
+                app.handle(),
+                "download-status-changed",
+                queue.subscribe_status(),
+            );
+
             app.manage(AppState {
                 queue,
                 config,
@@ -138,31 +180,64 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             commands::downloads::analyze_url,
             commands::downloads::add_download,
+            commands::downloads::schedule_download,
             commands::downloads::pause_download,
             commands::downloads::resume_download,
             commands::downloads::cancel_download,
+            commands::downloads::cancel_pending_add,
             commands::downloads::get_downloads,
+            commands::downloads::get_downloads_paged,
+            commands::downloads::search_downloads,
+            commands::downloads::get_queue_stats,
             commands::downloads::get_download,
+            commands::downloads::get_speed_history,
             commands::downloads::subscribe_progress,
+            commands::downloads::subscribe_low_disk_space,
             commands::downloads::retry_download,
             commands::downloads::delete_download,
             commands::downloads::pause_all_downloads,
             commands::downloads::resume_all_downloads,
+            commands::downloads::cancel_all_downloads,
             commands::downloads::delete_completed,
+            commands::downloads::recategorize_download,
+            commands::downloads::set_download_priority,
+            commands::downloads::set_download_save_path,
             commands::settings::get_settings,
             commands::settings::update_settings,
             commands::settings::get_config_path,
             commands::settings::open_config_file,
             commands::settings::export_settings,
             commands::settings::import_settings,
+            commands::settings::preview_settings_import,
             commands::settings::reset_settings,
             commands::files::open_file,
             commands::files::open_folder,
             commands::files::calculate_hash,
+            commands::files::verify_file,
             commands::files::get_download_path,
             commands::system::get_app_info,
             commands::system::get_disk_space,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |app, event| {
+            // Fires for the tray's Quit and when the last window closes: hold
+            // the exit until active downloads are paused with their chunks kept
+            if let RunEvent::ExitRequested { api, .. } = event {
+                if exit_ready.load(Ordering::SeqCst) {
+                    return;
+                }
+                api.prevent_exit();
+                let queue = app.state::<AppState>().queue.clone();
+                let app = app.clone();
+                let exit_ready = exit_ready.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = queue.shutdown(SHUTDOWN_TIMEOUT).await {
+                        eprintln!("[shutdown] Failed to pause downloads: {e}");
+                    }
+                    exit_ready.store(true, Ordering::SeqCst);
+                    app.exit(0);
+                });
+            }
+        });
 }
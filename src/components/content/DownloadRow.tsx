@@ -76,6 +76,7 @@ const DownloadRow: Component<DownloadRowProps> = (props) => {
   const liveSpeed = () => getProgress(dl().id)?.speed ?? dl().speed;
   const liveDownloaded = () => getProgress(dl().id)?.downloaded_size ?? dl().downloaded_size;
   const liveTotalSize = () => getProgress(dl().id)?.total_size ?? dl().total_size;
+  const livePhase = () => getProgress(dl().id)?.phase ?? "downloading";
 
   const handleClick = (e: MouseEvent) => {
     if (e.shiftKey) {
@@ -117,7 +118,7 @@ const DownloadRow: Component<DownloadRowProps> = (props) => {
   const etaSeconds = () => {
     const speed = liveSpeed();
     const total = liveTotalSize();
-    if (!isActive() || speed === 0 || !total) return null;
+    if (!isActive() || livePhase() !== "downloading" || speed === 0 || !total) return null;
     return Math.round((total - liveDownloaded()) / speed);
   };
 
@@ -152,7 +153,13 @@ const DownloadRow: Component<DownloadRowProps> = (props) => {
           {/* Meta row — order matches Pencil per status */}
           <div class="flex items-center gap-[12px] font-mono">
             {/* Active: speed, ETA, size, source */}
-            <Show when={isActive() && liveSpeed() > 0}>
+            <Show when={isActive() && livePhase() !== "downloading"}>
+              <span class="text-caption font-semibold text-accent">
+                {livePhase() === "merging" ? "Merging..." : "Verifying hash..."}
+              </span>
+            </Show>
+
+            <Show when={isActive() && livePhase() === "downloading" && liveSpeed() > 0}>
               <span class="text-caption font-semibold text-accent">{formatSpeed(liveSpeed())}</span>
             </Show>
 
@@ -180,6 +187,12 @@ const DownloadRow: Component<DownloadRowProps> = (props) => {
               <span class="text-caption font-medium text-secondary">{sizeLabel()}</span>
             </Show>
 
+            {/* Cancelled: status first, then size */}
+            <Show when={dl().status === "cancelled"}>
+              <span class="text-caption font-semibold text-tertiary">Cancelled</span>
+              <span class="text-caption font-medium text-secondary">{sizeLabel()}</span>
+            </Show>
+
             {/* Active: size after speed/ETA */}
             <Show when={isActive()}>
               <span class="text-caption font-medium text-secondary">{sizeLabel()}</span>
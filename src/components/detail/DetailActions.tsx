@@ -21,7 +21,7 @@ const DetailActions: Component<DetailActionsProps> = (props) => {
   const dl = () => props.download;
   const isActive = () => dl().status === "downloading" || dl().status === "analyzing";
   const isPaused = () => dl().status === "paused";
-  const isFailed = () => dl().status === "failed";
+  const isFailed = () => dl().status === "failed" || dl().status === "cancelled";
   const isCompleted = () => dl().status === "completed";
 
   const action = async (fn: () => Promise<void>) => {
@@ -125,10 +125,26 @@ const DetailPanel: Component = () => {
                     </div>
                   );
                 })()}
+                <Show when={download().final_url}>
+                  {(finalUrl) => (
+                    <p
+                      class="text-caption font-mono text-muted truncate select-all"
+                      title={finalUrl()}
+                    >
+                      → {finalUrl()}
+                    </p>
+                  )}
+                </Show>
               </div>
 
               {/* Progress section — only for active/paused/queued */}
-              <Show when={download().status !== "completed" && download().status !== "failed"}>
+              <Show
+                when={
+                  download().status !== "completed" &&
+                  download().status !== "failed" &&
+                  download().status !== "cancelled"
+                }
+              >
                 <div class="flex flex-col gap-[8px] rounded-lg bg-inset p-[16px]">
                   {/* Big percent + speed/ETA */}
                   <div class="flex items-center justify-between">
@@ -11,19 +11,19 @@ const DownloadsTab: Component = () => {
   return (
     <div class="flex flex-col gap-[24px]">
       <SettingSection title="Connections">
-        <SettingRow label="Default Connections" description="Number of parallel connections per download (1–128)">
+        <SettingRow label="Default Connections" description="Number of parallel connections per download (1–64)">
           <SettingStepper
             value={config.downloads.default_connections}
             min={1}
-            max={128}
+            max={64}
             onChange={(v) => update("downloads.default_connections", v)}
           />
         </SettingRow>
-        <SettingRow label="Max Concurrent Downloads" description="Maximum downloads running at once (1–20)">
+        <SettingRow label="Max Concurrent Downloads" description="Maximum downloads running at once (1–100)">
           <SettingStepper
             value={config.downloads.max_concurrent}
             min={1}
-            max={20}
+            max={100}
             onChange={(v) => update("downloads.max_concurrent", v)}
           />
         </SettingRow>
@@ -42,7 +42,7 @@ const DownloadsTab: Component = () => {
       </SettingSection>
 
       <SettingSection title="Behavior">
-        <SettingRow label="Auto Resume" description="Automatically resume incomplete downloads on app start">
+        <SettingRow label="Auto Resume" description="Restart downloads interrupted by a crash or force-quit; paused downloads stay paused">
           <SettingToggle
             checked={config.downloads.auto_resume}
             onChange={(v) => update("downloads.auto_resume", v)}
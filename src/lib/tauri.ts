@@ -1,10 +1,16 @@
 import { invoke, Channel, isTauri } from "@tauri-apps/api/core";
 import type {
   Download,
+  DownloadFilter,
+  DownloadPage,
+  QueueStats,
   DownloadProgress,
   DownloadOptions,
-  UrlAnalysis,
+  DownloadPreview,
   DiskSpace,
+  LowDiskSpace,
+  FileCategory,
+  Priority,
   AppInfo,
 } from "../types/download";
 import type { AppConfig } from "../types/settings";
@@ -17,10 +23,30 @@ export function getDownloads(): Promise<Download[]> {
   return invoke<Download[]>("get_downloads");
 }
 
+export function getDownloadsPaged(
+  filter: DownloadFilter | null,
+  offset: number,
+  limit: number,
+): Promise<DownloadPage> {
+  return invoke<DownloadPage>("get_downloads_paged", { filter, offset, limit });
+}
+
+export function searchDownloads(query: string): Promise<Download[]> {
+  return invoke<Download[]>("search_downloads", { query });
+}
+
+export function getQueueStats(): Promise<QueueStats> {
+  return invoke<QueueStats>("get_queue_stats");
+}
+
 export function getDownload(id: string): Promise<Download> {
   return invoke<Download>("get_download", { id });
 }
 
+export function getSpeedHistory(id: string, limit?: number): Promise<[string, number][]> {
+  return invoke<[string, number][]>("get_speed_history", { id, limit: limit ?? null });
+}
+
 export function subscribeProgress(
   downloadId: string,
   onProgress: (progress: DownloadProgress) => void,
@@ -31,26 +57,47 @@ export function subscribeProgress(
   return channel;
 }
 
-export function analyzeUrl(url: string): Promise<UrlAnalysis> {
-  return invoke<UrlAnalysis>("analyze_url", { url });
+export function subscribeLowDiskSpace(
+  onWarning: (warning: LowDiskSpace) => void,
+): Channel<LowDiskSpace> {
+  const channel = new Channel<LowDiskSpace>();
+  channel.onmessage = onWarning;
+  invoke("subscribe_low_disk_space", { onWarning: channel });
+  return channel;
+}
+
+export function analyzeUrl(url: string): Promise<DownloadPreview> {
+  return invoke<DownloadPreview>("analyze_url", { url });
 }
 
 export function addDownload(url: string, options?: DownloadOptions): Promise<string> {
   return invoke<string>("add_download", { url, options: options ?? null });
 }
 
+export function scheduleDownload(
+  url: string,
+  when: string,
+  options?: DownloadOptions,
+): Promise<string> {
+  return invoke<string>("schedule_download", { url, when, options: options ?? null });
+}
+
 // ── Download Actions ──────────────────────────
 
 export function pauseDownload(id: string): Promise<void> {
   return invoke("pause_download", { id });
 }
 
-export function resumeDownload(id: string): Promise<void> {
-  return invoke("resume_download", { id });
+export function resumeDownload(id: string, connections?: number): Promise<void> {
+  return invoke("resume_download", { id, connections: connections ?? null });
 }
 
-export function cancelDownload(id: string): Promise<void> {
-  return invoke("cancel_download", { id });
+export function cancelDownload(id: string, keepPartial = false): Promise<void> {
+  return invoke("cancel_download", { id, keepPartial });
+}
+
+export function cancelPendingAdd(url: string): Promise<boolean> {
+  return invoke("cancel_pending_add", { url });
 }
 
 export function retryDownload(id: string): Promise<void> {
@@ -61,6 +108,18 @@ export function deleteDownload(id: string, deleteFile: boolean): Promise<void> {
   return invoke("delete_download", { id, deleteFile });
 }
 
+export function recategorizeDownload(id: string, category: FileCategory): Promise<void> {
+  return invoke("recategorize_download", { id, category });
+}
+
+export function setDownloadPriority(id: string, priority: Priority): Promise<void> {
+  return invoke("set_download_priority", { id, priority });
+}
+
+export function setDownloadSavePath(id: string, dir: string): Promise<string> {
+  return invoke<string>("set_download_save_path", { id, dir });
+}
+
 export function pauseAllDownloads(): Promise<string[]> {
   return invoke<string[]>("pause_all_downloads");
 }
@@ -69,6 +128,10 @@ export function resumeAllDownloads(): Promise<string[]> {
   return invoke<string[]>("resume_all_downloads");
 }
 
+export function cancelAllDownloads(deleteQueued: boolean): Promise<string[]> {
+  return invoke<string[]>("cancel_all_downloads", { deleteQueued });
+}
+
 // ── Files ─────────────────────────────────────
 
 export function openFile(id: string): Promise<void> {
@@ -79,6 +142,14 @@ export function openFolder(id: string): Promise<void> {
   return invoke("open_folder", { id });
 }
 
+export function verifyFile(
+  path: string,
+  algorithm: "sha256" | "md5" | "sha1" | "sha512" | "auto",
+  expected: string,
+): Promise<boolean> {
+  return invoke("verify_file", { path, algorithm, expected });
+}
+
 // ── Settings ──────────────────────────────────
 
 export function getSettings(): Promise<AppConfig> {
@@ -105,6 +176,10 @@ export function importSettings(path: string): Promise<void> {
   return invoke("import_settings", { path });
 }
 
+export function previewSettingsImport(path: string): Promise<AppConfig> {
+  return invoke<AppConfig>("preview_settings_import", { path });
+}
+
 export function resetSettings(): Promise<void> {
   return invoke("reset_settings");
 }
@@ -78,6 +78,7 @@ const STATUS_ORDER: { key: DownloadStatus; label: string }[] = [
   { key: "paused", label: "Paused" },
   { key: "queued", label: "Queued" },
   { key: "failed", label: "Failed" },
+  { key: "cancelled", label: "Cancelled" },
   { key: "completed", label: "Completed" },
 ];
 
@@ -137,7 +138,9 @@ export const DownloadStoreProvider: ParentComponent = (props) => {
 
     // Unsubscribe from downloads that are no longer active
     const terminalIds = new Set(
-      downloads.filter((d) => d.status === "completed" || d.status === "failed").map((d) => d.id),
+      downloads
+        .filter((d) => d.status === "completed" || d.status === "failed" || d.status === "cancelled")
+        .map((d) => d.id),
     );
     for (const [id] of activeChannels) {
       if (!activeIds.has(id)) {
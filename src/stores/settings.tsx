@@ -19,19 +19,46 @@ const defaultConfig: AppConfig = {
     notification_level: "all",
     language: "en",
     auto_update: true,
+    file_mode: null,
   },
   downloads: {
     default_connections: 8,
+    max_connections_per_host: null,
     max_concurrent: 3,
     bandwidth_limit: null,
     auto_resume: true,
     large_file_threshold: null,
+    retry_policy: {
+      max_attempts: 4,
+      base_delay_ms: 1000,
+      multiplier: 2,
+      max_delay_ms: 30000,
+      jitter: null,
+    },
+    temp_dir: null,
+    segmented_writes: false,
+    parallel_verify: false,
+    follow_html_refresh: false,
+    on_complete_command: null,
+    progress_interval_ms: 250,
+    speed_smoothing: 0.3,
+    min_chunk_size: 262144,
+    connection_stagger_ms: 0,
+    max_history: null,
+    delete_pruned_files: false,
+    max_file_size: null,
+    low_disk_space_threshold: null,
+    pause_on_low_disk_space: false,
+    auto_retry: { max_retries: 0, delay_secs: 60 },
   },
   file_organization: {
     auto_categorize: true,
     date_subfolders: false,
     duplicate_handling: "ask",
     category_folders: {},
+    sniff_category: false,
+    skip_if_downloaded: false,
+    dedup: "off",
   },
   network: {
     proxy: {
@@ -40,9 +67,19 @@ const defaultConfig: AppConfig = {
       port: null,
       username: null,
       password: null,
+      socks5_remote_dns: false,
     },
     user_agent: null,
+    user_agents: [],
     speed_schedule: [],
+    ipfs_gateway: null,
+    timeouts: {
+      connect_ms: null,
+      read_ms: null,
+    },
+    prefer_http2: false,
+    max_redirects: null,
+    ftp_mode: "passive",
   },
   appearance: {
     theme: "dark",
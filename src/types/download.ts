@@ -1,3 +1,5 @@
+import type { CollisionStrategy, FtpMode, RetryPolicy } from "./settings";
+
 export type DownloadStatus =
   | "pending"
   | "analyzing"
@@ -5,6 +7,7 @@ export type DownloadStatus =
   | "paused"
   | "completed"
   | "failed"
+  | "cancelled"
   | "queued";
 
 export type FileCategory =
@@ -16,6 +19,8 @@ export type FileCategory =
   | "software"
   | "other";
 
+export type Priority = "high" | "normal" | "low";
+
 export interface Download {
   id: string;
   url: string;
@@ -42,15 +47,79 @@ export interface Download {
   started_at: string | null;
   completed_at: string | null;
   updated_at: string;
+  scheduled_at: string | null;
+  final_url: string | null;
+  priority: Priority;
+  etag: string | null;
+  last_modified: string | null;
+  average_speed: number | null;
+  peak_speed: number | null;
+  auth_scheme: string | null;
+  content_hash: string | null;
+  mirrors: string | null;
+}
+
+export interface DownloadFilter {
+  status?: DownloadStatus;
+  category?: FileCategory;
+  domain?: string;
+  created_after?: string;
+  created_before?: string;
+}
+
+export interface DownloadPage {
+  downloads: Download[];
+  total: number;
 }
 
+export interface StatusCounts {
+  pending: number;
+  analyzing: number;
+  queued: number;
+  downloading: number;
+  paused: number;
+  completed: number;
+  failed: number;
+  cancelled: number;
+}
+
+export interface QueueStats {
+  counts: StatusCounts;
+  total_downloaded_bytes: number;
+  current_speed: number;
+}
+
+export type DownloadPhase = "downloading" | "merging" | "verifying";
+
 export interface DownloadProgress {
   download_id: string;
   downloaded_size: number;
   total_size: number | null;
   speed: number;
+  instant_speed: number;
   eta_seconds: number | null;
   connections: ConnectionProgress[];
+  phase: DownloadPhase;
+}
+
+export interface DownloadCompleted {
+  id: string;
+  final_path: string;
+  total_bytes: number;
+  hash_verified: boolean | null;
+  elapsed_ms: number;
+}
+
+export interface DownloadStatusChanged {
+  id: string;
+  from: DownloadStatus;
+  to: DownloadStatus;
+}
+
+export interface LowDiskSpace {
+  id: string;
+  available: number;
+  threshold: number;
 }
 
 export interface ConnectionProgress {
@@ -68,17 +137,55 @@ export interface UrlAnalysis {
   resumable: boolean;
   category: FileCategory;
   server: string | null;
+  etag: string | null;
+  last_modified: string | null;
+  accept_ranges: boolean;
+  http_version: string | null;
+  redirected_to: string | null;
+}
+
+export interface DownloadPreview extends UrlAnalysis {
+  connections: number;
+  multi_connection: boolean;
 }
 
+export type Auth =
+  | { type: "basic"; user: string; pass: string }
+  | { type: "bearer"; token: string };
+
 export interface DownloadOptions {
   save_path?: string;
   filename?: string;
   connections?: number;
   category?: FileCategory;
+  priority?: Priority;
   referrer?: string;
   cookies?: string;
   user_agent?: string;
   headers?: Record<string, string>;
+  auth?: Auth;
+  proxy?: string;
+  no_proxy?: string[];
+  verify_from_sidecar?: boolean;
+  retry_policy?: RetryPolicy;
+  temp_dir?: string;
+  ipfs_gateway?: string;
+  connect_timeout_ms?: number;
+  read_timeout_ms?: number;
+  segmented_writes?: boolean;
+  parallel_verify?: boolean;
+  follow_html_refresh?: boolean;
+  collision?: CollisionStrategy;
+  prefer_http2?: boolean;
+  ftp_mode?: FtpMode;
+  max_redirects?: number;
+  progress_interval_ms?: number;
+  speed_smoothing?: number;
+  min_chunk_size?: number;
+  connection_stagger_ms?: number;
+  file_mode?: number;
+  range?: [number, number | null];
+  skip_if_downloaded?: boolean;
 }
 
 export interface DiskSpace {
@@ -2,6 +2,9 @@
 
 export type NotificationLevel = "all" | "failedonly" | "never";
 export type DuplicateAction = "ask" | "rename" | "overwrite" | "skip";
+export type CollisionStrategy = "overwrite" | "rename" | "skip" | "error";
+export type FtpMode = "passive" | "active";
+export type DedupMode = "off" | "hardlink" | "symlink";
 export type ProxyMode = "none" | "system" | "http" | "socks5";
 export type Theme = "system" | "light" | "dark";
 export type FontSize = "small" | "default" | "large";
@@ -14,14 +17,45 @@ export interface GeneralConfig {
   notification_level: NotificationLevel;
   language: string;
   auto_update: boolean;
+  file_mode: number | null;
 }
 
 export interface DownloadsConfig {
   default_connections: number;
+  max_connections_per_host: number | null;
   max_concurrent: number;
   bandwidth_limit: number | null;
   auto_resume: boolean;
   large_file_threshold: number | null;
+  retry_policy: RetryPolicy;
+  temp_dir: string | null;
+  segmented_writes: boolean;
+  parallel_verify: boolean;
+  follow_html_refresh: boolean;
+  on_complete_command: string | null;
+  progress_interval_ms: number;
+  speed_smoothing: number;
+  min_chunk_size: number;
+  connection_stagger_ms: number;
+  max_history: number | null;
+  delete_pruned_files: boolean;
+  max_file_size: number | null;
+  low_disk_space_threshold: number | null;
+  pause_on_low_disk_space: boolean;
+  auto_retry: AutoRetryConfig;
+}
+
+export interface AutoRetryConfig {
+  max_retries: number;
+  delay_secs: number;
+}
+
+export interface RetryPolicy {
+  max_attempts: number;
+  base_delay_ms: number;
+  multiplier: number;
+  max_delay_ms: number;
+  jitter: number | null;
 }
 
 export interface FileOrgConfig {
@@ -29,6 +63,9 @@ export interface FileOrgConfig {
   date_subfolders: boolean;
   duplicate_handling: DuplicateAction;
   category_folders: Record<string, string>;
+  sniff_category: boolean;
+  skip_if_downloaded: boolean;
+  dedup: DedupMode;
 }
 
 export interface ProxyConfig {
@@ -37,18 +74,32 @@ export interface ProxyConfig {
   port: number | null;
   username: string | null;
   password: string | null;
+  no_proxy: string[];
+  socks5_remote_dns: boolean;
 }
 
 export interface SpeedScheduleEntry {
   start_hour: number;
   end_hour: number;
   limit: number | null;
+  pause: boolean;
 }
 
 export interface NetworkConfig {
   proxy: ProxyConfig;
   user_agent: string | null;
+  user_agents: string[];
   speed_schedule: SpeedScheduleEntry[];
+  ipfs_gateway: string | null;
+  timeouts: TimeoutConfig;
+  prefer_http2: boolean;
+  max_redirects: number | null;
+  ftp_mode: FtpMode;
+}
+
+export interface TimeoutConfig {
+  connect_ms: number | null;
+  read_ms: number | null;
 }
 
 export interface AppearanceConfig {